@@ -0,0 +1,105 @@
+//! Compares the old linear cosine scan with the flat and HNSW indexes.
+//! Run with `cargo bench -p faceguard_core --bench embedding_index`.
+
+use faceguard_core::recognition::{EmbeddingIndex, FlatIndex, HnswIndex, VectorKey};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const DIM: usize = 512;
+const QUERIES: usize = 200;
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+    }
+
+    fn vector(&mut self) -> Vec<f32> {
+        (0..DIM).map(|_| self.next()).collect()
+    }
+}
+
+/// What `IdentityDatabase::find_by_embedding` did before the index
+fn linear_scan(stored: &[Vec<f32>], query: &[f32]) -> Option<(usize, f32)> {
+    let mut best = None;
+    let mut best_similarity = f32::MIN;
+    for (i, emb) in stored.iter().enumerate() {
+        let dot: f32 = query.iter().zip(emb).map(|(x, y)| x * y).sum();
+        let mag_a: f32 = query.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let mag_b: f32 = emb.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let similarity = dot / (mag_a * mag_b);
+        if similarity > best_similarity {
+            best_similarity = similarity;
+            best = Some((i, similarity));
+        }
+    }
+    best
+}
+
+fn time(queries: &[Vec<f32>], mut f: impl FnMut(&[f32])) -> Duration {
+    let start = Instant::now();
+    for query in queries {
+        f(query);
+    }
+    start.elapsed() / queries.len() as u32
+}
+
+fn main() {
+    let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+
+    for size in [1_000, 5_000, 20_000] {
+        let stored: Vec<Vec<f32>> = (0..size).map(|_| rng.vector()).collect();
+        // Probes are noisy re-captures of enrolled faces
+        let queries: Vec<Vec<f32>> = (0..QUERIES)
+            .map(|i| {
+                let noise = rng.vector();
+                stored[i * size / QUERIES].iter().zip(noise).map(|(x, n)| x + 0.5 * n).collect()
+            })
+            .collect();
+
+        let mut flat = FlatIndex::new();
+        let mut hnsw = HnswIndex::default();
+        let build = Instant::now();
+        for (i, v) in stored.iter().enumerate() {
+            flat.insert(key(i), v).unwrap();
+        }
+        let flat_build = build.elapsed();
+        let build = Instant::now();
+        for (i, v) in stored.iter().enumerate() {
+            hnsw.insert(key(i), v).unwrap();
+        }
+        let hnsw_build = build.elapsed();
+
+        let scan = time(&queries, |q| {
+            black_box(linear_scan(&stored, q));
+        });
+        let flat_query = time(&queries, |q| {
+            black_box(flat.search(q, 5));
+        });
+        let hnsw_query = time(&queries, |q| {
+            black_box(hnsw.search(q, 5));
+        });
+
+        let recall = queries
+            .iter()
+            .filter(|q| flat.search(q, 1).first().map(|r| r.0) == hnsw.search(q, 1).first().map(|r| r.0))
+            .count() as f32
+            / QUERIES as f32;
+
+        println!("{size} x {DIM}-dim");
+        println!("  linear scan  {:>10.1?}/query", scan);
+        println!("  flat index   {:>10.1?}/query  (build {:.1?})", flat_query, flat_build);
+        println!("  hnsw index   {:>10.1?}/query  (build {:.1?}, top-1 recall {:.2})", hnsw_query, hnsw_build, recall);
+    }
+}
+
+fn key(i: usize) -> VectorKey {
+    VectorKey {
+        identity_id: i as u32,
+        sample_id: 1,
+    }
+}
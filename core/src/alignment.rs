@@ -0,0 +1,215 @@
+use crate::detection::LANDMARK_COUNT;
+use crate::frame::{Frame, PixelFormat};
+use anyhow::{bail, Result};
+
+/// Side length of aligned face crops
+pub const ALIGNED_SIZE: u32 = 112;
+
+/// Canonical five-point positions in a 112x112 crop, as used to train
+/// ArcFace and MobileFaceNet
+const ARCFACE_TEMPLATE: [(f32, f32); LANDMARK_COUNT] = [
+    (38.2946, 51.6963),
+    (73.5318, 51.5014),
+    (56.0252, 71.7366),
+    (41.5493, 92.3655),
+    (70.7299, 92.2041),
+];
+
+/// Warp the face described by five-point `landmarks` (see
+/// [`crate::detection::LANDMARK_COUNT`]) onto the canonical 112x112 `Rgb8`
+/// crop expected by embedding models.
+///
+/// Uses the least-squares similarity transform (rotation, uniform scale,
+/// translation) so the face isn't sheared; pixels outside the frame are black.
+pub fn align_face(frame: &Frame, landmarks: &[(f32, f32)]) -> Result<Frame> {
+    if landmarks.len() != LANDMARK_COUNT {
+        bail!("face alignment needs {} landmarks, got {}", LANDMARK_COUNT, landmarks.len());
+    }
+
+    if !spans_an_area(landmarks) {
+        bail!("landmarks are degenerate");
+    }
+    let Some(transform) = Similarity::estimate(landmarks, &ARCFACE_TEMPLATE) else {
+        bail!("landmarks are degenerate");
+    };
+
+    let size = ALIGNED_SIZE as usize;
+    let mut data = Vec::with_capacity(size * size * 3);
+    for v in 0..size {
+        for u in 0..size {
+            let (x, y) = transform.inverse(u as f32, v as f32);
+            data.extend_from_slice(&sample_bilinear(frame, x, y));
+        }
+    }
+
+    Frame::new(ALIGNED_SIZE, ALIGNED_SIZE, PixelFormat::Rgb8, data, frame.timestamp())
+}
+
+/// Whether finite `points` spread in two dimensions. Coincident or
+/// collinear landmarks still give a least-squares fit, but not a face.
+fn spans_an_area(points: &[(f32, f32)]) -> bool {
+    if points.iter().any(|p| !p.0.is_finite() || !p.1.is_finite()) {
+        return false;
+    }
+    let n = points.len() as f32;
+    let (mx, my) = points.iter().fold((0.0, 0.0), |(sx, sy), p| (sx + p.0 / n, sy + p.1 / n));
+    let (mut xx, mut yy, mut xy) = (0.0, 0.0, 0.0);
+    for (x, y) in points {
+        let (dx, dy) = (x - mx, y - my);
+        xx += dx * dx;
+        yy += dy * dy;
+        xy += dx * dy;
+    }
+    // The covariance determinant vanishes when the points lie on a line
+    xx * yy - xy * xy > 1e-4 * (xx + yy) * (xx + yy)
+}
+
+/// `dst = [a -b; b a] * src + (tx, ty)`
+#[derive(Debug, Clone, Copy)]
+struct Similarity {
+    a: f32,
+    b: f32,
+    tx: f32,
+    ty: f32,
+}
+
+impl Similarity {
+    /// Closed-form least squares fit (Umeyama) from `src` to `dst`
+    fn estimate(src: &[(f32, f32)], dst: &[(f32, f32)]) -> Option<Self> {
+        let n = src.len() as f32;
+        let mean = |points: &[(f32, f32)]| {
+            let (sx, sy) = points.iter().fold((0.0, 0.0), |(sx, sy), p| (sx + p.0, sy + p.1));
+            (sx / n, sy / n)
+        };
+        let (smx, smy) = mean(src);
+        let (dmx, dmy) = mean(dst);
+
+        let (mut dot, mut cross, mut norm) = (0.0, 0.0, 0.0);
+        for (s, d) in src.iter().zip(dst) {
+            let (sx, sy) = (s.0 - smx, s.1 - smy);
+            let (dx, dy) = (d.0 - dmx, d.1 - dmy);
+            dot += sx * dx + sy * dy;
+            cross += sx * dy - sy * dx;
+            norm += sx * sx + sy * sy;
+        }
+        if norm < f32::EPSILON {
+            return None;
+        }
+
+        let (a, b) = (dot / norm, cross / norm);
+        Some(Self {
+            a,
+            b,
+            tx: dmx - (a * smx - b * smy),
+            ty: dmy - (b * smx + a * smy),
+        })
+    }
+
+    /// Map a destination point back into the source image
+    fn inverse(&self, x: f32, y: f32) -> (f32, f32) {
+        let det = self.a * self.a + self.b * self.b;
+        let (x, y) = (x - self.tx, y - self.ty);
+        ((self.a * x + self.b * y) / det, (self.a * y - self.b * x) / det)
+    }
+}
+
+fn sample_bilinear(frame: &Frame, x: f32, y: f32) -> [u8; 3] {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+
+    let pixel = |dx: f32, dy: f32| {
+        let (px, py) = (x0 + dx, y0 + dy);
+        if px < 0.0 || py < 0.0 {
+            return [0.0; 3];
+        }
+        frame
+            .get_pixel(px as u32, py as u32)
+            .map(|p| [p.r as f32, p.g as f32, p.b as f32])
+            .unwrap_or([0.0; 3])
+    };
+
+    let (tl, tr, bl, br) = (pixel(0.0, 0.0), pixel(1.0, 0.0), pixel(0.0, 1.0), pixel(1.0, 1.0));
+    let mut out = [0u8; 3];
+    for c in 0..3 {
+        let top = tl[c] * (1.0 - fx) + tr[c] * fx;
+        let bottom = bl[c] * (1.0 - fx) + br[c] * fx;
+        out[c] = (top * (1.0 - fy) + bottom * fy).round() as u8;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: (f32, f32), b: (f32, f32), tolerance: f32) -> bool {
+        (a.0 - b.0).abs() < tolerance && (a.1 - b.1).abs() < tolerance
+    }
+
+    /// RGB frame whose red/green channels hold `x / step` and `y / step`
+    fn gradient(size: u32, step: u32) -> Frame {
+        let data = (0..size)
+            .flat_map(|y| (0..size).flat_map(move |x| [(x / step) as u8, (y / step) as u8, 0]))
+            .collect();
+        Frame::new(size, size, PixelFormat::Rgb8, data, 5).unwrap()
+    }
+
+    #[test]
+    fn template_landmarks_give_the_identity() {
+        let transform = Similarity::estimate(&ARCFACE_TEMPLATE, &ARCFACE_TEMPLATE).unwrap();
+        assert!((transform.a - 1.0).abs() < 1e-5 && transform.b.abs() < 1e-5);
+        assert!(transform.tx.abs() < 1e-3 && transform.ty.abs() < 1e-3);
+
+        let frame = gradient(ALIGNED_SIZE, 1);
+        let aligned = align_face(&frame, &ARCFACE_TEMPLATE).unwrap();
+        assert_eq!((aligned.width(), aligned.height(), aligned.timestamp()), (ALIGNED_SIZE, ALIGNED_SIZE, 5));
+        assert_eq!(aligned.data(), frame.data());
+    }
+
+    #[test]
+    fn recovers_rotation_and_scale() {
+        for (angle, scale, shift) in [(0.3f32, 2.0f32, (40.0, -10.0)), (-1.2, 0.5, (0.0, 300.0)), (std::f32::consts::PI, 1.0, (5.0, 5.0))] {
+            let (sin, cos) = angle.sin_cos();
+            let src: Vec<_> = ARCFACE_TEMPLATE
+                .iter()
+                .map(|&(x, y)| (scale * (cos * x - sin * y) + shift.0, scale * (sin * x + cos * y) + shift.1))
+                .collect();
+            let transform = Similarity::estimate(&src, &ARCFACE_TEMPLATE).unwrap();
+
+            // The fit undoes the rotation and scaling
+            assert!(((transform.a * transform.a + transform.b * transform.b).sqrt() - 1.0 / scale).abs() < 1e-4);
+            assert!((transform.b.atan2(transform.a) + angle).sin().abs() < 1e-4);
+            for (s, d) in src.iter().zip(&ARCFACE_TEMPLATE) {
+                let mapped = (transform.a * s.0 - transform.b * s.1 + transform.tx, transform.b * s.0 + transform.a * s.1 + transform.ty);
+                assert!(close(mapped, *d, 1e-2), "{:?} -> {:?}, want {:?}", s, mapped, d);
+                assert!(close(transform.inverse(d.0, d.1), *s, 1e-2));
+            }
+        }
+    }
+
+    #[test]
+    fn scaled_face_is_resampled() {
+        // A face twice the template size: crop pixel (u, v) comes from (2u, 2v)
+        let frame = gradient(2 * ALIGNED_SIZE, 1);
+        let landmarks: Vec<_> = ARCFACE_TEMPLATE.iter().map(|&(x, y)| (2.0 * x, 2.0 * y)).collect();
+        let aligned = align_face(&frame, &landmarks).unwrap();
+        for (u, v) in [(0, 0), (10, 90), (56, 56), (111, 111)] {
+            let p = aligned.get_pixel(u, v).unwrap();
+            assert!((p.r as i32 - 2 * u as i32).abs() <= 1 && (p.g as i32 - 2 * v as i32).abs() <= 1, "({}, {}): {:?}", u, v, p);
+        }
+    }
+
+    #[test]
+    fn degenerate_landmarks_are_rejected() {
+        let frame = gradient(64, 1);
+        let coincident = [(10.0, 10.0); LANDMARK_COUNT];
+        let collinear: Vec<_> = (0..LANDMARK_COUNT).map(|i| (5.0 + 7.0 * i as f32, 3.0 + 2.0 * i as f32)).collect();
+        let mut with_nan = ARCFACE_TEMPLATE.to_vec();
+        with_nan[2].0 = f32::NAN;
+
+        assert!(Similarity::estimate(&coincident, &ARCFACE_TEMPLATE).is_none());
+        for landmarks in [&coincident[..], &collinear, &with_nan, &ARCFACE_TEMPLATE[..4]] {
+            assert!(align_face(&frame, landmarks).is_err(), "{:?}", landmarks);
+        }
+    }
+}
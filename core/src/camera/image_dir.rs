@@ -0,0 +1,145 @@
+use super::FrameSource;
+use crate::frame::{Frame, PixelFormat};
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "pgm", "ppm"];
+
+/// Plays back a directory of still images in file-name order.
+///
+/// Frames are timestamped `index * frame_interval_ms`, so runs are repeatable.
+pub struct ImageDirSource {
+    dir: PathBuf,
+    frame_interval_ms: u64,
+    files: Vec<PathBuf>,
+    index: usize,
+    opened: bool,
+}
+
+impl ImageDirSource {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            frame_interval_ms: 100,
+            files: Vec::new(),
+            index: 0,
+            opened: false,
+        }
+    }
+
+    pub fn with_frame_interval(mut self, frame_interval_ms: u64) -> Self {
+        self.frame_interval_ms = frame_interval_ms;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+impl FrameSource for ImageDirSource {
+    fn open(&mut self) -> Result<()> {
+        let entries = std::fs::read_dir(&self.dir)
+            .with_context(|| format!("reading image directory {}", self.dir.display()))?;
+
+        let mut files = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let is_image = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|e| IMAGE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+                .unwrap_or(false);
+            if is_image {
+                files.push(path);
+            }
+        }
+
+        if files.is_empty() {
+            bail!("no images found in {}", self.dir.display());
+        }
+
+        files.sort();
+        self.files = files;
+        self.index = 0;
+        self.opened = true;
+        Ok(())
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Frame>> {
+        if !self.opened {
+            bail!("image source not opened");
+        }
+
+        let Some(path) = self.files.get(self.index) else {
+            return Ok(None);
+        };
+
+        let image = image::open(path)
+            .with_context(|| format!("decoding {}", path.display()))?
+            .to_rgb8();
+        let (width, height) = image.dimensions();
+        let timestamp = self.index as u64 * self.frame_interval_ms;
+        self.index += 1;
+
+        Frame::new(width, height, PixelFormat::Rgb8, image.into_raw(), timestamp).map(Some)
+    }
+
+    fn close(&mut self) {
+        self.files.clear();
+        self.index = 0;
+        self.opened = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image_dir(name: &str, files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("faceguard-images-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (i, file) in files.iter().enumerate() {
+            let path = dir.join(file);
+            if file.ends_with(".txt") {
+                std::fs::write(path, "not an image").unwrap();
+            } else {
+                image::RgbImage::from_pixel(3, 2, image::Rgb([i as u8, 0, 0])).save(path).unwrap();
+            }
+        }
+        dir
+    }
+
+    #[test]
+    fn plays_images_in_file_name_order() {
+        let dir = image_dir("order", &["frame_002.png", "notes.txt", "frame_000.PNG", "frame_001.png"]);
+        let mut source = ImageDirSource::new(&dir).with_frame_interval(40);
+        source.open().unwrap();
+        assert_eq!(source.len(), 3);
+
+        // Red channel holds the order the files were written in
+        let mut seen = Vec::new();
+        while let Some(frame) = source.next_frame().unwrap() {
+            assert_eq!((frame.width(), frame.height()), (3, 2));
+            seen.push((frame.data()[0], frame.timestamp()));
+        }
+        assert_eq!(seen, [(2, 0), (3, 40), (0, 80)]);
+    }
+
+    #[test]
+    fn empty_directory_is_an_error() {
+        let dir = image_dir("empty", &["readme.txt"]);
+        assert!(ImageDirSource::new(&dir).open().is_err());
+        assert!(ImageDirSource::new(dir.join("missing")).open().is_err());
+    }
+
+    #[test]
+    fn unopened_source_is_an_error() {
+        assert!(ImageDirSource::new("unused").next_frame().is_err());
+    }
+}
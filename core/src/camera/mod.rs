@@ -0,0 +1,38 @@
+use crate::frame::Frame;
+use anyhow::Result;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod image_dir;
+#[cfg(not(target_arch = "wasm32"))]
+mod playback;
+#[cfg(not(target_arch = "wasm32"))]
+mod raw;
+#[cfg(all(target_os = "linux", feature = "v4l2"))]
+mod v4l2;
+#[cfg(not(target_arch = "wasm32"))]
+mod y4m;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use image_dir::ImageDirSource;
+#[cfg(not(target_arch = "wasm32"))]
+pub use playback::{FrameRate, Pacing};
+#[cfg(not(target_arch = "wasm32"))]
+pub use raw::{RawVideoHeader, RawVideoSource};
+#[cfg(all(target_os = "linux", feature = "v4l2"))]
+pub use v4l2::V4l2Source;
+#[cfg(not(target_arch = "wasm32"))]
+pub use y4m::Y4mSource;
+
+/// A stream of frames from a camera, an image sequence or a recorded file.
+///
+/// The browser feeds frames from `getUserMedia` directly, so the native
+/// implementations here are what let the pipeline run headless.
+pub trait FrameSource {
+    fn open(&mut self) -> Result<()>;
+
+    /// Returns `Ok(None)` once the source is exhausted
+    fn next_frame(&mut self) -> Result<Option<Frame>>;
+
+    fn close(&mut self);
+}
+
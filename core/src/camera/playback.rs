@@ -0,0 +1,109 @@
+use anyhow::{bail, Context, Result};
+use std::time::{Duration, Instant};
+
+/// How quickly a recorded source hands out frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Pacing {
+    /// Sleep between frames to match the recording's frame rate
+    Realtime,
+    #[default]
+    AsFastAsPossible,
+}
+
+/// Rational frame rate, e.g. 30000/1001 for NTSC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameRate {
+    pub num: u32,
+    pub den: u32,
+}
+
+impl FrameRate {
+    pub fn new(num: u32, den: u32) -> Result<Self> {
+        if num == 0 || den == 0 {
+            bail!("invalid frame rate {}/{}", num, den);
+        }
+        Ok(Self { num, den })
+    }
+
+    /// Parses `30`, `30/1` or `30:1`
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.split_once(['/', ':']) {
+            Some((num, den)) => Self::new(
+                num.trim().parse().context("invalid frame rate numerator")?,
+                den.trim().parse().context("invalid frame rate denominator")?,
+            ),
+            None => Self::new(value.trim().parse().context("invalid frame rate")?, 1),
+        }
+    }
+
+    pub fn fps(&self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    /// Milliseconds from the first frame to frame `index`.
+    ///
+    /// Computed in integer arithmetic so replays of the same file always
+    /// produce the same timestamps.
+    pub fn timestamp(&self, index: u64) -> u64 {
+        index * 1000 * self.den as u64 / self.num as u64
+    }
+}
+
+impl Default for FrameRate {
+    fn default() -> Self {
+        Self { num: 25, den: 1 }
+    }
+}
+
+/// Sleeps so that frame timestamps line up with elapsed wall time
+#[derive(Debug, Default)]
+pub(crate) struct Pacer {
+    started: Option<Instant>,
+}
+
+impl Pacer {
+    pub fn reset(&mut self) {
+        self.started = None;
+    }
+
+    /// `offset_ms` is the frame's offset from the first frame of the stream
+    pub fn wait(&mut self, pacing: Pacing, offset_ms: u64) {
+        if pacing != Pacing::Realtime {
+            return;
+        }
+
+        let started = *self.started.get_or_insert_with(Instant::now);
+        let due = started + Duration::from_millis(offset_ms);
+        let now = Instant::now();
+        if due > now {
+            std::thread::sleep(due - now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_frame_rates() {
+        assert_eq!(FrameRate::parse("30").unwrap(), FrameRate { num: 30, den: 1 });
+        assert_eq!(FrameRate::parse("30000/1001").unwrap(), FrameRate { num: 30000, den: 1001 });
+        assert_eq!(FrameRate::parse("25:1").unwrap(), FrameRate { num: 25, den: 1 });
+        for bad in ["", "0", "30/0", "x/1", "30/"] {
+            assert!(FrameRate::parse(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn timestamps_are_exact_multiples() {
+        let rate = FrameRate::new(25, 1).unwrap();
+        assert_eq!((0..4).map(|i| rate.timestamp(i)).collect::<Vec<_>>(), [0, 40, 80, 120]);
+
+        // NTSC doesn't drift: frame 30000 lands exactly on 1001 s
+        let ntsc = FrameRate::new(30000, 1001).unwrap();
+        assert_eq!(ntsc.timestamp(1), 33);
+        assert_eq!(ntsc.timestamp(30000), 1_001_000);
+        assert!((1..1000).all(|i| ntsc.timestamp(i) > ntsc.timestamp(i - 1)));
+    }
+}
@@ -0,0 +1,196 @@
+use super::playback::{FrameRate, Pacer, Pacing};
+use super::FrameSource;
+use crate::frame::{Frame, PixelFormat};
+use anyhow::{bail, Context, Result};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Describes the frames in a headerless raw video file.
+///
+/// Read from a sidecar next to the video (`clip.rgb` -> `clip.rgb.hdr`) made
+/// of `key=value` lines:
+///
+/// ```text
+/// width=640
+/// height=480
+/// format=rgb24   # or i420
+/// fps=30/1
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawVideoHeader {
+    pub width: u32,
+    pub height: u32,
+    pub format: PixelFormat,
+    pub frame_rate: FrameRate,
+}
+
+impl RawVideoHeader {
+    pub fn parse(text: &str) -> Result<Self> {
+        let (mut width, mut height, mut format) = (None, None, None);
+        let mut frame_rate = FrameRate::default();
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .with_context(|| format!("invalid raw video header line: {}", line))?;
+            let value = value.trim();
+            match key.trim() {
+                "width" => width = Some(value.parse().context("invalid raw video width")?),
+                "height" => height = Some(value.parse().context("invalid raw video height")?),
+                "format" => {
+                    format = Some(match value.to_ascii_lowercase().as_str() {
+                        "rgb24" | "rgb" => PixelFormat::Rgb8,
+                        "i420" | "yuv420p" => PixelFormat::I420,
+                        other => bail!("unsupported raw video format {}", other),
+                    })
+                }
+                "fps" => frame_rate = FrameRate::parse(value)?,
+                other => bail!("unknown raw video header key {}", other),
+            }
+        }
+
+        match (width, height, format) {
+            (Some(width), Some(height), Some(format)) if width > 0 && height > 0 => Ok(Self {
+                width,
+                height,
+                format,
+                frame_rate,
+            }),
+            _ => bail!("raw video header needs width, height and format"),
+        }
+    }
+
+    pub fn sidecar_path(video: &Path) -> PathBuf {
+        let mut name = video.as_os_str().to_os_string();
+        name.push(".hdr");
+        PathBuf::from(name)
+    }
+}
+
+/// Plays back headerless RGB24 or I420 video, with timestamps derived from
+/// the frame index like [`super::Y4mSource`].
+pub struct RawVideoSource {
+    path: PathBuf,
+    header: Option<RawVideoHeader>,
+    reader: Option<BufReader<File>>,
+    pacing: Pacing,
+    pacer: Pacer,
+    start_timestamp: u64,
+    index: u64,
+}
+
+impl RawVideoSource {
+    /// The header is read from the sidecar file when the source is opened
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            header: None,
+            reader: None,
+            pacing: Pacing::default(),
+            pacer: Pacer::default(),
+            start_timestamp: 0,
+            index: 0,
+        }
+    }
+
+    /// Skip the sidecar lookup and use an explicit header
+    pub fn with_header(mut self, header: RawVideoHeader) -> Self {
+        self.header = Some(header);
+        self
+    }
+
+    pub fn with_pacing(mut self, pacing: Pacing) -> Self {
+        self.pacing = pacing;
+        self
+    }
+
+    pub fn with_start_timestamp(mut self, timestamp: u64) -> Self {
+        self.start_timestamp = timestamp;
+        self
+    }
+
+    pub fn header(&self) -> Option<RawVideoHeader> {
+        self.header
+    }
+}
+
+impl FrameSource for RawVideoSource {
+    fn open(&mut self) -> Result<()> {
+        if self.header.is_none() {
+            let sidecar = RawVideoHeader::sidecar_path(&self.path);
+            let text = std::fs::read_to_string(&sidecar)
+                .with_context(|| format!("reading raw video header {}", sidecar.display()))?;
+            self.header = Some(RawVideoHeader::parse(&text)?);
+        }
+
+        let file = File::open(&self.path)
+            .with_context(|| format!("opening {}", self.path.display()))?;
+        self.reader = Some(BufReader::new(file));
+        self.index = 0;
+        self.pacer.reset();
+        Ok(())
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Frame>> {
+        let (Some(reader), Some(header)) = (self.reader.as_mut(), self.header) else {
+            bail!("raw video source not opened");
+        };
+
+        // A clean end of file lands exactly on a frame boundary
+        if reader
+            .fill_buf()
+            .with_context(|| format!("reading raw frame {}", self.index))?
+            .is_empty()
+        {
+            return Ok(None);
+        }
+        let mut data = vec![0u8; header.format.frame_size(header.width, header.height)];
+        reader
+            .read_exact(&mut data)
+            .with_context(|| format!("truncated raw frame {}", self.index))?;
+
+        let offset = header.frame_rate.timestamp(self.index);
+        self.pacer.wait(self.pacing, offset);
+        self.index += 1;
+
+        Frame::new(header.width, header.height, header.format, data, self.start_timestamp + offset).map(Some)
+    }
+
+    fn close(&mut self) {
+        self.reader = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source_with(bytes: &[u8], name: &str) -> RawVideoSource {
+        let path = std::env::temp_dir().join(format!("faceguard-raw-{}-{}", std::process::id(), name));
+        std::fs::write(&path, bytes).unwrap();
+        let header = RawVideoHeader::parse("width=2\nheight=2\nformat=rgb24\nfps=30/1").unwrap();
+        let mut source = RawVideoSource::new(&path).with_header(header).with_pacing(Pacing::AsFastAsPossible);
+        source.open().unwrap();
+        source
+    }
+
+    #[test]
+    fn clean_end_of_file() {
+        let mut source = source_with(&[7u8; 24], "clean");
+        assert!(source.next_frame().unwrap().is_some());
+        assert!(source.next_frame().unwrap().is_some());
+        assert!(source.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_frame_is_an_error() {
+        let mut source = source_with(&[7u8; 17], "truncated");
+        assert!(source.next_frame().unwrap().is_some());
+        assert!(source.next_frame().is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use super::FrameSource;
+use crate::frame::{yuv_to_rgb, Frame, PixelFormat};
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use v4l::buffer::Type;
+use v4l::io::mmap::Stream;
+use v4l::io::traits::CaptureStream;
+use v4l::video::Capture;
+use v4l::{Device, FourCC};
+
+const BUFFER_COUNT: u32 = 4;
+
+/// Captures from a Video4Linux2 device such as `/dev/video0`.
+///
+/// YUYV, RGB24, greyscale and MJPEG device formats are converted to `Rgb8`
+/// (or `Gray8`) frames stamped with the wall-clock capture time.
+pub struct V4l2Source {
+    path: PathBuf,
+    requested: Option<(u32, u32)>,
+    device: Option<Device>,
+    stream: Option<Stream<'static>>,
+    format: Option<v4l::Format>,
+}
+
+impl V4l2Source {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            requested: None,
+            device: None,
+            stream: None,
+            format: None,
+        }
+    }
+
+    /// Ask the driver for a capture size; it may pick the closest supported one
+    pub fn with_resolution(mut self, width: u32, height: u32) -> Self {
+        self.requested = Some((width, height));
+        self
+    }
+}
+
+fn convert(format: &v4l::Format, buf: &[u8], timestamp: u64) -> Result<Frame> {
+    let (width, height) = (format.width, format.height);
+    let stride = format.stride as usize;
+
+    match &format.fourcc.repr {
+        b"YUYV" => {
+            let mut rgb = Vec::with_capacity(width as usize * height as usize * 3);
+            for row in pack_rows(buf, stride, width as usize * 2, height)?.chunks_exact(width as usize * 2) {
+                for px in row.chunks_exact(4) {
+                    let (y0, u, y1, v) = (px[0], px[1], px[2], px[3]);
+                    rgb.extend_from_slice(&yuv_to_rgb(y0, u, v));
+                    rgb.extend_from_slice(&yuv_to_rgb(y1, u, v));
+                }
+            }
+            Frame::new(width, height, PixelFormat::Rgb8, rgb, timestamp)
+        }
+        b"RGB3" => Frame::new(width, height, PixelFormat::Rgb8, pack_rows(buf, stride, width as usize * 3, height)?, timestamp),
+        b"GREY" => Frame::new(width, height, PixelFormat::Gray8, pack_rows(buf, stride, width as usize, height)?, timestamp),
+        b"MJPG" => {
+            let image = image::load_from_memory_with_format(buf, image::ImageFormat::Jpeg)
+                .context("decoding MJPEG frame")?
+                .to_rgb8();
+            let (w, h) = image.dimensions();
+            Frame::new(w, h, PixelFormat::Rgb8, image.into_raw(), timestamp)
+        }
+        other => bail!("unsupported V4L2 pixel format {}", String::from_utf8_lossy(other)),
+    }
+}
+
+impl FrameSource for V4l2Source {
+    fn open(&mut self) -> Result<()> {
+        let device = Device::with_path(&self.path)
+            .with_context(|| format!("opening {}", self.path.display()))?;
+
+        let mut format = device.format()?;
+        if let Some((width, height)) = self.requested {
+            format.width = width;
+            format.height = height;
+        }
+        // Prefer YUYV since nearly every UVC camera supports it uncompressed
+        format.fourcc = FourCC::new(b"YUYV");
+        let format = device.set_format(&format)?;
+
+        let stream = Stream::with_buffers(&device, Type::VideoCapture, BUFFER_COUNT)
+            .context("allocating V4L2 capture buffers")?;
+
+        self.format = Some(format);
+        self.stream = Some(stream);
+        self.device = Some(device);
+        Ok(())
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Frame>> {
+        let (Some(stream), Some(format)) = (self.stream.as_mut(), self.format) else {
+            bail!("V4L2 source not opened");
+        };
+
+        let (buf, meta) = stream.next()?;
+        let used = (meta.bytesused as usize).min(buf.len());
+        let data = buf[..used].to_vec();
+        convert(&format, &data, now_ms()).map(Some)
+    }
+
+    fn close(&mut self) {
+        // Dropping the stream issues STREAMOFF and unmaps the buffers
+        self.stream = None;
+        self.device = None;
+        self.format = None;
+    }
+}
+
+fn pack_rows(buf: &[u8], stride: usize, row_bytes: usize, height: u32) -> Result<Vec<u8>> {
+    let stride = stride.max(row_bytes);
+    let mut packed = Vec::with_capacity(row_bytes * height as usize);
+    for y in 0..height as usize {
+        packed.extend_from_slice(buf.get(y * stride..y * stride + row_bytes).context("short V4L2 buffer")?);
+    }
+    Ok(packed)
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
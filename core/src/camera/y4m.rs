@@ -0,0 +1,223 @@
+use super::playback::{FrameRate, Pacer, Pacing};
+use super::FrameSource;
+use crate::frame::{Frame, PixelFormat};
+use anyhow::{bail, Context, Result};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+const Y4M_MAGIC: &str = "YUV4MPEG2";
+
+/// Plays back a YUV4MPEG2 (`.y4m`) file.
+///
+/// Only 4:2:0 and mono streams are supported. Frame `n` is timestamped
+/// `start + n / fps` rather than with the wall clock, so replays are repeatable.
+pub struct Y4mSource {
+    path: PathBuf,
+    reader: Option<BufReader<File>>,
+    width: u32,
+    height: u32,
+    format: PixelFormat,
+    frame_rate: FrameRate,
+    pacing: Pacing,
+    pacer: Pacer,
+    start_timestamp: u64,
+    index: u64,
+}
+
+impl Y4mSource {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            reader: None,
+            width: 0,
+            height: 0,
+            format: PixelFormat::I420,
+            frame_rate: FrameRate::default(),
+            pacing: Pacing::default(),
+            pacer: Pacer::default(),
+            start_timestamp: 0,
+            index: 0,
+        }
+    }
+
+    pub fn with_pacing(mut self, pacing: Pacing) -> Self {
+        self.pacing = pacing;
+        self
+    }
+
+    /// Timestamp given to the first frame (defaults to 0)
+    pub fn with_start_timestamp(mut self, timestamp: u64) -> Self {
+        self.start_timestamp = timestamp;
+        self
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn frame_rate(&self) -> FrameRate {
+        self.frame_rate
+    }
+
+    fn parse_header(&mut self, line: &str) -> Result<()> {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() != Some(Y4M_MAGIC) {
+            bail!("{} is not a YUV4MPEG2 file", self.path.display());
+        }
+
+        let (mut width, mut height) = (0, 0);
+        for token in tokens {
+            let mut chars = token.chars();
+            let tag = chars.next();
+            let value = chars.as_str();
+            match tag {
+                Some('W') => width = value.parse().context("invalid Y4M width")?,
+                Some('H') => height = value.parse().context("invalid Y4M height")?,
+                Some('F') => self.frame_rate = FrameRate::parse(value)?,
+                Some('C') => {
+                    self.format = match value {
+                        "420" | "420jpeg" | "420paldv" | "420mpeg2" => PixelFormat::I420,
+                        "mono" => PixelFormat::Gray8,
+                        other => bail!("unsupported Y4M colorspace {}", other),
+                    }
+                }
+                // Interlacing, aspect ratio and extensions don't affect decoding
+                _ => {}
+            }
+        }
+
+        if width == 0 || height == 0 {
+            bail!("Y4M header is missing frame dimensions");
+        }
+        self.width = width;
+        self.height = height;
+        Ok(())
+    }
+}
+
+impl FrameSource for Y4mSource {
+    fn open(&mut self) -> Result<()> {
+        let file = File::open(&self.path)
+            .with_context(|| format!("opening {}", self.path.display()))?;
+        let mut reader = BufReader::new(file);
+
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        self.parse_header(header.trim_end())?;
+
+        self.reader = Some(reader);
+        self.index = 0;
+        self.pacer.reset();
+        Ok(())
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Frame>> {
+        let Some(reader) = self.reader.as_mut() else {
+            bail!("Y4M source not opened");
+        };
+
+        let mut marker = String::new();
+        if reader.read_line(&mut marker)? == 0 {
+            return Ok(None);
+        }
+        if !marker.starts_with("FRAME") {
+            bail!("corrupt Y4M stream at frame {}", self.index);
+        }
+
+        let mut data = vec![0u8; self.format.frame_size(self.width, self.height)];
+        reader
+            .read_exact(&mut data)
+            .with_context(|| format!("truncated Y4M frame {}", self.index))?;
+
+        let offset = self.frame_rate.timestamp(self.index);
+        self.pacer.wait(self.pacing, offset);
+        self.index += 1;
+
+        Frame::new(self.width, self.height, self.format, data, self.start_timestamp + offset).map(Some)
+    }
+
+    fn close(&mut self) {
+        self.reader = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source_with(bytes: &[u8], name: &str) -> Y4mSource {
+        let path = std::env::temp_dir().join(format!("faceguard-y4m-{}-{}.y4m", std::process::id(), name));
+        std::fs::write(&path, bytes).unwrap();
+        Y4mSource::new(&path).with_start_timestamp(1000)
+    }
+
+    fn stream(header: &str, frames: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = format!("{}\n", header).into_bytes();
+        for frame in frames {
+            bytes.extend_from_slice(b"FRAME\n");
+            bytes.extend_from_slice(frame);
+        }
+        bytes
+    }
+
+    #[test]
+    fn parses_header_and_frames() {
+        // 4x2 I420: 8 luma bytes, then 2x1 U and V planes
+        let first = [1, 2, 3, 4, 5, 6, 7, 8, 100, 101, 200, 201];
+        let second = [9u8; 12];
+        let mut source = source_with(&stream("YUV4MPEG2 W4 H2 F30000:1001 Ip A1:1 C420jpeg XYSCSS=420JPEG", &[&first, &second]), "frames");
+        source.open().unwrap();
+        assert_eq!((source.width(), source.height()), (4, 2));
+        assert_eq!(source.frame_rate(), FrameRate::new(30000, 1001).unwrap());
+
+        let frame = source.next_frame().unwrap().unwrap();
+        assert_eq!((frame.width(), frame.height(), frame.format()), (4, 2, PixelFormat::I420));
+        assert_eq!(frame.data(), &first);
+        assert_eq!(frame.timestamp(), 1000);
+        assert_eq!(source.next_frame().unwrap().unwrap().timestamp(), 1033);
+        assert!(source.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn mono_streams_are_gray() {
+        let mut source = source_with(&stream("YUV4MPEG2 W2 H2 F25:1 Cmono", &[&[0, 64, 128, 255]]), "mono");
+        source.open().unwrap();
+        let frame = source.next_frame().unwrap().unwrap();
+        assert_eq!(frame.format(), PixelFormat::Gray8);
+        assert_eq!(frame.data(), &[0, 64, 128, 255]);
+    }
+
+    #[test]
+    fn truncated_final_frame_is_an_error() {
+        let mut source = source_with(&stream("YUV4MPEG2 W2 H2 F25:1 Cmono", &[&[1, 2, 3, 4], &[5, 6]]), "truncated");
+        source.open().unwrap();
+        assert!(source.next_frame().unwrap().is_some());
+        let err = source.next_frame().unwrap_err();
+        assert!(format!("{:#}", err).contains("truncated Y4M frame 1"));
+    }
+
+    #[test]
+    fn bad_headers_are_rejected() {
+        for (name, header) in [
+            ("magic", "YUV4MPEG W2 H2"),
+            ("size", "YUV4MPEG2 W2 F25:1"),
+            ("colorspace", "YUV4MPEG2 W2 H2 C444"),
+            ("rate", "YUV4MPEG2 W2 H2 F25:0"),
+        ] {
+            let mut source = source_with(&stream(header, &[]), name);
+            assert!(source.open().is_err(), "{}", header);
+        }
+    }
+
+    #[test]
+    fn corrupt_frame_marker_is_an_error() {
+        let mut source = source_with(b"YUV4MPEG2 W2 H2 Cmono\nFRAMX\n\x01\x02\x03\x04", "marker");
+        source.open().unwrap();
+        assert!(source.next_frame().is_err());
+    }
+}
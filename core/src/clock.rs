@@ -0,0 +1,110 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Source of wall-clock time in milliseconds since the Unix epoch
+pub trait Clock: Debug + Send + Sync {
+    fn now_ms(&self) -> u64;
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+/// `SystemTime`, for native builds
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+}
+
+/// `Date.now()`; `SystemTime` is unavailable in the browser
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BrowserClock;
+
+#[cfg(target_arch = "wasm32")]
+impl Clock for BrowserClock {
+    fn now_ms(&self) -> u64 {
+        js_sys::Date::now() as u64
+    }
+}
+
+/// Time that only moves when told to, for tests and replaying recordings
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: AtomicU64,
+}
+
+impl ManualClock {
+    pub fn new(start_ms: u64) -> Self {
+        Self {
+            now: AtomicU64::new(start_ms),
+        }
+    }
+
+    pub fn set(&self, ms: u64) {
+        self.now.store(ms, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, ms: u64) {
+        self.now.fetch_add(ms, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_ms(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+/// The platform's real clock
+pub fn default_clock() -> SharedClock {
+    #[cfg(target_arch = "wasm32")]
+    {
+        Arc::new(BrowserClock)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Arc::new(SystemClock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detection::FaceDetection;
+    use crate::events::{EventLog, EventType};
+    use crate::recognition::IdentityDatabase;
+    use crate::tracking::Tracker;
+
+    #[test]
+    fn components_share_an_injected_clock() {
+        let manual = Arc::new(ManualClock::new(1_000));
+        let clock: SharedClock = manual.clone();
+        let mut events = EventLog::new(10).with_clock(clock.clone());
+        let mut db = IdentityDatabase::new().with_clock(clock.clone());
+        let mut tracker = Tracker::new(0.3, 500).with_confirmation(1, 1).with_clock(clock);
+
+        let identity = db.add_identity("a".into(), None).unwrap();
+        assert_eq!(identity.created_at, 1_000);
+        let face = FaceDetection::new(0, 0.0, 0.0, 10.0, 10.0, 0.9);
+        let track = tracker.update_now(vec![face.clone()]).remove(0);
+        assert_eq!(track.last_seen, 1_000);
+
+        manual.advance(400);
+        let event = events.add_event(EventType::FaceRecognized, "a".into(), 0.9, Some(track.track_id), Some(identity.id));
+        assert_eq!(event.timestamp, 1_400);
+        db.update_last_seen(identity.id);
+        assert_eq!(db.get(identity.id).unwrap().last_seen, 1_400);
+        assert_eq!(tracker.update_now(vec![face.clone()])[0].track_id, track.track_id);
+
+        // Past max_age on the manual clock the track is dropped
+        manual.set(5_000);
+        assert_ne!(tracker.update_now(vec![face])[0].track_id, track.track_id);
+    }
+}
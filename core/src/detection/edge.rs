@@ -0,0 +1,129 @@
+use super::{Detector, FaceDetection};
+use crate::frame::Frame;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EdgeDensityConfig {
+    pub grid_size: u32,         // cells per side
+    pub edge_threshold: f32,    // brightness difference that counts as an edge
+    pub density_threshold: f32, // fraction of edge pixels for a cell to count
+    pub min_region_cells: usize,
+}
+
+impl Default for EdgeDensityConfig {
+    fn default() -> Self {
+        Self {
+            grid_size: 8,
+            edge_threshold: 15.0,
+            density_threshold: 0.04,
+            min_region_cells: 2,
+        }
+    }
+}
+
+/// Fallback detector used when no ML model is available.
+///
+/// Splits the frame into a grid, marks cells with many high-contrast
+/// transitions (eyes, nose, mouth) and groups connected cells into face
+/// regions. Uniform areas like walls have low edge density and are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct EdgeDensityDetector {
+    config: EdgeDensityConfig,
+}
+
+impl EdgeDensityDetector {
+    pub fn new(config: EdgeDensityConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &EdgeDensityConfig {
+        &self.config
+    }
+}
+
+impl Detector for EdgeDensityDetector {
+    fn detect(&mut self, frame: &Frame) -> Vec<FaceDetection> {
+        let (width, height) = (frame.width(), frame.height());
+        let grid_size = self.config.grid_size.max(1);
+
+        let mut detections = Vec::new();
+        let mut detection_id = 1;
+
+        let step_x = (width / grid_size).max(1);
+        let step_y = (height / grid_size).max(1);
+
+        let mut edge_cells = Vec::new();
+        for gy in 0..grid_size {
+            for gx in 0..grid_size {
+                let Some(cell) = frame.region(gx * step_x, gy * step_y, step_x, step_y) else {
+                    continue;
+                };
+
+                let edge_density = cell.edge_density(self.config.edge_threshold);
+                if edge_density > self.config.density_threshold {
+                    edge_cells.push((gx, gy, edge_density));
+                }
+            }
+        }
+
+        // Group connected high-edge cells into face regions
+        let densities: HashMap<(u32, u32), f32> = edge_cells.iter().map(|&(gx, gy, d)| ((gx, gy), d)).collect();
+        let mut visited = HashSet::new();
+
+        for (gx, gy, _) in edge_cells.iter() {
+            if visited.contains(&(*gx, *gy)) {
+                continue;
+            }
+
+            // Flood fill to find connected region
+            let mut region = Vec::new();
+            let mut queue = vec![(*gx, *gy)];
+
+            while let Some((cx, cy)) = queue.pop() {
+                if visited.contains(&(cx, cy)) || cx >= grid_size || cy >= grid_size {
+                    continue;
+                }
+                visited.insert((cx, cy));
+                region.push((cx, cy));
+
+                // Check 4 neighbors
+                for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                    let (Some(nx), Some(ny)) = (cx.checked_add_signed(dx), cy.checked_add_signed(dy)) else {
+                        continue;
+                    };
+                    // Check if neighbor is also high edge
+                    if nx < grid_size && ny < grid_size && !visited.contains(&(nx, ny)) && densities.contains_key(&(nx, ny)) {
+                        queue.push((nx, ny));
+                    }
+                }
+            }
+
+            // If region is large enough, treat as a face
+            if region.len() >= self.config.min_region_cells {
+                let min_x = region.iter().map(|(x, _)| *x).min().unwrap();
+                let min_y = region.iter().map(|(_, y)| *y).min().unwrap();
+                let max_x = region.iter().map(|(x, _)| *x).max().unwrap();
+                let max_y = region.iter().map(|(_, y)| *y).max().unwrap();
+
+                let x = (min_x * step_x) as f32;
+                let y = (min_y * step_y) as f32;
+                let w = ((max_x - min_x + 1) * step_x) as f32;
+                let h = ((max_y - min_y + 1) * step_y) as f32;
+
+                let avg_density = region
+                    .iter()
+                    .map(|cell| densities.get(cell).copied().unwrap_or(0.0))
+                    .sum::<f32>()
+                    / region.len() as f32;
+
+                let confidence = (avg_density * 1.5).clamp(0.4, 0.95);
+
+                detections.push(FaceDetection::new(detection_id, x, y, w, h, confidence));
+                detection_id += 1;
+            }
+        }
+
+        detections
+    }
+}
@@ -0,0 +1,125 @@
+use crate::frame::Frame;
+use serde::{Deserialize, Serialize};
+
+mod edge;
+#[cfg(feature = "onnx")]
+mod onnx;
+
+pub use edge::{EdgeDensityConfig, EdgeDensityDetector};
+#[cfg(feature = "onnx")]
+pub use onnx::{FaceModel, OnnxDetectorConfig, OnnxFaceDetector};
+
+/// Number of facial landmarks carried on a detection: left eye, right eye,
+/// nose tip, left mouth corner, right mouth corner (left/right as they
+/// appear in the image)
+pub const LANDMARK_COUNT: usize = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaceDetection {
+    pub id: u32,
+    pub bbox: (f32, f32, f32, f32), // (x, y, w, h)
+    pub confidence: f32,
+    pub landmarks: Option<Vec<(f32, f32)>>, // Optional facial landmarks
+}
+
+impl FaceDetection {
+    pub fn new(id: u32, x: f32, y: f32, w: f32, h: f32, confidence: f32) -> Self {
+        Self {
+            id,
+            bbox: (x, y, w, h),
+            confidence,
+            landmarks: None,
+        }
+    }
+
+    pub fn area(&self) -> f32 {
+        self.bbox.2 * self.bbox.3
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (self.bbox.0 + self.bbox.2 / 2.0, self.bbox.1 + self.bbox.3 / 2.0)
+    }
+
+    /// Calculate Intersection over Union with another detection
+    pub fn iou(&self, other: &FaceDetection) -> f32 {
+        let (x1, y1, w1, h1) = self.bbox;
+        let (x2, y2, w2, h2) = other.bbox;
+
+        let x_left = x1.max(x2);
+        let y_top = y1.max(y2);
+        let x_right = (x1 + w1).min(x2 + w2);
+        let y_bottom = (y1 + h1).min(y2 + h2);
+
+        if x_right < x_left || y_bottom < y_top {
+            return 0.0;
+        }
+
+        let intersection = (x_right - x_left) * (y_bottom - y_top);
+        let union = self.area() + other.area() - intersection;
+
+        intersection / union
+    }
+}
+
+/// Convert BlazeFace's six keypoints (eyes, nose tip, mouth centre, ears) to
+/// the five-point layout. BlazeFace has no mouth corners, so they are placed
+/// either side of the mouth centre along the eye line, at the spacing of the
+/// ArcFace template.
+pub fn five_point_from_blazeface(keypoints: &[(f32, f32)]) -> Option<Vec<(f32, f32)>> {
+    if keypoints.len() < 4 {
+        return None;
+    }
+
+    let (mut left_eye, mut right_eye) = (keypoints[0], keypoints[1]);
+    if left_eye.0 > right_eye.0 {
+        std::mem::swap(&mut left_eye, &mut right_eye);
+    }
+    let (nose, mouth) = (keypoints[2], keypoints[3]);
+
+    // Mouth corners are ~0.83x the inter-ocular distance apart
+    let half = ((right_eye.0 - left_eye.0) * 0.414, (right_eye.1 - left_eye.1) * 0.414);
+    Some(vec![
+        left_eye,
+        right_eye,
+        nose,
+        (mouth.0 - half.0, mouth.1 - half.1),
+        (mouth.0 + half.0, mouth.1 + half.1),
+    ])
+}
+
+/// Finds faces in a single frame. Detectors may keep state between frames
+/// (model sessions, scratch buffers), hence `&mut self`.
+pub trait Detector {
+    fn detect(&mut self, frame: &Frame) -> Vec<FaceDetection>;
+}
+
+/// Process detections with NMS (Non-Maximum Suppression)
+pub fn apply_nms(mut detections: Vec<FaceDetection>, iou_threshold: f32) -> Vec<FaceDetection> {
+    detections.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    
+    let mut keep = Vec::new();
+    while !detections.is_empty() {
+        let best = detections.remove(0);
+        keep.push(best.clone());
+        
+        detections.retain(|det| best.iou(det) < iou_threshold);
+    }
+    keep
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nms_tolerates_nan_confidence() {
+        let detections = vec![
+            FaceDetection::new(0, 0.0, 0.0, 10.0, 10.0, 0.5),
+            FaceDetection::new(1, 1.0, 1.0, 10.0, 10.0, f32::NAN),
+            FaceDetection::new(2, 50.0, 50.0, 10.0, 10.0, 0.9),
+        ];
+        let kept = apply_nms(detections, 0.3);
+        assert_eq!(kept.len(), 2);
+        assert!(kept.iter().any(|d| d.id == 2));
+    }
+}
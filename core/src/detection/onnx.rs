@@ -0,0 +1,351 @@
+use super::{apply_nms, five_point_from_blazeface, Detector, FaceDetection};
+use crate::frame::Frame;
+use crate::onnx::{OnnxModel, TensorLayout};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FaceModel {
+    /// MediaPipe BlazeFace short-range, 128x128 input, six keypoints
+    BlazeFaceShortRange,
+    /// UltraFace RFB-320 (version-RFB-320.onnx), 320x240 input, boxes only
+    UltraFaceRfb320,
+}
+
+impl FaceModel {
+    pub fn input_size(&self) -> (u32, u32) {
+        match self {
+            FaceModel::BlazeFaceShortRange => (128, 128),
+            FaceModel::UltraFaceRfb320 => (320, 240),
+        }
+    }
+
+    /// `(mean, std)` normalisation of 0-255 inputs
+    fn normalization(&self) -> (f32, f32) {
+        match self {
+            FaceModel::BlazeFaceShortRange => (127.5, 127.5),
+            FaceModel::UltraFaceRfb320 => (127.0, 128.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OnnxDetectorConfig {
+    pub score_threshold: f32,
+    pub nms_threshold: f32,
+    pub layout: TensorLayout,
+}
+
+impl Default for OnnxDetectorConfig {
+    fn default() -> Self {
+        Self {
+            score_threshold: 0.5,
+            nms_threshold: 0.3,
+            layout: TensorLayout::Nchw,
+        }
+    }
+}
+
+/// Face detector running a local ONNX model on the CPU.
+///
+/// BlazeFace detections carry five-point `landmarks` derived from its
+/// keypoints; UltraFace has none.
+pub struct OnnxFaceDetector {
+    model: OnnxModel,
+    kind: FaceModel,
+    config: OnnxDetectorConfig,
+    anchors: Vec<(f32, f32)>,
+    failures: u64,
+    last_error: Option<String>,
+}
+
+impl OnnxFaceDetector {
+    pub fn from_path(path: impl AsRef<Path>, kind: FaceModel, config: OnnxDetectorConfig) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        Self::from_bytes(&bytes, kind, config)
+    }
+
+    pub fn from_bytes(bytes: &[u8], kind: FaceModel, config: OnnxDetectorConfig) -> Result<Self> {
+        let (width, height) = kind.input_size();
+        let model = OnnxModel::load(bytes, width, height, config.layout)?;
+        let anchors = match kind {
+            FaceModel::BlazeFaceShortRange => blazeface_anchors(),
+            FaceModel::UltraFaceRfb320 => Vec::new(),
+        };
+
+        Ok(Self {
+            model,
+            kind,
+            config,
+            anchors,
+            failures: 0,
+            last_error: None,
+        })
+    }
+
+    pub fn kind(&self) -> FaceModel {
+        self.kind
+    }
+
+    /// How many [`Detector::detect`] calls failed and returned no faces
+    pub fn failures(&self) -> u64 {
+        self.failures
+    }
+
+    /// The most recent failure seen by [`Detector::detect`]
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// Like [`Detector::detect`] but reports inference failures
+    pub fn try_detect(&mut self, frame: &Frame) -> Result<Vec<FaceDetection>> {
+        let (input, letterbox) = self.model.letterbox(frame)?;
+        let (mean, std) = self.kind.normalization();
+        let outputs = self.model.run(&input, mean, std)?;
+
+        let (in_w, in_h) = (self.model.width() as f32, self.model.height() as f32);
+        let to_frame = |x: f32, y: f32| letterbox.to_frame(x * in_w, y * in_h);
+
+        let threshold = self.config.score_threshold;
+        let candidates = match self.kind {
+            FaceModel::BlazeFaceShortRange => decode_blazeface(&outputs, &self.anchors, in_w, threshold, to_frame)?,
+            FaceModel::UltraFaceRfb320 => decode_ultraface(&outputs, threshold, to_frame)?,
+        };
+
+        let mut detections = apply_nms(candidates, self.config.nms_threshold);
+        for (i, det) in detections.iter_mut().enumerate() {
+            det.id = i as u32 + 1;
+        }
+        Ok(detections)
+    }
+}
+
+impl Detector for OnnxFaceDetector {
+    /// Failures come back as no faces; check `failures` and `last_error`
+    /// to tell a broken model from an empty scene
+    fn detect(&mut self, frame: &Frame) -> Vec<FaceDetection> {
+        self.try_detect(frame).unwrap_or_else(|err| {
+            self.failures += 1;
+            self.last_error = Some(format!("{:#}", err));
+            Vec::new()
+        })
+    }
+}
+
+/// Decode BlazeFace regressors against `anchors`. Offsets are in input
+/// pixels of a square `input_size` model; `to_frame` maps normalised input
+/// coordinates back to the frame.
+fn decode_blazeface(
+    outputs: &[(Vec<usize>, Vec<f32>)],
+    anchors: &[(f32, f32)],
+    input_size: f32,
+    threshold: f32,
+    to_frame: impl Fn(f32, f32) -> (f32, f32),
+) -> Result<Vec<FaceDetection>> {
+    let regressors = find_output(outputs, 16).context("BlazeFace regressor output not found")?;
+    let scores = find_output(outputs, 1).context("BlazeFace score output not found")?;
+    if regressors.len() != anchors.len() * 16 || scores.len() != anchors.len() {
+        bail!("BlazeFace outputs don't match {} anchors", anchors.len());
+    }
+
+    let mut detections = Vec::new();
+    for (i, &(ax, ay)) in anchors.iter().enumerate() {
+        let score = sigmoid(scores[i].clamp(-100.0, 100.0));
+        if score.is_nan() || score < threshold {
+            continue;
+        }
+
+        // Offsets are in input pixels relative to the anchor centre
+        let raw = &regressors[i * 16..(i + 1) * 16];
+        let cx = ax + raw[0] / input_size;
+        let cy = ay + raw[1] / input_size;
+        let (w, h) = (raw[2] / input_size, raw[3] / input_size);
+
+        let (x1, y1) = to_frame(cx - w / 2.0, cy - h / 2.0);
+        let (x2, y2) = to_frame(cx + w / 2.0, cy + h / 2.0);
+        let mut det = FaceDetection::new(0, x1, y1, x2 - x1, y2 - y1, score);
+        let keypoints: Vec<_> = (0..6)
+            .map(|k| to_frame(ax + raw[4 + k * 2] / input_size, ay + raw[5 + k * 2] / input_size))
+            .collect();
+        det.landmarks = five_point_from_blazeface(&keypoints);
+        if is_finite(&det) {
+            detections.push(det);
+        }
+    }
+    Ok(detections)
+}
+
+/// Decode UltraFace scores and corner boxes, both normalised to the input
+fn decode_ultraface(
+    outputs: &[(Vec<usize>, Vec<f32>)],
+    threshold: f32,
+    to_frame: impl Fn(f32, f32) -> (f32, f32),
+) -> Result<Vec<FaceDetection>> {
+    let scores = find_output(outputs, 2).context("UltraFace score output not found")?;
+    let boxes = find_output(outputs, 4).context("UltraFace box output not found")?;
+    if scores.len() / 2 != boxes.len() / 4 {
+        bail!("UltraFace score and box outputs differ in length");
+    }
+
+    let mut detections = Vec::new();
+    for (score, bbox) in scores.chunks_exact(2).zip(boxes.chunks_exact(4)) {
+        // Column 1 is the face class, already softmaxed by the model
+        let score = score[1];
+        if score.is_nan() || score < threshold {
+            continue;
+        }
+
+        let (x1, y1) = to_frame(bbox[0], bbox[1]);
+        let (x2, y2) = to_frame(bbox[2], bbox[3]);
+        let det = FaceDetection::new(0, x1, y1, x2 - x1, y2 - y1, score);
+        if is_finite(&det) {
+            detections.push(det);
+        }
+    }
+    Ok(detections)
+}
+
+/// Whether a decoded box and its landmarks are usable; a model fed odd
+/// input can produce NaN or infinite coordinates
+fn is_finite(det: &FaceDetection) -> bool {
+    let (x, y, w, h) = det.bbox;
+    [x, y, w, h].iter().all(|v| v.is_finite())
+        && det
+            .landmarks
+            .iter()
+            .flatten()
+            .all(|(lx, ly)| lx.is_finite() && ly.is_finite())
+}
+
+/// The output whose innermost dimension is `last_dim`
+fn find_output(outputs: &[(Vec<usize>, Vec<f32>)], last_dim: usize) -> Option<&[f32]> {
+    outputs
+        .iter()
+        .find(|(shape, _)| shape.last() == Some(&last_dim))
+        .map(|(_, values)| values.as_slice())
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// SSD anchor centres for BlazeFace short-range, normalised to 0..1.
+///
+/// Matches MediaPipe's `SsdAnchorsCalculator` with strides [8, 16, 16, 16],
+/// two anchors per layer and fixed anchor size: 16x16x2 + 8x8x6 = 896.
+fn blazeface_anchors() -> Vec<(f32, f32)> {
+    const INPUT_SIZE: usize = 128;
+    let mut anchors = Vec::with_capacity(896);
+    for (stride, per_cell) in [(8, 2), (16, 6)] {
+        let grid = INPUT_SIZE / stride;
+        for y in 0..grid {
+            for x in 0..grid {
+                let cx = (x as f32 + 0.5) / grid as f32;
+                let cy = (y as f32 + 0.5) / grid as f32;
+                anchors.extend(std::iter::repeat_n((cx, cy), per_cell));
+            }
+        }
+    }
+    anchors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEVER: f32 = -100.0;
+
+    fn blazeface_outputs(anchors: usize) -> (Vec<f32>, Vec<f32>) {
+        (vec![0.0; anchors * 16], vec![NEVER; anchors])
+    }
+
+    fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+        (a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3
+    }
+
+    #[test]
+    fn blazeface_has_896_anchors() {
+        let anchors = blazeface_anchors();
+        assert_eq!(anchors.len(), 896);
+        // 16x16 grid, two per cell, then 8x8 with six per cell
+        assert_eq!(anchors[0], (0.5 / 16.0, 0.5 / 16.0));
+        assert_eq!(anchors[1], anchors[0]);
+        assert_eq!(anchors[2], (1.5 / 16.0, 0.5 / 16.0));
+        assert_eq!(anchors[511], (15.5 / 16.0, 15.5 / 16.0));
+        assert_eq!(anchors[512], (0.5 / 8.0, 0.5 / 8.0));
+        assert_eq!(anchors[895], (7.5 / 8.0, 7.5 / 8.0));
+        assert!(anchors.iter().all(|&(x, y)| (0.0..1.0).contains(&x) && (0.0..1.0).contains(&y)));
+    }
+
+    #[test]
+    fn blazeface_decodes_boxes_and_landmarks() {
+        let anchors = blazeface_anchors();
+        let (mut regressors, mut scores) = blazeface_outputs(anchors.len());
+        let i = 600;
+        let (ax, ay) = anchors[i];
+        scores[i] = 4.0;
+        // Centre 12.8 px right of the anchor, 25.6 px square
+        let keypoints = [(-6.4, -6.4), (6.4, -6.4), (0.0, 0.0), (0.0, 6.4), (-12.8, 0.0), (12.8, 0.0)];
+        let mut raw = vec![12.8, 0.0, 25.6, 25.6];
+        raw.extend(keypoints.iter().flat_map(|&(x, y)| [x, y]));
+        regressors[i * 16..(i + 1) * 16].copy_from_slice(&raw);
+
+        let outputs = vec![(vec![1, 896, 16], regressors), (vec![1, 896, 1], scores)];
+        // A 1280x1280 frame letterboxed into the 128 px input
+        let to_frame = |x: f32, y: f32| (x * 1280.0, y * 1280.0);
+        let detections = decode_blazeface(&outputs, &anchors, 128.0, 0.5, to_frame).unwrap();
+        assert_eq!(detections.len(), 1);
+
+        let det = &detections[0];
+        assert!((det.confidence - sigmoid(4.0)).abs() < 1e-6);
+        let (x, y, w, h) = det.bbox;
+        assert!(close((x, y), ((ax + 0.1 - 0.1) * 1280.0, (ay - 0.1) * 1280.0)), "{:?}", det.bbox);
+        assert!(close((w, h), (256.0, 256.0)), "{:?}", det.bbox);
+
+        let landmarks = det.landmarks.as_ref().unwrap();
+        assert_eq!(landmarks.len(), 5);
+        let at = |dx: f32, dy: f32| ((ax + dx / 128.0) * 1280.0, (ay + dy / 128.0) * 1280.0);
+        assert!(close(landmarks[0], at(-6.4, -6.4)));
+        assert!(close(landmarks[1], at(6.4, -6.4)));
+        assert!(close(landmarks[2], at(0.0, 0.0)));
+        // Mouth corners either side of the mouth centre along the eye line
+        assert!(close(landmarks[3], at(-12.8 * 0.414, 6.4)));
+        assert!(close(landmarks[4], at(12.8 * 0.414, 6.4)));
+    }
+
+    #[test]
+    fn blazeface_skips_nan_scores_and_boxes() {
+        let anchors = blazeface_anchors();
+        let (mut regressors, mut scores) = blazeface_outputs(anchors.len());
+        scores[10] = f32::NAN;
+        scores[20] = 4.0;
+        regressors[20 * 16] = f32::NAN;
+        scores[30] = 4.0;
+        regressors[30 * 16 + 7] = f32::INFINITY;
+        let outputs = vec![(vec![1, 896, 16], regressors), (vec![1, 896, 1], scores)];
+        let detections = decode_blazeface(&outputs, &anchors, 128.0, 0.5, |x, y| (x, y)).unwrap();
+        assert!(detections.is_empty());
+    }
+
+    #[test]
+    fn blazeface_rejects_mismatched_outputs() {
+        let anchors = blazeface_anchors();
+        let outputs = vec![(vec![1, 100, 16], vec![0.0; 1600]), (vec![1, 100, 1], vec![0.0; 100])];
+        assert!(decode_blazeface(&outputs, &anchors, 128.0, 0.5, |x, y| (x, y)).is_err());
+        assert!(decode_blazeface(&outputs[..1], &anchors, 128.0, 0.5, |x, y| (x, y)).is_err());
+    }
+
+    #[test]
+    fn ultraface_decodes_corner_boxes() {
+        let scores = vec![0.9, 0.1, 0.2, 0.8, 0.0, f32::NAN, 0.3, 0.7];
+        let boxes = vec![0.0, 0.0, 1.0, 1.0, 0.25, 0.5, 0.75, 1.0, 0.0, 0.0, 1.0, 1.0, f32::NAN, 0.0, 1.0, 1.0];
+        let outputs = vec![(vec![1, 4, 2], scores), (vec![1, 4, 4], boxes)];
+        let detections = decode_ultraface(&outputs, 0.5, |x, y| (x * 320.0, y * 240.0)).unwrap();
+        assert_eq!(detections.len(), 1);
+        assert_eq!(detections[0].bbox, (80.0, 120.0, 160.0, 120.0));
+        assert_eq!(detections[0].confidence, 0.8);
+        assert!(detections[0].landmarks.is_none());
+    }
+}
@@ -0,0 +1,208 @@
+use crate::clock::{default_clock, SharedClock};
+use serde::{Deserialize, Serialize};
+
+mod snapshot;
+mod timeline;
+
+pub use snapshot::{capture_snapshot, frame_snapshot_id, snapshot_id, SnapshotPolicy};
+pub use timeline::{collapse_runs, group_by_day_hour, DayGroup, EventRun, HourGroup, LocalTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventType {
+    FaceDetected,
+    FaceRecognized,
+    UnknownFace,
+    LowConfidence,
+    Blacklisted,
+    AfterHours,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaceEvent {
+    pub id: u32,
+    pub event_type: EventType,
+    pub name: String,
+    pub confidence: f32,
+    pub timestamp: u64,
+    pub track_id: Option<u32>,
+    #[serde(default)]
+    pub identity_id: Option<u32>,
+    /// Id of the snapshot taken when the event fired, see `Store::get_snapshot`
+    #[serde(default)]
+    pub snapshot: Option<String>,
+}
+
+impl FaceEvent {
+    pub fn new(id: u32, event_type: EventType, name: String, confidence: f32, timestamp: u64) -> Self {
+        Self {
+            id,
+            event_type,
+            name,
+            confidence,
+            timestamp,
+            track_id: None,
+            identity_id: None,
+            snapshot: None,
+        }
+    }
+
+    pub fn with_track(mut self, track_id: u32) -> Self {
+        self.track_id = Some(track_id);
+        self
+    }
+
+    pub fn with_identity(mut self, identity_id: u32) -> Self {
+        self.identity_id = Some(identity_id);
+        self
+    }
+
+    pub fn with_snapshot(mut self, snapshot: String) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventLog {
+    events: Vec<FaceEvent>,
+    next_id: u32,
+    max_events: usize,
+    #[serde(skip, default = "default_clock")]
+    clock: SharedClock,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new(1000)
+    }
+}
+
+impl EventLog {
+    pub fn new(max_events: usize) -> Self {
+        Self {
+            events: Vec::new(),
+            next_id: 1,
+            max_events,
+            clock: default_clock(),
+        }
+    }
+
+    /// Replace the clock events are stamped with, e.g. after loading
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    pub fn add_event(
+        &mut self,
+        event_type: EventType,
+        name: String,
+        confidence: f32,
+        track_id: Option<u32>,
+        identity_id: Option<u32>,
+    ) -> FaceEvent {
+        let timestamp = self.clock.now_ms();
+        self.add_event_at(event_type, name, confidence, track_id, identity_id, timestamp)
+    }
+
+    /// `add_event` stamped with the frame's own timestamp, so replaying a
+    /// recording gives the same log every run
+    pub fn add_event_at(
+        &mut self,
+        event_type: EventType,
+        name: String,
+        confidence: f32,
+        track_id: Option<u32>,
+        identity_id: Option<u32>,
+        timestamp: u64,
+    ) -> FaceEvent {
+        let mut event = FaceEvent::new(self.next_id, event_type, name, confidence, timestamp);
+        self.next_id += 1;
+        
+        if let Some(tid) = track_id {
+            event = event.with_track(tid);
+        }
+        if let Some(iid) = identity_id {
+            event = event.with_identity(iid);
+        }
+        
+        self.events.push(event.clone());
+        
+        // Keep only recent events
+        if self.events.len() > self.max_events {
+            self.events.drain(0..self.events.len() - self.max_events);
+        }
+        
+        event
+    }
+
+    pub fn get_all(&self) -> Vec<FaceEvent> {
+        self.events.clone()
+    }
+
+    pub fn get_recent(&self, count: usize) -> Vec<FaceEvent> {
+        let start = if self.events.len() > count {
+            self.events.len() - count
+        } else {
+            0
+        };
+        self.events[start..].to_vec()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Record the snapshot taken for an event already in the log
+    pub fn set_snapshot(&mut self, event_id: u32, snapshot: String) {
+        if let Some(event) = self.events.iter_mut().rev().find(|e| e.id == event_id) {
+            event.snapshot = Some(snapshot);
+        }
+    }
+
+    /// Every event attributed to `identity_id`, oldest first
+    pub fn for_identity(&self, identity_id: u32) -> Vec<FaceEvent> {
+        self.events
+            .iter()
+            .filter(|e| e.identity_id == Some(identity_id))
+            .cloned()
+            .collect()
+    }
+
+    /// Most recent event attributed to `identity_id`
+    pub fn last_seen(&self, identity_id: u32) -> Option<FaceEvent> {
+        self.events.iter().rev().find(|e| e.identity_id == Some(identity_id)).cloned()
+    }
+
+    /// Point events for identity `from` at `to`, e.g. after a merge
+    pub fn reassign_identity(&mut self, from: u32, to: u32, name: &str) {
+        for event in self.events.iter_mut().filter(|e| e.identity_id == Some(from)) {
+            event.identity_id = Some(to);
+            event.name = name.to_string();
+        }
+    }
+
+    pub fn filter_by_type(&self, event_type: EventType) -> Vec<FaceEvent> {
+        self.events.iter()
+            .filter(|e| e.event_type == event_type)
+            .cloned()
+            .collect()
+    }
+}
+
+pub fn log_event(_event: &FaceEvent) {
+    // Compatibility function
+}
+
+// Legacy function for compatibility
+pub fn generate_events() -> Vec<FaceEvent> {
+    vec![]
+}
@@ -0,0 +1,295 @@
+use super::{EventType, FaceEvent};
+use crate::frame::{Frame, PixelFormat};
+use crate::storage::Store;
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use image::ExtendedColorType;
+use serde::{Deserialize, Serialize};
+
+// Lowest quality tried before shrinking an oversized snapshot instead
+const MIN_JPEG_QUALITY: u8 = 40;
+
+/// What to capture when an event fires and how much space it may take
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotPolicy {
+    /// Event types that get snapshots
+    pub event_types: Vec<EventType>,
+    /// Crop around the face, padded by `face_padding` of its size
+    pub capture_face: bool,
+    pub face_padding: f32,
+    pub face_max_side: u32,
+    pub capture_frame: bool,
+    pub frame_max_side: u32,
+    pub jpeg_quality: u8, // 1 - 100
+    /// Largest stored snapshot, i.e. the base64 data URL rather than the
+    /// JPEG; quality and then size are reduced to fit
+    pub max_bytes: usize,
+    /// Total size of all snapshots; the oldest are deleted beyond it
+    pub retention_bytes: usize,
+}
+
+impl Default for SnapshotPolicy {
+    fn default() -> Self {
+        Self {
+            event_types: vec![
+                EventType::UnknownFace,
+                EventType::LowConfidence,
+                EventType::Blacklisted,
+                EventType::AfterHours,
+            ],
+            capture_face: true,
+            face_padding: 0.25,
+            face_max_side: 160,
+            capture_frame: false,
+            frame_max_side: 640,
+            jpeg_quality: 80,
+            max_bytes: 48 * 1024,
+            retention_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+impl SnapshotPolicy {
+    pub fn applies_to(&self, event_type: EventType) -> bool {
+        (self.capture_face || self.capture_frame) && self.event_types.contains(&event_type)
+    }
+
+    /// JPEG data URL of `frame` shrunk to `max_side`, or `None` if it can't
+    /// be brought under `max_bytes`
+    pub fn encode(&self, frame: &Frame, max_side: u32) -> Result<Option<String>> {
+        let mut frame = frame.thumbnail(max_side)?;
+        let mut quality = self.jpeg_quality.clamp(1, 100);
+        loop {
+            let data_url = format!("data:image/jpeg;base64,{}", BASE64.encode(encode_jpeg(&frame, quality)?));
+            if data_url.len() <= self.max_bytes {
+                return Ok(Some(data_url));
+            }
+            if quality > MIN_JPEG_QUALITY {
+                quality = quality.saturating_sub(15).max(MIN_JPEG_QUALITY);
+            } else if frame.width().max(frame.height()) > 32 {
+                frame = frame.thumbnail(frame.width().max(frame.height()) / 2)?;
+            } else {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Id a snapshot for `event` is stored under. Ids sort oldest first, with
+/// the event id breaking ties.
+pub fn snapshot_id(event: &FaceEvent) -> String {
+    format!("{:013}-{:010}", event.timestamp, event.id)
+}
+
+/// Id of the full frame stored alongside the face crop `snapshot_id`
+pub fn frame_snapshot_id(snapshot_id: &str) -> String {
+    format!("{}/frame", snapshot_id)
+}
+
+/// Capture what the policy asks for around `bbox` and store it, returning
+/// the id to put on the event if anything was stored
+pub fn capture_snapshot<S: Store + ?Sized>(
+    store: &mut S,
+    policy: &SnapshotPolicy,
+    event: &FaceEvent,
+    frame: &Frame,
+    bbox: (f32, f32, f32, f32),
+) -> Result<Option<String>> {
+    if !policy.applies_to(event.event_type) {
+        return Ok(None);
+    }
+    let id = snapshot_id(event);
+    let mut stored = false;
+
+    if policy.capture_face {
+        let face = crop_padded(frame, bbox, policy.face_padding)?;
+        if let Some(data_url) = policy.encode(&face, policy.face_max_side)? {
+            store.put_snapshot(&id, &data_url)?;
+            stored = true;
+        }
+    }
+    if policy.capture_frame {
+        if let Some(data_url) = policy.encode(frame, policy.frame_max_side)? {
+            store.put_snapshot(&frame_snapshot_id(&id), &data_url)?;
+            stored = true;
+        }
+    }
+    Ok(stored.then_some(id))
+}
+
+fn crop_padded(frame: &Frame, (x, y, w, h): (f32, f32, f32, f32), padding: f32) -> Result<Frame> {
+    let (pad_x, pad_y) = (w * padding, h * padding);
+    let x0 = (x - pad_x).max(0.0) as u32;
+    let y0 = (y - pad_y).max(0.0) as u32;
+    let x1 = ((x + w + pad_x) as u32).min(frame.width());
+    let y1 = ((y + h + pad_y) as u32).min(frame.height());
+    frame
+        .crop(x0, y0, x1.saturating_sub(x0), y1.saturating_sub(y0))
+        .context("face is outside the frame")
+}
+
+fn encode_jpeg(frame: &Frame, quality: u8) -> Result<Vec<u8>> {
+    let rgb = frame.convert(PixelFormat::Rgb8).packed();
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, quality)
+        .encode(rgb.data(), rgb.width(), rgb.height(), ExtendedColorType::Rgb8)
+        .context("encoding JPEG")?;
+    Ok(jpeg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn byte(&mut self) -> u8 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 as u8
+        }
+    }
+
+    #[derive(Default)]
+    struct MemoryStore {
+        records: BTreeMap<String, String>,
+    }
+
+    impl Store for MemoryStore {
+        fn name(&self) -> &'static str {
+            "memory"
+        }
+
+        fn get(&self, key: &str) -> Result<Option<String>> {
+            Ok(self.records.get(key).cloned())
+        }
+
+        fn put(&mut self, key: &str, value: &str) -> Result<()> {
+            self.records.insert(key.to_string(), value.to_string());
+            Ok(())
+        }
+
+        fn delete(&mut self, key: &str) -> Result<()> {
+            self.records.remove(key);
+            Ok(())
+        }
+
+        fn keys(&self) -> Result<Vec<String>> {
+            Ok(self.records.keys().cloned().collect())
+        }
+    }
+
+    /// RGB noise, which JPEG compresses badly
+    fn noise(width: u32, height: u32) -> Frame {
+        let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+        let data = (0..width * height * 3).map(|_| rng.byte()).collect();
+        Frame::new(width, height, PixelFormat::Rgb8, data, 0).unwrap()
+    }
+
+    /// RGB frame whose red/green channels hold the pixel's x/y
+    fn coordinates(width: u32, height: u32) -> Frame {
+        let data = (0..height).flat_map(|y| (0..width).flat_map(move |x| [x as u8, y as u8, 0])).collect();
+        Frame::new(width, height, PixelFormat::Rgb8, data, 0).unwrap()
+    }
+
+    fn event(id: u32, event_type: EventType, timestamp: u64) -> FaceEvent {
+        FaceEvent::new(id, event_type, String::from("Unknown"), 0.3, timestamp)
+    }
+
+    #[test]
+    fn ids_sort_by_time_then_event() {
+        assert_eq!(snapshot_id(&event(7, EventType::UnknownFace, 42)), "0000000000042-0000000007");
+        assert_eq!(snapshot_id(&event(u32::MAX, EventType::UnknownFace, 1)), "0000000000001-4294967295");
+        let mut ids: Vec<_> = [(10, 5), (9, 5), (100, 4), (2, 6)]
+            .iter()
+            .map(|&(id, timestamp)| snapshot_id(&event(id, EventType::UnknownFace, timestamp)))
+            .collect();
+        ids.sort();
+        assert_eq!(ids.iter().map(|id| &id[id.len() - 3..]).collect::<Vec<_>>(), ["100", "009", "010", "002"]);
+        assert_eq!(frame_snapshot_id(&ids[0]), format!("{}/frame", ids[0]));
+    }
+
+    #[test]
+    fn encoding_fits_the_data_url_in_max_bytes() {
+        let frame = noise(120, 90);
+        for max_bytes in [200_000, 8_000, 3_000, 1_200] {
+            let policy = SnapshotPolicy {
+                max_bytes,
+                ..SnapshotPolicy::default()
+            };
+            let data_url = policy.encode(&frame, 120).unwrap().unwrap();
+            assert!(data_url.starts_with("data:image/jpeg;base64,"));
+            assert!(data_url.len() <= max_bytes, "{} > {}", data_url.len(), max_bytes);
+            let jpeg = BASE64.decode(&data_url["data:image/jpeg;base64,".len()..]).unwrap();
+            assert_eq!(&jpeg[..2], [0xFF, 0xD8]);
+        }
+
+        // Too small even at the lowest quality and size
+        let policy = SnapshotPolicy {
+            max_bytes: 100,
+            ..SnapshotPolicy::default()
+        };
+        assert_eq!(policy.encode(&frame, 120).unwrap(), None);
+    }
+
+    #[test]
+    fn padded_crops_stay_inside_the_frame() {
+        let frame = coordinates(100, 80);
+        let crop = crop_padded(&frame, (20.0, 20.0, 40.0, 20.0), 0.25).unwrap();
+        assert_eq!((crop.width(), crop.height()), (60, 30));
+        let corner = crop.get_pixel(0, 0).unwrap();
+        assert_eq!((corner.r, corner.g), (10, 15));
+
+        // Padding past the edges is clipped
+        let crop = crop_padded(&frame, (-10.0, 70.0, 30.0, 30.0), 0.5).unwrap();
+        assert_eq!((crop.width(), crop.height()), (35, 25));
+        let corner = crop.get_pixel(0, 0).unwrap();
+        assert_eq!((corner.r, corner.g), (0, 55));
+
+        assert!(crop_padded(&frame, (150.0, 10.0, 20.0, 20.0), 0.25).is_err());
+    }
+
+    #[test]
+    fn captures_what_the_policy_asks_for() {
+        let frame = noise(64, 48);
+        let mut store = MemoryStore::default();
+
+        let policy = SnapshotPolicy::default();
+        let known = event(1, EventType::FaceRecognized, 1_000);
+        assert_eq!(capture_snapshot(&mut store, &policy, &known, &frame, (8.0, 8.0, 24.0, 24.0)).unwrap(), None);
+        assert!(store.records.is_empty());
+
+        let unknown = event(2, EventType::UnknownFace, 2_000);
+        let id = capture_snapshot(&mut store, &policy, &unknown, &frame, (8.0, 8.0, 24.0, 24.0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(id, snapshot_id(&unknown));
+        assert_eq!(store.snapshot_ids().unwrap(), [id.as_str()]);
+
+        let policy = SnapshotPolicy {
+            capture_face: false,
+            capture_frame: true,
+            ..SnapshotPolicy::default()
+        };
+        let blacklisted = event(3, EventType::Blacklisted, 3_000);
+        let id = capture_snapshot(&mut store, &policy, &blacklisted, &frame, (8.0, 8.0, 24.0, 24.0))
+            .unwrap()
+            .unwrap();
+        assert!(store.get_snapshot(&id).unwrap().is_none());
+        assert!(store.get_snapshot(&frame_snapshot_id(&id)).unwrap().is_some());
+
+        // Nothing stored when the snapshot can't be made small enough
+        let policy = SnapshotPolicy {
+            max_bytes: 10,
+            ..SnapshotPolicy::default()
+        };
+        let late = event(4, EventType::AfterHours, 4_000);
+        assert_eq!(capture_snapshot(&mut store, &policy, &late, &frame, (8.0, 8.0, 24.0, 24.0)).unwrap(), None);
+        assert_eq!(store.snapshot_ids().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,276 @@
+use super::FaceEvent;
+use std::fmt;
+
+const MS_PER_MINUTE: i64 = 60_000;
+const MS_PER_DAY: i64 = 86_400_000;
+
+/// Calendar date and time of a timestamp in a given UTC offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LocalTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl LocalTime {
+    /// `utc_offset_minutes` is east of UTC, e.g. 60 for CET
+    pub fn from_timestamp(timestamp: u64, utc_offset_minutes: i32) -> Self {
+        let ms = timestamp as i64 + utc_offset_minutes as i64 * MS_PER_MINUTE;
+        let days = ms.div_euclid(MS_PER_DAY);
+        let secs = ms.rem_euclid(MS_PER_DAY) / 1000;
+        let (year, month, day) = civil_from_days(days);
+        Self {
+            year,
+            month,
+            day,
+            hour: (secs / 3600) as u32,
+            minute: (secs / 60 % 60) as u32,
+            second: (secs % 60) as u32,
+        }
+    }
+
+    /// `YYYY-MM-DD`
+    pub fn date_label(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+
+    /// `HH:MM:SS`
+    pub fn time_label(&self) -> String {
+        format!("{:02}:{:02}:{:02}", self.hour, self.minute, self.second)
+    }
+}
+
+impl fmt::Display for LocalTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.date_label(), self.time_label())
+    }
+}
+
+/// Year, month and day of a count of days since 1970-01-01
+/// (Howard Hinnant's `civil_from_days`)
+fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year as i32, month, day)
+}
+
+/// Consecutive events from one track with the same type and identity,
+/// shown as a single timeline entry
+#[derive(Debug, Clone)]
+pub struct EventRun {
+    pub first: FaceEvent,
+    pub last: FaceEvent,
+    pub count: usize,
+    /// Latest snapshot taken during the run
+    pub snapshot: Option<String>,
+}
+
+impl EventRun {
+    fn new(event: FaceEvent) -> Self {
+        Self {
+            snapshot: event.snapshot.clone(),
+            first: event.clone(),
+            last: event,
+            count: 1,
+        }
+    }
+
+    fn continues(&self, event: &FaceEvent, max_gap_ms: u64) -> bool {
+        self.last.track_id.is_some()
+            && self.last.track_id == event.track_id
+            && self.last.event_type == event.event_type
+            && self.last.identity_id == event.identity_id
+            && event.timestamp.saturating_sub(self.last.timestamp) <= max_gap_ms
+    }
+}
+
+/// Collapse repeats from the same track in chronologically ordered events.
+/// Events without a track are never collapsed.
+pub fn collapse_runs(events: &[FaceEvent], max_gap_ms: u64) -> Vec<EventRun> {
+    let mut runs: Vec<EventRun> = Vec::new();
+    for event in events {
+        // Another track may have interleaved, so look back a little
+        let open = runs
+            .iter_mut()
+            .rev()
+            .take(8)
+            .find(|run| run.continues(event, max_gap_ms));
+        match open {
+            Some(run) => {
+                if event.snapshot.is_some() {
+                    run.snapshot = event.snapshot.clone();
+                }
+                run.last = event.clone();
+                run.count += 1;
+            }
+            None => runs.push(EventRun::new(event.clone())),
+        }
+    }
+    runs
+}
+
+#[derive(Debug, Clone)]
+pub struct HourGroup {
+    pub hour: u32,
+    /// Newest first
+    pub runs: Vec<EventRun>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DayGroup {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    /// Newest first
+    pub hours: Vec<HourGroup>,
+}
+
+impl DayGroup {
+    pub fn event_count(&self) -> usize {
+        self.hours.iter().flat_map(|h| &h.runs).map(|r| r.count).sum()
+    }
+}
+
+/// Group runs by local day and hour of their last event, newest first.
+/// `utc_offset_minutes` is asked per timestamp so daylight saving changes
+/// land in the right hour.
+pub fn group_by_day_hour(mut runs: Vec<EventRun>, utc_offset_minutes: impl Fn(u64) -> i32) -> Vec<DayGroup> {
+    runs.sort_by_key(|run| std::cmp::Reverse(run.last.timestamp));
+    let mut days: Vec<DayGroup> = Vec::new();
+    for run in runs {
+        let time = LocalTime::from_timestamp(run.last.timestamp, utc_offset_minutes(run.last.timestamp));
+        let date = time.date_label();
+        if days.last().is_none_or(|d| d.date != date) {
+            days.push(DayGroup {
+                date,
+                hours: Vec::new(),
+            });
+        }
+        let day = days.last_mut().expect("pushed above");
+        if day.hours.last().is_none_or(|h| h.hour != time.hour) {
+            day.hours.push(HourGroup {
+                hour: time.hour,
+                runs: Vec::new(),
+            });
+        }
+        day.hours.last_mut().expect("pushed above").runs.push(run);
+    }
+    days
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventType;
+
+    fn event(id: u32, event_type: EventType, track: Option<u32>, timestamp: u64) -> FaceEvent {
+        let event = FaceEvent::new(id, event_type, String::from("Alice"), 0.9, timestamp);
+        match track {
+            Some(track) => event.with_track(track),
+            None => event,
+        }
+    }
+
+    #[test]
+    fn civil_dates_follow_the_gregorian_calendar() {
+        let leap = |y: i32| y % 4 == 0 && (y % 100 != 0 || y % 400 == 0);
+        let (mut year, mut month, mut day) = (1970, 1, 1);
+        for days in 0..(200 * 366) {
+            assert_eq!(civil_from_days(days), (year, month, day), "day {}", days);
+            let length = match month {
+                2 if leap(year) => 29,
+                2 => 28,
+                4 | 6 | 9 | 11 => 30,
+                _ => 31,
+            };
+            day += 1;
+            if day > length {
+                (month, day) = (month + 1, 1);
+                if month > 12 {
+                    (year, month) = (year + 1, 1);
+                }
+            }
+        }
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(-719_468), (0, 3, 1));
+    }
+
+    #[test]
+    fn local_times_across_offsets_and_leap_days() {
+        let at = |timestamp, offset| LocalTime::from_timestamp(timestamp, offset).to_string();
+        assert_eq!(at(0, 0), "1970-01-01 00:00:00");
+        assert_eq!(at(0, -60), "1969-12-31 23:00:00");
+        assert_eq!(at(951_868_799_000, 0), "2000-02-29 23:59:59");
+        assert_eq!(at(951_868_799_000, 1), "2000-03-01 00:00:59");
+        assert_eq!(at(1_709_210_096_999, 0), "2024-02-29 12:34:56");
+        assert_eq!(at(4_107_542_400_000, -1), "2100-02-28 23:59:00");
+        assert_eq!(at(1_704_065_400_000, 30), "2024-01-01 00:00:00");
+        assert_eq!(at(1_704_065_400_000, -330), "2023-12-31 18:00:00");
+    }
+
+    #[test]
+    fn runs_collapse_per_track() {
+        let events = [
+            event(0, EventType::FaceRecognized, Some(1), 1_000),
+            event(1, EventType::FaceRecognized, Some(2), 1_500),
+            event(2, EventType::FaceRecognized, Some(1), 2_000),
+            event(3, EventType::UnknownFace, Some(1), 2_500),
+            event(4, EventType::UnknownFace, None, 3_000),
+            event(5, EventType::UnknownFace, None, 3_100),
+            event(6, EventType::UnknownFace, Some(1), 7_000),
+        ];
+        let mut with_snapshot = events.clone();
+        with_snapshot[2].snapshot = Some(String::from("snap"));
+
+        let runs = collapse_runs(&with_snapshot, 5_000);
+        let summary: Vec<_> = runs.iter().map(|r| (r.first.id, r.last.id, r.count)).collect();
+        assert_eq!(summary, [(0, 2, 2), (1, 1, 1), (3, 6, 2), (4, 4, 1), (5, 5, 1)]);
+        assert_eq!(runs[0].snapshot.as_deref(), Some("snap"));
+        assert_eq!(runs[2].snapshot, None);
+
+        // A longer gap than allowed starts a new run
+        let runs = collapse_runs(&events, 1_000);
+        assert_eq!(runs.iter().map(|r| r.count).collect::<Vec<_>>(), [2, 1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn groups_by_local_day_and_hour() {
+        // 2023-12-31 23:30 UTC, then 40 and 90 minutes later
+        let base = 1_704_065_400_000;
+        let events: Vec<_> = [0, 40, 41, 90]
+            .iter()
+            .enumerate()
+            .map(|(i, minutes)| event(i as u32, EventType::FaceDetected, None, base + minutes * 60_000))
+            .collect();
+        let runs = collapse_runs(&events, 0);
+
+        let layout = |days: &[DayGroup]| -> Vec<(String, Vec<(u32, usize)>)> {
+            days.iter()
+                .map(|d| (d.date.clone(), d.hours.iter().map(|h| (h.hour, h.runs.len())).collect()))
+                .collect()
+        };
+        let utc = group_by_day_hour(runs.clone(), |_| 0);
+        assert_eq!(
+            layout(&utc),
+            [(String::from("2024-01-01"), vec![(1, 1), (0, 2)]), (String::from("2023-12-31"), vec![(23, 1)])]
+        );
+        assert_eq!(utc[0].hours[1].runs[0].last.id, 2);
+        assert_eq!(utc.iter().map(DayGroup::event_count).sum::<usize>(), 4);
+
+        // Clocks going forward mid-way (daylight saving) apply per event
+        let shifted = group_by_day_hour(runs, |t| if t < base + 60 * 60_000 { 0 } else { 60 });
+        assert_eq!(
+            layout(&shifted),
+            [(String::from("2024-01-01"), vec![(2, 1), (0, 2)]), (String::from("2023-12-31"), vec![(23, 1)])]
+        );
+    }
+}
@@ -0,0 +1,881 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PixelFormat {
+    Rgba8,
+    Rgb8,
+    Gray8,
+    I420, // planar Y, then U and V at half resolution
+}
+
+impl PixelFormat {
+    /// Bytes per pixel of the first (or only) plane
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Rgba8 => 4,
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Gray8 | PixelFormat::I420 => 1,
+        }
+    }
+
+    /// Size in bytes of a tightly packed image of the given dimensions
+    pub fn frame_size(&self, width: u32, height: u32) -> usize {
+        let (w, h) = (width as usize, height as usize);
+        match self {
+            PixelFormat::I420 => w * h + 2 * w.div_ceil(2) * h.div_ceil(2),
+            _ => w * h * self.bytes_per_pixel(),
+        }
+    }
+}
+
+/// Clockwise rotation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rotation {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Rgba {
+    /// Mean of the colour channels, 0-255
+    pub fn brightness(&self) -> f32 {
+        (self.r as f32 + self.g as f32 + self.b as f32) / 3.0
+    }
+}
+
+/// One plane of pixel data: the whole image for packed formats, or Y/U/V for I420
+#[derive(Debug, Clone, Copy)]
+struct Plane<'a> {
+    data: &'a [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    channels: usize,
+}
+
+impl<'a> Plane<'a> {
+    fn row(&self, y: usize) -> &'a [u8] {
+        let start = y * self.stride;
+        &self.data[start..start + self.width * self.channels]
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> &'a [u8] {
+        let start = y * self.stride + x * self.channels;
+        &self.data[start..start + self.channels]
+    }
+
+    fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Vec<u8> {
+        let mut out = Vec::with_capacity(width * height * self.channels);
+        for row in y..y + height {
+            let start = row * self.stride + x * self.channels;
+            out.extend_from_slice(&self.data[start..start + width * self.channels]);
+        }
+        out
+    }
+
+    fn resize_bilinear(&self, width: usize, height: usize) -> Vec<u8> {
+        let mut out = Vec::with_capacity(width * height * self.channels);
+        let scale_x = self.width as f32 / width as f32;
+        let scale_y = self.height as f32 / height as f32;
+
+        for dy in 0..height {
+            // Sample at pixel centres so up- and down-scaling stay aligned
+            let sy = ((dy as f32 + 0.5) * scale_y - 0.5).max(0.0);
+            let y0 = (sy as usize).min(self.height - 1);
+            let y1 = (y0 + 1).min(self.height - 1);
+            let fy = sy - y0 as f32;
+
+            for dx in 0..width {
+                let sx = ((dx as f32 + 0.5) * scale_x - 0.5).max(0.0);
+                let x0 = (sx as usize).min(self.width - 1);
+                let x1 = (x0 + 1).min(self.width - 1);
+                let fx = sx - x0 as f32;
+
+                for c in 0..self.channels {
+                    let top = self.pixel(x0, y0)[c] as f32 * (1.0 - fx) + self.pixel(x1, y0)[c] as f32 * fx;
+                    let bottom = self.pixel(x0, y1)[c] as f32 * (1.0 - fx) + self.pixel(x1, y1)[c] as f32 * fx;
+                    out.push((top * (1.0 - fy) + bottom * fy).round() as u8);
+                }
+            }
+        }
+        out
+    }
+
+    fn rotate(&self, rotation: Rotation) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.width * self.height * self.channels);
+        match rotation {
+            Rotation::Rotate90 => {
+                for x in 0..self.width {
+                    for y in (0..self.height).rev() {
+                        out.extend_from_slice(self.pixel(x, y));
+                    }
+                }
+            }
+            Rotation::Rotate180 => {
+                for y in (0..self.height).rev() {
+                    for x in (0..self.width).rev() {
+                        out.extend_from_slice(self.pixel(x, y));
+                    }
+                }
+            }
+            Rotation::Rotate270 => {
+                for x in (0..self.width).rev() {
+                    for y in 0..self.height {
+                        out.extend_from_slice(self.pixel(x, y));
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// An owned image with its capture timestamp.
+///
+/// Rows of the first plane may be padded (`stride >= width * bytes_per_pixel`);
+/// every operation that produces a new frame returns it tightly packed.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    width: u32,
+    height: u32,
+    format: PixelFormat,
+    stride: usize,
+    timestamp: u64, // milliseconds
+    data: Vec<u8>,
+}
+
+impl Frame {
+    /// Wrap a tightly packed pixel buffer
+    pub fn new(width: u32, height: u32, format: PixelFormat, data: Vec<u8>, timestamp: u64) -> Result<Self> {
+        let stride = width as usize * format.bytes_per_pixel();
+        Self::with_stride(width, height, format, stride, data, timestamp)
+    }
+
+    /// Wrap a pixel buffer whose rows are `stride` bytes apart.
+    ///
+    /// For I420 the chroma planes follow the luma plane with a stride of
+    /// `stride / 2` (rounded up).
+    pub fn with_stride(
+        width: u32,
+        height: u32,
+        format: PixelFormat,
+        stride: usize,
+        data: Vec<u8>,
+        timestamp: u64,
+    ) -> Result<Self> {
+        if width == 0 || height == 0 {
+            bail!("frame dimensions must be non-zero, got {}x{}", width, height);
+        }
+
+        let row_bytes = width as usize * format.bytes_per_pixel();
+        if stride < row_bytes {
+            bail!("stride {} is shorter than a {:?} row of {} bytes", stride, format, row_bytes);
+        }
+
+        let expected = Self::buffer_size(height, format, stride);
+        if data.len() < expected {
+            bail!(
+                "{:?} frame {}x{} (stride {}) needs {} bytes, got {}",
+                format, width, height, stride, expected, data.len()
+            );
+        }
+
+        Ok(Self {
+            width,
+            height,
+            format,
+            stride,
+            timestamp,
+            data,
+        })
+    }
+
+    /// Build a frame from browser `ImageData` bytes
+    pub fn from_rgba(width: u32, height: u32, data: Vec<u8>, timestamp: u64) -> Result<Self> {
+        Self::new(width, height, PixelFormat::Rgba8, data, timestamp)
+    }
+
+    fn buffer_size(height: u32, format: PixelFormat, stride: usize) -> usize {
+        let h = height as usize;
+        match format {
+            PixelFormat::I420 => stride * h + 2 * stride.div_ceil(2) * h.div_ceil(2),
+            _ => stride * h,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// Bytes per row of the first plane
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Pixel at `(x, y)`, or `None` outside the frame
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<Rgba> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        let (x, y) = (x as usize, y as usize);
+        let p = self.plane(0).pixel(x, y);
+        Some(match self.format {
+            PixelFormat::Rgba8 => Rgba { r: p[0], g: p[1], b: p[2], a: p[3] },
+            PixelFormat::Rgb8 => Rgba { r: p[0], g: p[1], b: p[2], a: 255 },
+            PixelFormat::Gray8 => Rgba { r: p[0], g: p[0], b: p[0], a: 255 },
+            PixelFormat::I420 => {
+                let [r, g, b] = yuv_to_rgb(p[0], self.plane(1).pixel(x / 2, y / 2)[0], self.plane(2).pixel(x / 2, y / 2)[0]);
+                Rgba { r, g, b, a: 255 }
+            }
+        })
+    }
+
+    /// Rows of the first plane (the luma plane for I420), without padding
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        let row_bytes = self.width as usize * self.format.bytes_per_pixel();
+        self.data
+            .chunks(self.stride)
+            .take(self.height as usize)
+            .map(move |row| &row[..row_bytes])
+    }
+
+    /// A borrowed view of a rectangle, clipped to the frame.
+    /// Returns `None` when the rectangle lies entirely outside.
+    pub fn region(&self, x: u32, y: u32, width: u32, height: u32) -> Option<Region<'_>> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let width = width.min(self.width - x);
+        let height = height.min(self.height - y);
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        Some(Region {
+            frame: self,
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    fn planes(&self) -> Vec<Plane<'_>> {
+        let count = if self.format == PixelFormat::I420 { 3 } else { 1 };
+        (0..count).map(|index| self.plane(index)).collect()
+    }
+
+    /// Plane 0 is the packed image or the luma plane; 1 and 2 are U and V
+    fn plane(&self, index: usize) -> Plane<'_> {
+        let (w, h) = (self.width as usize, self.height as usize);
+        if index == 0 {
+            let size = Self::buffer_size(self.height, PixelFormat::Gray8, self.stride);
+            return Plane {
+                data: &self.data[..size],
+                width: w,
+                height: h,
+                stride: self.stride,
+                channels: self.format.bytes_per_pixel(),
+            };
+        }
+
+        let luma_size = self.stride * h;
+        let (cw, ch, cstride) = (w.div_ceil(2), h.div_ceil(2), self.stride.div_ceil(2));
+        let start = luma_size + (index - 1) * cstride * ch;
+        Plane {
+            data: &self.data[start..start + cstride * ch],
+            width: cw,
+            height: ch,
+            stride: cstride,
+            channels: 1,
+        }
+    }
+
+    fn assemble(&self, width: u32, height: u32, planes: Vec<Vec<u8>>) -> Frame {
+        let data = planes.concat();
+        Frame {
+            width,
+            height,
+            format: self.format,
+            stride: width as usize * self.format.bytes_per_pixel(),
+            timestamp: self.timestamp,
+            data,
+        }
+    }
+
+    /// Copy with any row padding removed
+    pub fn packed(&self) -> Frame {
+        let planes = self.planes().iter().map(|p| p.crop(0, 0, p.width, p.height)).collect();
+        self.assemble(self.width, self.height, planes)
+    }
+
+    /// Copy out a rectangle. For I420 the origin is rounded down to an even
+    /// coordinate so the chroma planes stay aligned.
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Frame> {
+        let (x, y) = match self.format {
+            PixelFormat::I420 => (x & !1, y & !1),
+            _ => (x, y),
+        };
+        let fits = |start: u32, len: u32, limit: u32| start.checked_add(len).is_some_and(|end| end <= limit);
+        if width == 0 || height == 0 || !fits(x, width, self.width) || !fits(y, height, self.height) {
+            bail!(
+                "crop {}x{} at ({}, {}) is outside the {}x{} frame",
+                width, height, x, y, self.width, self.height
+            );
+        }
+
+        let planes = self
+            .planes()
+            .iter()
+            .enumerate()
+            .map(|(i, plane)| {
+                if i == 0 {
+                    plane.crop(x as usize, y as usize, width as usize, height as usize)
+                } else {
+                    let cw = (width as usize).div_ceil(2).min(plane.width - x as usize / 2);
+                    let ch = (height as usize).div_ceil(2).min(plane.height - y as usize / 2);
+                    plane.crop(x as usize / 2, y as usize / 2, cw, ch)
+                }
+            })
+            .collect();
+        Ok(self.assemble(width, height, planes))
+    }
+
+    /// Resize with bilinear interpolation
+    pub fn resize(&self, width: u32, height: u32) -> Result<Frame> {
+        if width == 0 || height == 0 {
+            bail!("cannot resize to {}x{}", width, height);
+        }
+
+        let planes = self
+            .planes()
+            .iter()
+            .enumerate()
+            .map(|(i, plane)| {
+                if i == 0 {
+                    plane.resize_bilinear(width as usize, height as usize)
+                } else {
+                    plane.resize_bilinear((width as usize).div_ceil(2), (height as usize).div_ceil(2))
+                }
+            })
+            .collect();
+        Ok(self.assemble(width, height, planes))
+    }
+
+    /// Shrink (never enlarge) so that neither side exceeds `max_side`,
+    /// keeping the aspect ratio. Used for enrollment and event thumbnails.
+    pub fn thumbnail(&self, max_side: u32) -> Result<Frame> {
+        let longest = self.width.max(self.height);
+        if longest <= max_side {
+            return Ok(self.clone());
+        }
+        let scale = max_side as f32 / longest as f32;
+        let width = ((self.width as f32 * scale).round() as u32).max(1);
+        let height = ((self.height as f32 * scale).round() as u32).max(1);
+        self.resize(width, height)
+    }
+
+    pub fn rotate(&self, rotation: Rotation) -> Frame {
+        let (width, height) = match rotation {
+            Rotation::Rotate180 => (self.width, self.height),
+            Rotation::Rotate90 | Rotation::Rotate270 => (self.height, self.width),
+        };
+        let planes = self.planes().iter().map(|plane| plane.rotate(rotation)).collect();
+        self.assemble(width, height, planes)
+    }
+
+    /// Convert to another pixel layout
+    pub fn convert(&self, format: PixelFormat) -> Frame {
+        if format == self.format {
+            return self.packed();
+        }
+
+        let (w, h) = (self.width as usize, self.height as usize);
+        let data = match format {
+            PixelFormat::Gray8 => self.to_luma(),
+            PixelFormat::Rgb8 => {
+                let mut out = Vec::with_capacity(w * h * 3);
+                self.for_each_rgba(|px| out.extend_from_slice(&px[..3]));
+                out
+            }
+            PixelFormat::Rgba8 => {
+                let mut out = Vec::with_capacity(w * h * 4);
+                self.for_each_rgba(|px| out.extend_from_slice(&px));
+                out
+            }
+            PixelFormat::I420 => self.to_i420(),
+        };
+
+        Frame {
+            width: self.width,
+            height: self.height,
+            format,
+            stride: w * format.bytes_per_pixel(),
+            timestamp: self.timestamp,
+            data,
+        }
+    }
+
+    pub fn to_gray(&self) -> Frame {
+        self.convert(PixelFormat::Gray8)
+    }
+
+    fn to_luma(&self) -> Vec<u8> {
+        let (w, h) = (self.width as usize, self.height as usize);
+        match self.format {
+            PixelFormat::Gray8 | PixelFormat::I420 => self.planes()[0].crop(0, 0, w, h),
+            _ => {
+                let mut out = Vec::with_capacity(w * h);
+                self.for_each_rgba(|px| out.push(luma(px[0], px[1], px[2])));
+                out
+            }
+        }
+    }
+
+    fn to_i420(&self) -> Vec<u8> {
+        let (w, h) = (self.width as usize, self.height as usize);
+        let (cw, ch) = (w.div_ceil(2), h.div_ceil(2));
+        let rgb = self.convert(PixelFormat::Rgb8);
+        let px = |x: usize, y: usize| {
+            let i = (y * w + x) * 3;
+            (rgb.data[i] as f32, rgb.data[i + 1] as f32, rgb.data[i + 2] as f32)
+        };
+
+        let mut out = Vec::with_capacity(PixelFormat::I420.frame_size(self.width, self.height));
+        for y in 0..h {
+            for x in 0..w {
+                let (r, g, b) = px(x, y);
+                out.push((16.0 + 0.257 * r + 0.504 * g + 0.098 * b).round().clamp(0.0, 255.0) as u8);
+            }
+        }
+
+        // Average each 2x2 block for the chroma planes
+        let mut u_plane = Vec::with_capacity(cw * ch);
+        let mut v_plane = Vec::with_capacity(cw * ch);
+        for cy in 0..ch {
+            for cx in 0..cw {
+                let (mut r, mut g, mut b, mut n) = (0.0, 0.0, 0.0, 0.0);
+                for y in (cy * 2)..(cy * 2 + 2).min(h) {
+                    for x in (cx * 2)..(cx * 2 + 2).min(w) {
+                        let p = px(x, y);
+                        r += p.0;
+                        g += p.1;
+                        b += p.2;
+                        n += 1.0;
+                    }
+                }
+                let (r, g, b) = (r / n, g / n, b / n);
+                u_plane.push((128.0 - 0.148 * r - 0.291 * g + 0.439 * b).round().clamp(0.0, 255.0) as u8);
+                v_plane.push((128.0 + 0.439 * r - 0.368 * g - 0.071 * b).round().clamp(0.0, 255.0) as u8);
+            }
+        }
+
+        out.extend_from_slice(&u_plane);
+        out.extend_from_slice(&v_plane);
+        out
+    }
+
+    /// Visit every pixel in row-major order as RGBA
+    fn for_each_rgba(&self, mut f: impl FnMut([u8; 4])) {
+        let planes = self.planes();
+        let (w, h) = (self.width as usize, self.height as usize);
+        for y in 0..h {
+            match self.format {
+                PixelFormat::Rgba8 => planes[0].row(y).chunks_exact(4).for_each(|p| f([p[0], p[1], p[2], p[3]])),
+                PixelFormat::Rgb8 => planes[0].row(y).chunks_exact(3).for_each(|p| f([p[0], p[1], p[2], 255])),
+                PixelFormat::Gray8 => planes[0].row(y).iter().for_each(|&v| f([v, v, v, 255])),
+                PixelFormat::I420 => {
+                    for x in 0..w {
+                        let (u, v) = (planes[1].pixel(x / 2, y / 2)[0], planes[2].pixel(x / 2, y / 2)[0]);
+                        let [r, g, b] = yuv_to_rgb(planes[0].pixel(x, y)[0], u, v);
+                        f([r, g, b, 255]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A rectangle within a [`Frame`]; coordinates are relative to its top-left corner
+#[derive(Debug, Clone, Copy)]
+pub struct Region<'a> {
+    frame: &'a Frame,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Region<'_> {
+    pub fn x(&self) -> u32 {
+        self.x
+    }
+
+    pub fn y(&self) -> u32 {
+        self.y
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<Rgba> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.frame.get_pixel(self.x + x, self.y + y)
+    }
+
+    /// Every `step`-th pixel in both directions, row by row
+    pub fn pixels(&self, step: u32) -> impl Iterator<Item = Rgba> + '_ {
+        let step = step.max(1) as usize;
+        (0..self.height)
+            .step_by(step)
+            .flat_map(move |y| (0..self.width).step_by(step).map(move |x| (x, y)))
+            .filter_map(|(x, y)| self.get_pixel(x, y))
+    }
+
+    /// Average brightness of sampled pixels in 0.0..=1.0
+    pub fn mean_brightness(&self, step: u32) -> f32 {
+        let (total, count) = self
+            .pixels(step)
+            .fold((0.0, 0), |(total, count), px| (total + px.brightness() / 255.0, count + 1));
+        if count > 0 {
+            total / count as f32
+        } else {
+            0.0
+        }
+    }
+
+    /// Fraction of horizontal and vertical neighbour pairs whose brightness
+    /// differs by more than `threshold`
+    pub fn edge_density(&self, threshold: f32) -> f32 {
+        let mut edge_count = 0;
+        let mut total_count = 0;
+
+        for y in 0..self.height.saturating_sub(1) {
+            for x in 0..self.width.saturating_sub(1) {
+                let (Some(p), Some(right), Some(below)) =
+                    (self.get_pixel(x, y), self.get_pixel(x + 1, y), self.get_pixel(x, y + 1))
+                else {
+                    continue;
+                };
+
+                let b = p.brightness();
+                for neighbour in [right, below] {
+                    if (b - neighbour.brightness()).abs() > threshold {
+                        edge_count += 1;
+                    }
+                    total_count += 1;
+                }
+            }
+        }
+
+        if total_count > 0 {
+            edge_count as f32 / total_count as f32
+        } else {
+            0.0
+        }
+    }
+}
+
+/// BT.601 luma from RGB
+pub(crate) fn luma(r: u8, g: u8, b: u8) -> u8 {
+    (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32).round() as u8
+}
+
+/// BT.601 limited-range YUV to RGB
+pub(crate) fn yuv_to_rgb(y: u8, u: u8, v: u8) -> [u8; 3] {
+    let c = y as f32 - 16.0;
+    let d = u as f32 - 128.0;
+    let e = v as f32 - 128.0;
+    let r = 1.164 * c + 1.596 * e;
+    let g = 1.164 * c - 0.392 * d - 0.813 * e;
+    let b = 1.164 * c + 2.017 * d;
+    [r.clamp(0.0, 255.0) as u8, g.clamp(0.0, 255.0) as u8, b.clamp(0.0, 255.0) as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detection::{Detector, EdgeDensityDetector};
+
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn below(&mut self, n: u32) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as u32
+        }
+
+        /// A random size in `min..max`
+        fn between(&mut self, min: u32, max: u32) -> u32 {
+            min + self.below(max - min)
+        }
+    }
+
+    /// RGB frame, padded rows, whose red/green channels hold the pixel's x/y
+    fn coordinate_frame(width: u32, height: u32, padding: usize) -> Frame {
+        let stride = width as usize * 3 + padding;
+        let mut data = vec![0u8; stride * height as usize];
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let i = y * stride + x * 3;
+                data[i] = x as u8;
+                data[i + 1] = y as u8;
+            }
+        }
+        Frame::with_stride(width, height, PixelFormat::Rgb8, stride, data, 0).unwrap()
+    }
+
+    #[test]
+    fn get_pixel_bounds() {
+        let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..50 {
+            let (w, h) = (rng.between(1, 100), rng.between(1, 100));
+            let frame = coordinate_frame(w, h, rng.below(5) as usize);
+            for y in 0..h + 3 {
+                for x in 0..w + 3 {
+                    match frame.get_pixel(x, y) {
+                        Some(p) => assert_eq!((p.r, p.g), (x as u8, y as u8), "{}x{} at ({}, {})", w, h, x, y),
+                        None => assert!(x >= w || y >= h, "{}x{} at ({}, {})", w, h, x, y),
+                    }
+                }
+            }
+            assert_eq!(frame.get_pixel(u32::MAX, 0), None);
+            assert_eq!(frame.rows().count(), h as usize);
+            assert!(frame.rows().all(|row| row.len() == w as usize * 3));
+
+            let i420 = Frame::new(w, h, PixelFormat::I420, vec![128; PixelFormat::I420.frame_size(w, h)], 0).unwrap();
+            assert!(i420.get_pixel(w - 1, h - 1).is_some());
+            assert!(i420.get_pixel(w, h - 1).is_none());
+        }
+    }
+
+    #[test]
+    fn region_clipping() {
+        let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+        for _ in 0..500 {
+            let (w, h) = (rng.between(1, 80), rng.between(1, 80));
+            let frame = coordinate_frame(w, h, rng.below(3) as usize);
+            let (x, y) = (rng.below(w + 10), rng.below(h + 10));
+            let (rw, rh) = (rng.below(100), rng.below(100));
+            let Some(region) = frame.region(x, y, rw, rh) else {
+                assert!(x >= w || y >= h || rw == 0 || rh == 0);
+                continue;
+            };
+            assert_eq!((region.width(), region.height()), (rw.min(w - x), rh.min(h - y)));
+            assert!(region.x() + region.width() <= w && region.y() + region.height() <= h);
+            for ry in 0..region.height() + 2 {
+                for rx in 0..region.width() + 2 {
+                    let expected = (rx < region.width() && ry < region.height()).then(|| frame.get_pixel(x + rx, y + ry).unwrap());
+                    assert_eq!(region.get_pixel(rx, ry), expected);
+                }
+            }
+            assert_eq!(region.pixels(1).count(), (region.width() * region.height()) as usize);
+        }
+    }
+
+    #[test]
+    fn crop_copies_the_rectangle() {
+        let mut rng = XorShift(0x5851_f42d_4c95_7f2d);
+        for _ in 0..200 {
+            let (w, h) = (rng.between(1, 60), rng.between(1, 60));
+            let frame = coordinate_frame(w, h, rng.below(4) as usize);
+            let (x, y) = (rng.below(w), rng.below(h));
+            let (cw, ch) = (rng.between(1, w - x + 1), rng.between(1, h - y + 1));
+            let crop = frame.crop(x, y, cw, ch).unwrap();
+            assert_eq!((crop.width(), crop.height(), crop.stride()), (cw, ch, cw as usize * 3));
+            for cy in 0..ch {
+                for cx in 0..cw {
+                    assert_eq!(crop.get_pixel(cx, cy), frame.get_pixel(x + cx, y + cy));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn crop_outside_the_frame_is_an_error() {
+        let frame = coordinate_frame(10, 8, 0);
+        assert!(frame.crop(0, 0, 10, 8).is_ok());
+        for (x, y, w, h) in [(0, 0, 0, 1), (0, 0, 11, 1), (9, 0, 2, 1), (0, 7, 1, 2), (u32::MAX, 0, 2, 2), (0, u32::MAX, 2, 2), (1, 1, u32::MAX, 1)] {
+            assert!(frame.crop(x, y, w, h).is_err(), "({}, {}) {}x{}", x, y, w, h);
+        }
+    }
+
+    #[test]
+    fn i420_crop_keeps_chroma_aligned() {
+        // 4x4 luma 0..16, U plane 2x2 = 100..104, V plane = 200..204
+        let mut data: Vec<u8> = (0..16).collect();
+        data.extend([100, 101, 102, 103, 200, 201, 202, 203]);
+        let frame = Frame::new(4, 4, PixelFormat::I420, data, 0).unwrap();
+        assert_eq!(PixelFormat::I420.frame_size(3, 3), 9 + 2 * 4);
+
+        // The origin rounds down to (2, 2)
+        let crop = frame.crop(3, 3, 2, 2).unwrap();
+        assert_eq!(crop.data(), &[10, 11, 14, 15, 103, 203]);
+        assert_eq!(frame.crop(2, 0, 2, 2).unwrap().data(), &[2, 3, 6, 7, 101, 201]);
+    }
+
+    #[test]
+    fn yuv_and_rgb_conversions() {
+        assert_eq!(yuv_to_rgb(16, 128, 128), [0, 0, 0]);
+        assert!(yuv_to_rgb(235, 128, 128).iter().all(|&c| c >= 254));
+        assert_eq!(luma(255, 255, 255), 255);
+        assert_eq!(luma(255, 0, 0), 76);
+
+        let colours = [[200u8, 30, 60], [10, 180, 90], [0, 0, 0], [255, 255, 255]];
+        for colour in colours {
+            let data = colour.repeat(6 * 4);
+            let rgb = Frame::new(6, 4, PixelFormat::Rgb8, data, 7).unwrap();
+
+            let gray = rgb.to_gray();
+            assert_eq!(gray.format(), PixelFormat::Gray8);
+            assert!(gray.data().iter().all(|&v| v == luma(colour[0], colour[1], colour[2])));
+
+            let rgba = rgb.convert(PixelFormat::Rgba8);
+            assert_eq!(rgba.get_pixel(5, 3), Some(Rgba { r: colour[0], g: colour[1], b: colour[2], a: 255 }));
+
+            // Limited-range YUV loses a little precision on the way back
+            let back = rgb.convert(PixelFormat::I420).convert(PixelFormat::Rgb8);
+            assert_eq!((back.timestamp(), back.data().len()), (7, 6 * 4 * 3));
+            for (a, b) in back.data().iter().zip(rgb.data()) {
+                assert!((*a as i32 - *b as i32).abs() <= 3, "{:?}: {} vs {}", colour, a, b);
+            }
+        }
+
+        let gray = Frame::new(2, 1, PixelFormat::Gray8, vec![9, 250], 0).unwrap();
+        assert_eq!(gray.convert(PixelFormat::Rgb8).data(), &[9, 9, 9, 250, 250, 250]);
+    }
+
+    #[test]
+    fn resize_interpolates_at_pixel_centres() {
+        let frame = Frame::new(2, 1, PixelFormat::Gray8, vec![0, 255], 0).unwrap();
+        assert_eq!(frame.resize(4, 1).unwrap().data(), &[0, 64, 191, 255]);
+        assert_eq!(frame.resize(2, 1).unwrap().data(), frame.data());
+        assert!(frame.resize(0, 1).is_err());
+
+        let flat = Frame::new(5, 3, PixelFormat::Rgb8, vec![77; 5 * 3 * 3], 0).unwrap();
+        let resized = flat.resize(13, 2).unwrap();
+        assert_eq!((resized.width(), resized.height()), (13, 2));
+        assert!(resized.data().iter().all(|&v| v == 77));
+
+        let i420 = Frame::new(5, 3, PixelFormat::I420, vec![90; PixelFormat::I420.frame_size(5, 3)], 0).unwrap();
+        assert_eq!(i420.resize(7, 7).unwrap().data().len(), PixelFormat::I420.frame_size(7, 7));
+    }
+
+    #[test]
+    fn rotations() {
+        let (w, h) = (5, 3);
+        let frame = coordinate_frame(w, h, 2);
+        let quarter = frame.rotate(Rotation::Rotate90);
+        assert_eq!((quarter.width(), quarter.height()), (h, w));
+        let half = frame.rotate(Rotation::Rotate180);
+        let three_quarters = frame.rotate(Rotation::Rotate270);
+        for y in 0..h {
+            for x in 0..w {
+                let p = frame.get_pixel(x, y);
+                assert_eq!(quarter.get_pixel(h - 1 - y, x), p);
+                assert_eq!(half.get_pixel(w - 1 - x, h - 1 - y), p);
+                assert_eq!(three_quarters.get_pixel(y, w - 1 - x), p);
+            }
+        }
+
+        let full = quarter.rotate(Rotation::Rotate90).rotate(Rotation::Rotate90).rotate(Rotation::Rotate90);
+        assert_eq!(full.data(), frame.packed().data());
+        assert_eq!(quarter.rotate(Rotation::Rotate270).data(), frame.packed().data());
+
+        let i420 = Frame::new(4, 2, PixelFormat::I420, (0..12).collect(), 0).unwrap();
+        assert_eq!(i420.rotate(Rotation::Rotate180).data(), &[7, 6, 5, 4, 3, 2, 1, 0, 9, 8, 11, 10]);
+    }
+
+    #[test]
+    fn thumbnails_shrink_keeping_aspect() {
+        let thumb = |w: u32, h: u32, max: u32| {
+            let frame = Frame::new(w, h, PixelFormat::Gray8, vec![0; (w * h) as usize], 0).unwrap();
+            let thumb = frame.thumbnail(max).unwrap();
+            (thumb.width(), thumb.height())
+        };
+        assert_eq!(thumb(400, 200, 100), (100, 50));
+        assert_eq!(thumb(200, 400, 100), (50, 100));
+        assert_eq!(thumb(80, 60, 100), (80, 60));
+        assert_eq!(thumb(3, 1000, 100), (1, 100));
+    }
+
+    #[test]
+    fn edge_density_on_non_square_frames() {
+        let mut rng = XorShift(0xdead_beef_cafe_f00d);
+        for _ in 0..20 {
+            let (w, h) = loop {
+                let (w, h) = (rng.between(2, 120), rng.between(2, 120));
+                if w != h {
+                    break (w, h);
+                }
+            };
+            // Vertical stripes: every horizontal pair is an edge, no vertical one
+            let data = (0..h).flat_map(|_| (0..w).map(|x| if x % 2 == 0 { 0 } else { 255 })).collect();
+            let frame = Frame::new(w, h, PixelFormat::Gray8, data, 0).unwrap();
+            let density = frame.region(0, 0, w, h).unwrap().edge_density(15.0);
+            assert!((density - 0.5).abs() < 1e-6, "{}x{}: {}", w, h, density);
+        }
+    }
+
+    #[test]
+    fn edge_detector_finds_texture_in_the_far_corner() {
+        let mut rng = XorShift(0x0123_4567_89ab_cdef);
+        for _ in 0..10 {
+            let (w, h) = (rng.between(160, 400), rng.between(80, 160));
+            // Checkerboard in the bottom-right quarter of a flat frame
+            let data = (0..h)
+                .flat_map(|y| (0..w).map(move |x| if x >= w * 3 / 4 && y >= h * 3 / 4 && (x + y) % 2 == 0 { 255 } else { 100 }))
+                .collect();
+            let frame = Frame::new(w, h, PixelFormat::Gray8, data, 0).unwrap();
+            let detections = EdgeDensityDetector::default().detect(&frame);
+            assert_eq!(detections.len(), 1, "{}x{}", w, h);
+            let (x, y, dw, dh) = detections[0].bbox;
+            assert!(x + dw <= w as f32 && y + dh <= h as f32, "{}x{}: {:?}", w, h, detections[0].bbox);
+            assert!(x >= w as f32 * 0.6 && y >= h as f32 * 0.6, "{}x{}: {:?}", w, h, detections[0].bbox);
+        }
+    }
+}
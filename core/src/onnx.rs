@@ -0,0 +1,178 @@
+use crate::frame::{Frame, PixelFormat};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tract_onnx::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TensorLayout {
+    #[default]
+    Nchw,
+    Nhwc,
+}
+
+/// Where a letterboxed model input sits inside the source frame
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Letterbox {
+    pub scale: f32,
+    pub pad_x: f32,
+    pub pad_y: f32,
+}
+
+impl Letterbox {
+    /// Map a point in model input pixels back to frame pixels
+    pub fn to_frame(self, x: f32, y: f32) -> (f32, f32) {
+        ((x - self.pad_x) / self.scale, (y - self.pad_y) / self.scale)
+    }
+}
+
+/// An ONNX model compiled for CPU inference with a fixed RGB input size
+pub(crate) struct OnnxModel {
+    plan: TypedRunnableModel<TypedModel>,
+    width: u32,
+    height: u32,
+    layout: TensorLayout,
+}
+
+impl OnnxModel {
+    pub fn load(mut bytes: &[u8], width: u32, height: u32, layout: TensorLayout) -> Result<Self> {
+        let shape = match layout {
+            TensorLayout::Nchw => [1, 3, height as usize, width as usize],
+            TensorLayout::Nhwc => [1, height as usize, width as usize, 3],
+        };
+
+        let plan = tract_onnx::onnx()
+            .model_for_read(&mut bytes)
+            .and_then(|model| model.with_input_fact(0, f32::fact(shape).into()))
+            .and_then(|model| model.into_optimized())
+            .and_then(|model| model.into_runnable())
+            .map_err(|e| anyhow!("loading ONNX model: {}", e))?;
+
+        Ok(Self {
+            plan,
+            width,
+            height,
+            layout,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Run on an `Rgb8` frame of exactly the model input size, normalising
+    /// each channel value as `(v - mean) / std`. Returns every output as
+    /// `(shape, values)`.
+    pub fn run(&self, input: &Frame, mean: f32, std: f32) -> Result<Vec<(Vec<usize>, Vec<f32>)>> {
+        if input.format() != PixelFormat::Rgb8 || input.width() != self.width || input.height() != self.height {
+            return Err(anyhow!(
+                "model expects {}x{} Rgb8 input, got {}x{} {:?}",
+                self.width,
+                self.height,
+                input.width(),
+                input.height(),
+                input.format()
+            ));
+        }
+
+        let (w, h) = (self.width as usize, self.height as usize);
+        let data = input.packed().into_data();
+        let value = |y: usize, x: usize, c: usize| (data[(y * w + x) * 3 + c] as f32 - mean) / std;
+        let tensor: Tensor = match self.layout {
+            TensorLayout::Nchw => tract_ndarray::Array4::from_shape_fn((1, 3, h, w), |(_, c, y, x)| value(y, x, c)).into(),
+            TensorLayout::Nhwc => tract_ndarray::Array4::from_shape_fn((1, h, w, 3), |(_, y, x, c)| value(y, x, c)).into(),
+        };
+
+        let outputs = self
+            .plan
+            .run(tvec!(tensor.into()))
+            .map_err(|e| anyhow!("ONNX inference failed: {}", e))?;
+
+        outputs
+            .iter()
+            .map(|output| {
+                let view = output
+                    .to_array_view::<f32>()
+                    .map_err(|e| anyhow!("unexpected ONNX output type: {}", e))?;
+                Ok((view.shape().to_vec(), view.iter().copied().collect()))
+            })
+            .collect()
+    }
+
+    /// Scale `frame` to fit the model input, keeping its aspect ratio, and
+    /// pad the rest with black
+    pub fn letterbox(&self, frame: &Frame) -> Result<(Frame, Letterbox)> {
+        letterbox(frame, self.width, self.height)
+    }
+}
+
+/// Fit `frame` into a `width` x `height` RGB input, centred on black
+pub(crate) fn letterbox(frame: &Frame, width: u32, height: u32) -> Result<(Frame, Letterbox)> {
+    let scale = (width as f32 / frame.width() as f32).min(height as f32 / frame.height() as f32);
+    let scaled_w = ((frame.width() as f32 * scale).round() as u32).clamp(1, width);
+    let scaled_h = ((frame.height() as f32 * scale).round() as u32).clamp(1, height);
+    let pad_x = (width - scaled_w) / 2;
+    let pad_y = (height - scaled_h) / 2;
+
+    let scaled = frame.convert(PixelFormat::Rgb8).resize(scaled_w, scaled_h)?;
+    let (w, sw) = (width as usize, scaled_w as usize);
+    let mut data = vec![0u8; w * height as usize * 3];
+    for (y, row) in scaled.rows().enumerate() {
+        let start = ((y + pad_y as usize) * w + pad_x as usize) * 3;
+        data[start..start + sw * 3].copy_from_slice(row);
+    }
+
+    let input = Frame::new(width, height, PixelFormat::Rgb8, data, frame.timestamp())?;
+    Ok((
+        input,
+        Letterbox {
+            scale,
+            pad_x: pad_x as f32,
+            pad_y: pad_y as f32,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letterbox_pads_and_maps_back() {
+        // 640x480 gray frame with a white pixel block at (320..330, 100..110)
+        let mut data = vec![50u8; 640 * 480];
+        for y in 100..110 {
+            data[y * 640 + 320..y * 640 + 330].fill(255);
+        }
+        let frame = Frame::new(640, 480, PixelFormat::Gray8, data, 9).unwrap();
+        let (input, letterbox) = letterbox(&frame, 128, 128).unwrap();
+        assert_eq!((input.width(), input.height(), input.format(), input.timestamp()), (128, 128, PixelFormat::Rgb8, 9));
+        assert_eq!((letterbox.scale, letterbox.pad_x, letterbox.pad_y), (0.2, 0.0, 16.0));
+
+        // Bars above and below are black, the picture sits between them
+        assert_eq!(input.get_pixel(64, 15).unwrap().r, 0);
+        assert_eq!(input.get_pixel(64, 112).unwrap().r, 0);
+        assert_eq!(input.get_pixel(10, 16).unwrap().r, 50);
+        assert_eq!(input.get_pixel(65, 16 + 21).unwrap().r, 255);
+
+        assert_eq!(letterbox.to_frame(0.0, 16.0), (0.0, 0.0));
+        assert_eq!(letterbox.to_frame(64.0, 64.0), (320.0, 240.0));
+        assert_eq!(letterbox.to_frame(128.0, 112.0), (640.0, 480.0));
+    }
+
+    #[test]
+    fn letterbox_tall_frames_pad_the_sides() {
+        let frame = Frame::new(100, 200, PixelFormat::Gray8, vec![200; 100 * 200], 0).unwrap();
+        let (input, letterbox) = letterbox(&frame, 320, 240).unwrap();
+        assert_eq!((letterbox.scale, letterbox.pad_x, letterbox.pad_y), (1.2, 100.0, 0.0));
+        assert_eq!(input.get_pixel(99, 120).unwrap().r, 0);
+        assert_eq!(input.get_pixel(100, 120).unwrap().r, 200);
+        assert_eq!(input.get_pixel(219, 120).unwrap().r, 200);
+        assert_eq!(input.get_pixel(220, 120).unwrap().r, 0);
+        let (x, y) = letterbox.to_frame(160.0, 120.0);
+        assert!((x - 50.0).abs() < 1e-4 && (y - 100.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Decision {
+    Accept,
+    Reject,
+    /// The best candidate passes its threshold but the runner-up is too close
+    Ambiguous,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Candidate {
+    pub identity_id: u32,
+    pub name: String,
+    pub similarity: f32,
+}
+
+/// Outcome of matching one embedding against the database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecognitionResult {
+    /// Best first
+    pub candidates: Vec<Candidate>,
+    /// Similarity of the first candidate minus the second, if there are two
+    pub margin: Option<f32>,
+    pub decision: Decision,
+}
+
+impl RecognitionResult {
+    pub fn best(&self) -> Option<&Candidate> {
+        self.candidates.first()
+    }
+
+    /// The best candidate, only if it was accepted
+    pub fn accepted(&self) -> Option<&Candidate> {
+        self.best().filter(|_| self.decision == Decision::Accept)
+    }
+}
+
+/// Open-set decision rules applied to the ranked candidates
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecisionRules {
+    /// Minimum cosine similarity to accept a candidate
+    pub threshold: f32,
+    /// Minimum lead of the best candidate over the runner-up
+    pub margin: f32,
+    /// Candidates to keep; the margin rule needs at least two
+    pub top_k: usize,
+    /// Thresholds overriding `threshold` for particular identities
+    #[serde(default)]
+    pub per_identity: HashMap<u32, f32>,
+}
+
+impl Default for DecisionRules {
+    fn default() -> Self {
+        Self {
+            threshold: 0.45,
+            margin: 0.05,
+            top_k: 5,
+            per_identity: HashMap::new(),
+        }
+    }
+}
+
+impl DecisionRules {
+    pub fn with_identity_threshold(mut self, identity_id: u32, threshold: f32) -> Self {
+        self.per_identity.insert(identity_id, threshold);
+        self
+    }
+
+    pub fn threshold_for(&self, identity_id: u32) -> f32 {
+        self.per_identity.get(&identity_id).copied().unwrap_or(self.threshold)
+    }
+
+    /// Decide on candidates sorted best first
+    pub fn decide(&self, candidates: Vec<Candidate>) -> RecognitionResult {
+        let margin = match candidates.as_slice() {
+            [first, second, ..] => Some(first.similarity - second.similarity),
+            _ => None,
+        };
+
+        let decision = match candidates.first() {
+            Some(best) if best.similarity >= self.threshold_for(best.identity_id) => {
+                if margin.is_some_and(|m| m < self.margin) {
+                    Decision::Ambiguous
+                } else {
+                    Decision::Accept
+                }
+            }
+            _ => Decision::Reject,
+        };
+
+        RecognitionResult {
+            candidates,
+            margin,
+            decision,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(scores: &[(u32, f32)]) -> Vec<Candidate> {
+        scores
+            .iter()
+            .map(|&(identity_id, similarity)| Candidate {
+                identity_id,
+                name: format!("#{}", identity_id),
+                similarity,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn accepts_at_the_threshold() {
+        let rules = DecisionRules::default();
+        let result = rules.decide(candidates(&[(1, 0.45)]));
+        assert_eq!(result.decision, Decision::Accept);
+        assert_eq!(result.margin, None);
+        assert_eq!(result.accepted().map(|c| c.identity_id), Some(1));
+
+        let result = rules.decide(candidates(&[(1, 0.9), (2, 0.3)]));
+        assert_eq!(result.decision, Decision::Accept);
+        assert!((result.margin.unwrap() - 0.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn weak_best_match_is_rejected() {
+        let rules = DecisionRules::default();
+        let result = rules.decide(candidates(&[(1, 0.449), (2, 0.1)]));
+        assert_eq!(result.decision, Decision::Reject);
+        // The candidates stay for auditing, but nobody is accepted
+        assert_eq!(result.best().map(|c| c.identity_id), Some(1));
+        assert!(result.accepted().is_none());
+    }
+
+    #[test]
+    fn close_runner_up_is_ambiguous() {
+        let rules = DecisionRules::default();
+        let result = rules.decide(candidates(&[(1, 0.80), (2, 0.77)]));
+        assert_eq!(result.decision, Decision::Ambiguous);
+        assert!(result.accepted().is_none());
+
+        // Both over the threshold is fine with a wide enough lead
+        assert_eq!(rules.decide(candidates(&[(1, 0.80), (2, 0.74)])).decision, Decision::Accept);
+        // A close runner-up doesn't matter when the best is rejected anyway
+        assert_eq!(rules.decide(candidates(&[(1, 0.40), (2, 0.39)])).decision, Decision::Reject);
+    }
+
+    #[test]
+    fn no_candidates_is_unknown() {
+        let result = DecisionRules::default().decide(Vec::new());
+        assert_eq!(result.decision, Decision::Reject);
+        assert_eq!(result.margin, None);
+        assert!(result.best().is_none());
+    }
+
+    #[test]
+    fn per_identity_thresholds() {
+        let rules = DecisionRules::default().with_identity_threshold(7, 0.8).with_identity_threshold(8, 0.3);
+        assert_eq!(rules.threshold_for(7), 0.8);
+        assert_eq!(rules.threshold_for(1), 0.45);
+        assert_eq!(rules.decide(candidates(&[(7, 0.7)])).decision, Decision::Reject);
+        assert_eq!(rules.decide(candidates(&[(8, 0.35)])).decision, Decision::Accept);
+        assert_eq!(rules.decide(candidates(&[(1, 0.35)])).decision, Decision::Reject);
+    }
+
+    #[test]
+    fn margin_rule_can_be_disabled() {
+        let rules = DecisionRules {
+            margin: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(rules.decide(candidates(&[(1, 0.6), (2, 0.6)])).decision, Decision::Accept);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::frame::Frame;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Identifies the model that produced a set of embeddings. Vectors from
+/// different models (or versions, or dimensions) are not comparable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbeddingModel {
+    pub name: String,
+    pub version: String,
+    pub dim: usize,
+}
+
+impl EmbeddingModel {
+    pub fn new(name: impl Into<String>, version: impl Into<String>, dim: usize) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+            dim,
+        }
+    }
+}
+
+impl fmt::Display for EmbeddingModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{} ({}-dim)", self.name, self.version, self.dim)
+    }
+}
+
+/// Turns an aligned face crop (see [`crate::alignment::align_face`]) into an
+/// L2-normalised embedding vector
+pub trait FaceEmbedder {
+    fn model(&self) -> &EmbeddingModel;
+
+    fn embed(&mut self, face: &Frame) -> Result<Vec<f32>>;
+}
+
+/// Scale `v` to unit length in place; zero vectors are left untouched
+pub fn l2_normalize(v: &mut [f32]) {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for x in v.iter_mut() {
+            *x /= norm;
+        }
+    }
+}
@@ -0,0 +1,586 @@
+use super::embedding::l2_normalize;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// Which stored vector an index entry refers to. `sample_id` 0 is the
+/// identity's template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VectorKey {
+    pub identity_id: u32,
+    pub sample_id: u32,
+}
+
+/// Nearest-neighbour search over L2-normalised embeddings by cosine
+/// similarity. Vectors are normalised on insert and queries on search.
+pub trait EmbeddingIndex {
+    /// Insert or replace the vector stored under `key`. Fails if its
+    /// dimension differs from the vectors already stored.
+    fn insert(&mut self, key: VectorKey, vector: &[f32]) -> Result<()>;
+
+    fn remove(&mut self, key: VectorKey) -> bool;
+
+    /// Up to `k` entries, most similar first
+    fn search(&self, query: &[f32], k: usize) -> Vec<(VectorKey, f32)>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn clear(&mut self);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum IndexKind {
+    /// Exact brute-force scan
+    #[default]
+    Flat,
+    /// Approximate HNSW graph, for databases with thousands of samples
+    Hnsw,
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let mut v = vector.to_vec();
+    l2_normalize(&mut v);
+    v
+}
+
+/// Dot product with independent accumulators so the loop vectorises
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let mut acc = [0.0f32; 8];
+    let chunks_a = a.chunks_exact(8);
+    let chunks_b = b.chunks_exact(8);
+    let tail: f32 = chunks_a.remainder().iter().zip(chunks_b.remainder()).map(|(x, y)| x * y).sum();
+    for (ca, cb) in chunks_a.zip(chunks_b) {
+        for i in 0..8 {
+            acc[i] += ca[i] * cb[i];
+        }
+    }
+    acc.iter().sum::<f32>() + tail
+}
+
+fn check_dim(vector: &[f32], dim: usize) -> Result<()> {
+    if vector.is_empty() || vector.len() != dim {
+        bail!("expected a {}-dim vector, got {}", dim, vector.len());
+    }
+    Ok(())
+}
+
+fn sort_by_score(results: &mut [(VectorKey, f32)]) {
+    results.sort_by(|a, b| b.1.total_cmp(&a.1));
+}
+
+/// Exact index keeping every vector in one contiguous buffer
+#[derive(Debug, Clone, Default)]
+pub struct FlatIndex {
+    dim: usize,
+    data: Vec<f32>,
+    keys: Vec<VectorKey>,
+    positions: HashMap<VectorKey, usize>,
+}
+
+impl FlatIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn row(&self, index: usize) -> &[f32] {
+        &self.data[index * self.dim..(index + 1) * self.dim]
+    }
+}
+
+impl EmbeddingIndex for FlatIndex {
+    fn insert(&mut self, key: VectorKey, vector: &[f32]) -> Result<()> {
+        if self.keys.is_empty() {
+            self.dim = vector.len();
+        }
+        check_dim(vector, self.dim)?;
+        let vector = normalized(vector);
+        match self.positions.get(&key) {
+            Some(&pos) => self.data[pos * self.dim..(pos + 1) * self.dim].copy_from_slice(&vector),
+            None => {
+                self.positions.insert(key, self.keys.len());
+                self.keys.push(key);
+                self.data.extend_from_slice(&vector);
+            }
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, key: VectorKey) -> bool {
+        let Some(pos) = self.positions.remove(&key) else {
+            return false;
+        };
+        // Swap-remove, moving the last row into the hole
+        let last = self.keys.len() - 1;
+        if pos != last {
+            let (dim, moved) = (self.dim, self.keys[last]);
+            self.data.copy_within(last * dim..(last + 1) * dim, pos * dim);
+            self.keys[pos] = moved;
+            self.positions.insert(moved, pos);
+        }
+        self.keys.pop();
+        self.data.truncate(last * self.dim);
+        true
+    }
+
+    fn search(&self, query: &[f32], k: usize) -> Vec<(VectorKey, f32)> {
+        if k == 0 || query.len() != self.dim {
+            return Vec::new();
+        }
+        let query = normalized(query);
+        let mut results: Vec<_> = self
+            .keys
+            .iter()
+            .enumerate()
+            .map(|(i, &key)| (key, dot(&query, self.row(i))))
+            .collect();
+        if results.len() > k {
+            results.select_nth_unstable_by(k - 1, |a, b| b.1.total_cmp(&a.1));
+            results.truncate(k);
+        }
+        sort_by_score(&mut results);
+        results
+    }
+
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HnswConfig {
+    /// Neighbours kept per node on the upper layers (twice this on layer 0)
+    pub m: usize,
+    pub ef_construction: usize,
+    pub ef_search: usize,
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 100,
+            ef_search: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct HnswNode {
+    key: VectorKey,
+    vector: Vec<f32>,
+    neighbors: Vec<Vec<usize>>, // one list per layer
+    deleted: bool,
+}
+
+/// Candidate ordered by similarity
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Scored(f32, usize);
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+/// Hierarchical navigable small-world graph. Removal tombstones nodes; the
+/// graph is rebuilt once half of it is dead.
+#[derive(Debug, Clone)]
+pub struct HnswIndex {
+    config: HnswConfig,
+    nodes: Vec<HnswNode>,
+    positions: HashMap<VectorKey, usize>,
+    entry: Option<usize>,
+    rng: u64,
+}
+
+impl Default for HnswIndex {
+    fn default() -> Self {
+        Self::new(HnswConfig::default())
+    }
+}
+
+impl HnswIndex {
+    pub fn new(config: HnswConfig) -> Self {
+        Self {
+            config,
+            nodes: Vec::new(),
+            positions: HashMap::new(),
+            entry: None,
+            rng: 0x9e37_79b9_7f4a_7c15,
+        }
+    }
+
+    pub fn config(&self) -> HnswConfig {
+        self.config
+    }
+
+    /// Geometric layer draw with p = 1/m (xorshift, deterministic per index)
+    fn random_level(&mut self) -> usize {
+        let m = self.config.m.max(2) as f64;
+        let mut level = 0;
+        loop {
+            self.rng ^= self.rng << 13;
+            self.rng ^= self.rng >> 7;
+            self.rng ^= self.rng << 17;
+            let u = (self.rng >> 11) as f64 / (1u64 << 53) as f64;
+            if u >= 1.0 / m || level >= 16 {
+                return level;
+            }
+            level += 1;
+        }
+    }
+
+    fn similarity(&self, query: &[f32], node: usize) -> f32 {
+        dot(query, &self.nodes[node].vector)
+    }
+
+    fn max_neighbors(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.config.m * 2
+        } else {
+            self.config.m
+        }
+    }
+
+    /// Best-first search of one layer, returning up to `ef` nodes, best first
+    fn search_layer(&self, query: &[f32], entry: usize, ef: usize, layer: usize) -> Vec<Scored> {
+        let mut visited = HashSet::from([entry]);
+        let first = Scored(self.similarity(query, entry), entry);
+        let mut candidates = BinaryHeap::from([first]);
+        // Min-heap of the current results via Reverse ordering
+        let mut results = BinaryHeap::from([std::cmp::Reverse(first)]);
+
+        while let Some(current) = candidates.pop() {
+            let worst = results.peek().map_or(f32::MIN, |r| r.0 .0);
+            if current.0 < worst && results.len() >= ef {
+                break;
+            }
+            for &next in self.nodes[current.1].neighbors.get(layer).into_iter().flatten() {
+                if !visited.insert(next) {
+                    continue;
+                }
+                let scored = Scored(self.similarity(query, next), next);
+                let worst = results.peek().map_or(f32::MIN, |r| r.0 .0);
+                if results.len() < ef || scored.0 > worst {
+                    candidates.push(scored);
+                    results.push(std::cmp::Reverse(scored));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        let mut found: Vec<_> = results.into_iter().map(|r| r.0).collect();
+        found.sort_by(|a, b| b.cmp(a));
+        found
+    }
+
+    fn greedy_descent(&self, query: &[f32], mut entry: usize, from: usize, to: usize) -> usize {
+        for layer in (to..=from).rev() {
+            entry = self.search_layer(query, entry, 1, layer)[0].1;
+        }
+        entry
+    }
+
+    fn top_level(&self) -> usize {
+        self.entry.map_or(0, |e| self.nodes[e].neighbors.len() - 1)
+    }
+
+    fn link(&mut self, node: usize, neighbors: &[Scored], layer: usize) {
+        let limit = self.max_neighbors(layer);
+        self.nodes[node].neighbors[layer] = neighbors.iter().take(limit).map(|s| s.1).collect();
+
+        for &Scored(_, other) in neighbors.iter().take(limit) {
+            self.nodes[other].neighbors[layer].push(node);
+            if self.nodes[other].neighbors[layer].len() > limit {
+                // Keep the closest neighbours of the overfull node
+                let base = self.nodes[other].vector.clone();
+                let mut scored: Vec<_> = self.nodes[other].neighbors[layer]
+                    .iter()
+                    .map(|&n| Scored(dot(&base, &self.nodes[n].vector), n))
+                    .collect();
+                scored.sort_by(|a, b| b.cmp(a));
+                self.nodes[other].neighbors[layer] = scored.into_iter().take(limit).map(|s| s.1).collect();
+            }
+        }
+    }
+
+    fn rebuild(&mut self) {
+        let live: Vec<_> = self
+            .nodes
+            .drain(..)
+            .filter(|n| !n.deleted)
+            .map(|n| (n.key, n.vector))
+            .collect();
+        self.positions.clear();
+        self.entry = None;
+        for (key, vector) in live {
+            self.insert_normalized(key, vector);
+        }
+    }
+
+    fn insert_normalized(&mut self, key: VectorKey, vector: Vec<f32>) {
+        let level = self.random_level();
+        let id = self.nodes.len();
+        self.nodes.push(HnswNode {
+            key,
+            vector,
+            neighbors: vec![Vec::new(); level + 1],
+            deleted: false,
+        });
+        self.positions.insert(key, id);
+
+        let Some(entry) = self.entry else {
+            self.entry = Some(id);
+            return;
+        };
+
+        let query = self.nodes[id].vector.clone();
+        let top = self.top_level();
+        let mut entry = if top > level {
+            self.greedy_descent(&query, entry, top, level + 1)
+        } else {
+            entry
+        };
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&query, entry, self.config.ef_construction, layer);
+            entry = found[0].1;
+            self.link(id, &found, layer);
+        }
+        if level > top {
+            self.entry = Some(id);
+        }
+    }
+}
+
+impl EmbeddingIndex for HnswIndex {
+    fn insert(&mut self, key: VectorKey, vector: &[f32]) -> Result<()> {
+        check_dim(vector, self.nodes.first().map_or(vector.len(), |n| n.vector.len()))?;
+        self.remove(key);
+        self.insert_normalized(key, normalized(vector));
+        Ok(())
+    }
+
+    fn remove(&mut self, key: VectorKey) -> bool {
+        let Some(id) = self.positions.remove(&key) else {
+            return false;
+        };
+        self.nodes[id].deleted = true;
+        if self.positions.len() * 2 < self.nodes.len() {
+            self.rebuild();
+        }
+        true
+    }
+
+    fn search(&self, query: &[f32], k: usize) -> Vec<(VectorKey, f32)> {
+        let Some(entry) = self.entry else {
+            return Vec::new();
+        };
+        if k == 0 || query.len() != self.nodes[entry].vector.len() {
+            return Vec::new();
+        }
+
+        let query = normalized(query);
+        let entry = self.greedy_descent(&query, entry, self.top_level(), 1);
+        // Over-fetch so tombstoned nodes don't starve the result
+        let ef = self.config.ef_search.max(k) + (self.nodes.len() - self.positions.len()).min(k);
+        self.search_layer(&query, entry, ef, 0)
+            .into_iter()
+            .filter(|s| !self.nodes[s.1].deleted)
+            .take(k)
+            .map(|s| (self.nodes[s.1].key, s.0))
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    fn clear(&mut self) {
+        *self = Self::new(self.config);
+    }
+}
+
+/// The index an [`super::IdentityDatabase`] maintains, chosen by [`IndexKind`]
+#[derive(Debug, Clone)]
+pub(crate) enum AnyIndex {
+    Flat(FlatIndex),
+    Hnsw(HnswIndex),
+}
+
+impl AnyIndex {
+    pub fn new(kind: IndexKind) -> Self {
+        match kind {
+            IndexKind::Flat => AnyIndex::Flat(FlatIndex::new()),
+            IndexKind::Hnsw => AnyIndex::Hnsw(HnswIndex::default()),
+        }
+    }
+
+    fn inner(&self) -> &dyn EmbeddingIndex {
+        match self {
+            AnyIndex::Flat(index) => index,
+            AnyIndex::Hnsw(index) => index,
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut dyn EmbeddingIndex {
+        match self {
+            AnyIndex::Flat(index) => index,
+            AnyIndex::Hnsw(index) => index,
+        }
+    }
+}
+
+impl Default for AnyIndex {
+    fn default() -> Self {
+        Self::new(IndexKind::default())
+    }
+}
+
+impl EmbeddingIndex for AnyIndex {
+    fn insert(&mut self, key: VectorKey, vector: &[f32]) -> Result<()> {
+        self.inner_mut().insert(key, vector)
+    }
+
+    fn remove(&mut self, key: VectorKey) -> bool {
+        self.inner_mut().remove(key)
+    }
+
+    fn search(&self, query: &[f32], k: usize) -> Vec<(VectorKey, f32)> {
+        self.inner().search(query, k)
+    }
+
+    fn len(&self) -> usize {
+        self.inner().len()
+    }
+
+    fn clear(&mut self) {
+        self.inner_mut().clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn vector(&mut self, dim: usize) -> Vec<f32> {
+            (0..dim)
+                .map(|_| {
+                    self.0 ^= self.0 << 13;
+                    self.0 ^= self.0 >> 7;
+                    self.0 ^= self.0 << 17;
+                    (self.0 >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+                })
+                .collect()
+        }
+    }
+
+    fn key(i: usize) -> VectorKey {
+        VectorKey {
+            identity_id: i as u32 / 4,
+            sample_id: i as u32 % 4,
+        }
+    }
+
+    fn keys(results: &[(VectorKey, f32)]) -> Vec<VectorKey> {
+        results.iter().map(|(key, _)| *key).collect()
+    }
+
+    #[test]
+    fn wrong_dimension_is_rejected() {
+        let indexes: [&mut dyn EmbeddingIndex; 2] = [&mut FlatIndex::new(), &mut HnswIndex::default()];
+        for index in indexes {
+            index.insert(key(0), &[1.0, 0.0, 0.0]).unwrap();
+            assert!(index.insert(key(1), &[1.0, 0.0]).is_err());
+            assert!(index.insert(key(1), &[]).is_err());
+            assert_eq!(index.len(), 1);
+        }
+    }
+
+    #[test]
+    fn hnsw_top_k_matches_flat() {
+        const DIM: usize = 64;
+        let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+        let stored: Vec<_> = (0..600).map(|_| rng.vector(DIM)).collect();
+        let mut flat = FlatIndex::new();
+        let mut hnsw = HnswIndex::default();
+        for (i, v) in stored.iter().enumerate() {
+            flat.insert(key(i), v).unwrap();
+            hnsw.insert(key(i), v).unwrap();
+        }
+
+        let (mut found, mut total) = (0, 0);
+        for i in (0..stored.len()).step_by(7) {
+            let query: Vec<f32> = stored[i].iter().zip(rng.vector(DIM)).map(|(x, n)| x + 0.3 * n).collect();
+            let exact = keys(&flat.search(&query, 5));
+            let approx = hnsw.search(&query, 5);
+            assert_eq!(approx[0].0, exact[0], "query {}", i);
+            assert!(approx.windows(2).all(|w| w[0].1 >= w[1].1));
+            found += keys(&approx).iter().filter(|k| exact.contains(k)).count();
+            total += exact.len();
+        }
+        // Recall of the approximate index on random data
+        assert!(found as f32 / total as f32 >= 0.95, "recall {}/{}", found, total);
+    }
+
+    #[test]
+    fn remove_and_rebuild() {
+        const DIM: usize = 16;
+        let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+        let stored: Vec<_> = (0..200).map(|_| rng.vector(DIM)).collect();
+        let mut flat = FlatIndex::new();
+        let mut hnsw = HnswIndex::default();
+        for (i, v) in stored.iter().enumerate() {
+            flat.insert(key(i), v).unwrap();
+            hnsw.insert(key(i), v).unwrap();
+        }
+
+        // Removing most entries makes the HNSW graph rebuild itself
+        for i in (0..stored.len()).filter(|i| i % 3 != 0) {
+            assert!(flat.remove(key(i)));
+            assert!(hnsw.remove(key(i)));
+            assert!(!hnsw.remove(key(i)));
+        }
+        assert_eq!(flat.len(), 67);
+        assert_eq!(hnsw.len(), 67);
+        for (i, v) in stored.iter().enumerate() {
+            let expected = (i % 3 == 0).then_some(key(i));
+            assert_eq!(flat.search(v, 1).first().map(|r| r.0).filter(|k| *k == key(i)), expected, "flat {}", i);
+            assert_eq!(hnsw.search(v, 1).first().map(|r| r.0).filter(|k| *k == key(i)), expected, "hnsw {}", i);
+        }
+
+        // Re-inserting a key replaces its vector
+        hnsw.insert(key(0), &stored[1]).unwrap();
+        assert_eq!(hnsw.len(), 67);
+        assert_eq!(hnsw.search(&stored[1], 1)[0].0, key(0));
+
+        flat.clear();
+        hnsw.clear();
+        assert!(flat.is_empty() && hnsw.is_empty());
+        assert!(hnsw.search(&stored[0], 3).is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Group an identity belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum IdentityRole {
+    #[default]
+    Resident,
+    Staff,
+    Visitor,
+    /// Recognition raises `Blacklisted` events
+    Blacklisted,
+}
+
+impl IdentityRole {
+    pub const ALL: [IdentityRole; 4] = [
+        IdentityRole::Resident,
+        IdentityRole::Staff,
+        IdentityRole::Visitor,
+        IdentityRole::Blacklisted,
+    ];
+
+    /// Rank used when two identities are merged, the higher one winning:
+    /// blacklisted over visitor over staff over resident
+    fn restriction(&self) -> u8 {
+        match self {
+            IdentityRole::Resident => 0,
+            IdentityRole::Staff => 1,
+            IdentityRole::Visitor => 2,
+            IdentityRole::Blacklisted => 3,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            IdentityRole::Resident => "Resident",
+            IdentityRole::Staff => "Staff",
+            IdentityRole::Visitor => "Visitor",
+            IdentityRole::Blacklisted => "Blacklisted",
+        }
+    }
+}
+
+/// Descriptive data attached to an identity, not used for matching
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IdentityMetadata {
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub role: IdentityRole,
+    /// Validity window in ms since the epoch; open-ended when `None`
+    #[serde(default)]
+    pub valid_from: Option<u64>,
+    #[serde(default)]
+    pub valid_until: Option<u64>,
+    /// Free-form key/value pairs (employee number, department, ...)
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl IdentityMetadata {
+    pub fn with_role(mut self, role: IdentityRole) -> Self {
+        self.role = role;
+        self
+    }
+
+    /// Add a tag unless it is blank or already present
+    pub fn add_tag(&mut self, tag: &str) {
+        let tag = tag.trim();
+        if !tag.is_empty() && !self.tags.iter().any(|t| t == tag) {
+            self.tags.push(tag.to_string());
+        }
+    }
+
+    pub fn is_valid_at(&self, timestamp: u64) -> bool {
+        self.valid_from.is_none_or(|from| timestamp >= from) && self.valid_until.is_none_or(|until| timestamp <= until)
+    }
+
+    /// Fill in anything `other` has that this one lacks, and keep the
+    /// stricter role and the narrower validity window of the two
+    pub(crate) fn absorb(&mut self, other: IdentityMetadata) {
+        if other.role.restriction() > self.role.restriction() {
+            self.role = other.role;
+        }
+        self.valid_from = self.valid_from.max(other.valid_from);
+        self.valid_until = match (self.valid_until, other.valid_until) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        if self.notes.is_empty() {
+            self.notes = other.notes;
+        }
+        for tag in &other.tags {
+            self.add_tag(tag);
+        }
+        for (key, value) in other.attributes {
+            self.attributes.entry(key).or_insert(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn absorb_keeps_the_stricter_terms() {
+        let mut kept = IdentityMetadata {
+            notes: "front desk".into(),
+            tags: vec!["a".into()],
+            valid_from: Some(100),
+            ..Default::default()
+        };
+        let mut other = IdentityMetadata::default().with_role(IdentityRole::Blacklisted);
+        other.notes = "ignored".into();
+        other.tags = vec!["a".into(), "b".into()];
+        other.valid_from = Some(50);
+        other.valid_until = Some(500);
+        kept.absorb(other);
+
+        assert_eq!(kept.role, IdentityRole::Blacklisted);
+        assert_eq!((kept.valid_from, kept.valid_until), (Some(100), Some(500)));
+        assert_eq!(kept.notes, "front desk");
+        assert_eq!(kept.tags, ["a", "b"]);
+
+        // A milder role never replaces a stricter one
+        kept.absorb(IdentityMetadata::default().with_role(IdentityRole::Staff));
+        assert_eq!(kept.role, IdentityRole::Blacklisted);
+        assert_eq!((kept.valid_from, kept.valid_until), (Some(100), Some(500)));
+    }
+
+    #[test]
+    fn roles_rank_by_restriction() {
+        let mut ranked = IdentityRole::ALL;
+        ranked.sort_by_key(|role| role.restriction());
+        assert_eq!(ranked, IdentityRole::ALL);
+    }
+}
@@ -0,0 +1,766 @@
+use crate::clock::{default_clock, SharedClock};
+use crate::events::{EventLog, EventType};
+use anyhow::{anyhow, bail, Result};
+use index::AnyIndex;
+use serde::{Deserialize, Serialize};
+
+mod decision;
+mod embedding;
+mod index;
+mod metadata;
+#[cfg(feature = "onnx")]
+mod onnx;
+mod sample;
+
+pub use decision::{Candidate, Decision, DecisionRules, RecognitionResult};
+pub use embedding::{l2_normalize, EmbeddingModel, FaceEmbedder};
+pub use index::{EmbeddingIndex, FlatIndex, HnswConfig, HnswIndex, IndexKind, VectorKey};
+pub use metadata::{IdentityMetadata, IdentityRole};
+pub use sample::{FaceSample, MatchStrategy};
+#[cfg(feature = "onnx")]
+pub use onnx::{OnnxEmbedderConfig, OnnxFaceEmbedder};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaceIdentity {
+    pub id: u32,
+    pub name: String,
+    pub confidence: f32,
+    /// Template (mean of `samples`), L2-normalised, see IdentityDatabase::model
+    pub embedding: Option<Vec<f32>>,
+    pub created_at: u64,
+    pub last_seen: u64,
+    #[serde(default)]
+    pub samples: Vec<FaceSample>,
+    #[serde(default)]
+    next_sample_id: u32,
+    #[serde(default)]
+    pub metadata: IdentityMetadata,
+}
+
+impl FaceIdentity {
+    pub fn new(id: u32, name: String, now: u64) -> Self {
+        Self {
+            id,
+            name,
+            confidence: 0.0,
+            embedding: None,
+            created_at: now,
+            last_seen: now,
+            samples: Vec::new(),
+            next_sample_id: 1,
+            metadata: IdentityMetadata::default(),
+        }
+    }
+
+    pub fn with_embedding(mut self, embedding: Vec<f32>) -> Self {
+        self.embedding = Some(embedding);
+        self
+    }
+
+    /// Store a sample under a fresh id and refresh the template
+    pub fn add_sample(&mut self, mut sample: FaceSample) -> u32 {
+        // Identities saved before samples existed deserialize with 0
+        self.next_sample_id = self.next_sample_id.max(1);
+        sample.id = self.next_sample_id;
+        self.next_sample_id += 1;
+        self.samples.push(sample);
+        self.recompute_template();
+        self.next_sample_id - 1
+    }
+
+    /// Take a sample out and refresh the template. Removing the last one
+    /// clears the template too, so the identity no longer matches anyone.
+    pub fn remove_sample(&mut self, sample_id: u32) -> Option<FaceSample> {
+        let index = self.samples.iter().position(|s| s.id == sample_id)?;
+        let sample = self.samples.remove(index);
+        if self.samples.is_empty() {
+            self.embedding = None;
+        }
+        self.recompute_template();
+        Some(sample)
+    }
+
+    /// Turn a legacy identity's template into its only sample, so that
+    /// adding samples later doesn't replace it
+    fn template_to_sample(&mut self) {
+        if !self.samples.is_empty() {
+            return;
+        }
+        if let Some(embedding) = self.embedding.clone() {
+            self.add_sample(FaceSample::new(embedding, 1.0, self.created_at));
+        }
+    }
+
+    /// Rebuild `embedding` from the samples. Identities without samples
+    /// keep whatever template they were created with.
+    pub fn recompute_template(&mut self) {
+        if let Some(template) = sample::mean_template(&self.samples) {
+            self.embedding = Some(template);
+        }
+    }
+
+    /// Event to raise when this identity is recognized at `timestamp`:
+    /// blacklisted identities and visits outside the validity window are
+    /// flagged rather than logged as plain recognitions
+    pub fn event_type_at(&self, timestamp: u64) -> EventType {
+        if self.metadata.role == IdentityRole::Blacklisted {
+            EventType::Blacklisted
+        } else if !self.metadata.is_valid_at(timestamp) {
+            EventType::AfterHours
+        } else {
+            EventType::FaceRecognized
+        }
+    }
+
+    /// Similarity of `query` to this identity, `None` if it has no embedding
+    pub fn similarity(&self, query: &[f32], strategy: MatchStrategy) -> Option<f32> {
+        match strategy {
+            MatchStrategy::MaxOverSamples if !self.samples.is_empty() => self
+                .samples
+                .iter()
+                .map(|s| cosine_similarity(query, &s.embedding))
+                .reduce(f32::max),
+            _ => self.embedding.as_ref().map(|emb| cosine_similarity(query, emb)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StoredDatabase")]
+pub struct IdentityDatabase {
+    identities: Vec<FaceIdentity>,
+    next_id: u32,
+    /// Model every stored embedding came from; set by the first enrolment
+    #[serde(default)]
+    model: Option<EmbeddingModel>,
+    #[serde(default)]
+    strategy: MatchStrategy,
+    #[serde(default)]
+    index_kind: IndexKind,
+    /// Rebuilt from `identities` on load, then maintained on every change
+    #[serde(skip)]
+    index: AnyIndex,
+    /// Identities left out of `index`, see `unindexed`
+    #[serde(skip)]
+    unindexed: Vec<u32>,
+    #[serde(skip, default = "default_clock")]
+    clock: SharedClock,
+}
+
+/// Serialized form of [`IdentityDatabase`], everything but the index
+#[derive(Deserialize)]
+struct StoredDatabase {
+    identities: Vec<FaceIdentity>,
+    next_id: u32,
+    #[serde(default)]
+    model: Option<EmbeddingModel>,
+    #[serde(default)]
+    strategy: MatchStrategy,
+    #[serde(default)]
+    index_kind: IndexKind,
+}
+
+impl From<StoredDatabase> for IdentityDatabase {
+    fn from(stored: StoredDatabase) -> Self {
+        let mut db = Self {
+            identities: stored.identities,
+            next_id: stored.next_id,
+            model: stored.model,
+            strategy: stored.strategy,
+            index_kind: stored.index_kind,
+            index: AnyIndex::new(stored.index_kind),
+            unindexed: Vec::new(),
+            clock: default_clock(),
+        };
+        db.rebuild_index();
+        db
+    }
+}
+
+impl Default for IdentityDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IdentityDatabase {
+    pub fn new() -> Self {
+        Self {
+            identities: Vec::new(),
+            next_id: 1,
+            model: None,
+            strategy: MatchStrategy::default(),
+            index_kind: IndexKind::default(),
+            index: AnyIndex::default(),
+            unindexed: Vec::new(),
+            clock: default_clock(),
+        }
+    }
+
+    /// Replace the clock used for creation and last-seen times
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    pub fn with_strategy(mut self, strategy: MatchStrategy) -> Self {
+        self.set_strategy(strategy);
+        self
+    }
+
+    pub fn strategy(&self) -> MatchStrategy {
+        self.strategy
+    }
+
+    pub fn set_strategy(&mut self, strategy: MatchStrategy) {
+        self.strategy = strategy;
+        self.rebuild_index();
+    }
+
+    pub fn with_index(mut self, kind: IndexKind) -> Self {
+        self.set_index_kind(kind);
+        self
+    }
+
+    pub fn index_kind(&self) -> IndexKind {
+        self.index_kind
+    }
+
+    pub fn set_index_kind(&mut self, kind: IndexKind) {
+        self.index_kind = kind;
+        self.index = AnyIndex::new(kind);
+        self.rebuild_index();
+    }
+
+    fn rebuild_index(&mut self) {
+        self.index.clear();
+        self.unindexed.clear();
+        let dim = self.dim();
+        for identity in &self.identities {
+            // Index the dimension most of the data has, so one bad record
+            // can't lock out the rest
+            let fits = index_entries(identity, self.strategy)
+                .iter()
+                .all(|(_, vector)| dim.is_none_or(|dim| vector.len() == dim));
+            if fits {
+                index_identity(&mut self.index, &mut self.unindexed, identity, self.strategy);
+            } else {
+                self.unindexed.push(identity.id);
+            }
+        }
+    }
+
+    /// Apply `f` to one identity, keeping its index entries in step
+    fn update_identity<R>(&mut self, id: u32, f: impl FnOnce(&mut FaceIdentity) -> R) -> Option<R> {
+        let identity = self.identities.iter_mut().find(|i| i.id == id)?;
+        unindex_identity(&mut self.index, identity, self.strategy);
+        let result = f(identity);
+        index_identity(&mut self.index, &mut self.unindexed, identity, self.strategy);
+        Some(result)
+    }
+
+    /// Identities that can't be matched because their embeddings don't
+    /// have the database's dimension, e.g. stored data mixing models
+    pub fn unindexed(&self) -> &[u32] {
+        &self.unindexed
+    }
+
+    /// Embedding dimension: the model's, or else the most common one stored
+    fn dim(&self) -> Option<usize> {
+        if let Some(model) = &self.model {
+            return Some(model.dim);
+        }
+        let mut counts = std::collections::HashMap::new();
+        for identity in &self.identities {
+            let vectors = identity.samples.iter().map(|s| &s.embedding).chain(&identity.embedding);
+            for vector in vectors {
+                *counts.entry(vector.len()).or_insert(0) += 1;
+            }
+        }
+        counts.into_iter().max_by_key(|&(dim, count)| (count, dim)).map(|(dim, _)| dim)
+    }
+
+    pub fn model(&self) -> Option<&EmbeddingModel> {
+        self.model.as_ref()
+    }
+
+    /// Check that embeddings from `model` can be stored and compared here,
+    /// adopting it if the database has no model yet
+    pub fn ensure_model(&mut self, model: &EmbeddingModel) -> Result<()> {
+        match &self.model {
+            Some(existing) if existing != model => {
+                bail!("identity database was built with {}, not {}", existing, model)
+            }
+            Some(_) => Ok(()),
+            None => {
+                let mut stored = self.identities.iter().filter_map(|i| i.embedding.as_ref());
+                if let Some(emb) = stored.find(|e| e.len() != model.dim) {
+                    bail!("stored {}-dim embeddings don't match {}", emb.len(), model);
+                }
+                self.model = Some(model.clone());
+                Ok(())
+            }
+        }
+    }
+
+    /// Add an identity built from samples embedded by `model`, refusing
+    /// vectors from a different model than the rest of the database
+    pub fn enroll(&mut self, name: String, samples: Vec<FaceSample>, model: &EmbeddingModel) -> Result<FaceIdentity> {
+        if samples.is_empty() {
+            bail!("enrolling {} needs at least one sample", name);
+        }
+        check_samples(&samples, model)?;
+        self.ensure_model(model)?;
+
+        let mut identity = FaceIdentity::new(self.next_id, name, self.clock.now_ms());
+        self.next_id += 1;
+        for sample in samples {
+            identity.add_sample(sample);
+        }
+        index_identity(&mut self.index, &mut self.unindexed, &identity, self.strategy);
+        self.identities.push(identity.clone());
+        Ok(identity)
+    }
+
+    /// Add a sample to an existing identity (re-training) and return its id
+    pub fn add_sample(&mut self, identity_id: u32, sample: FaceSample, model: &EmbeddingModel) -> Result<u32> {
+        check_samples(std::slice::from_ref(&sample), model)?;
+        self.ensure_model(model)?;
+        self.update_identity(identity_id, |identity| identity.add_sample(sample))
+            .ok_or_else(|| anyhow!("no identity with id {}", identity_id))
+    }
+
+    /// Remove one sample from an identity. The last sample can't be
+    /// removed; remove the identity instead.
+    pub fn remove_sample(&mut self, identity_id: u32, sample_id: u32) -> Result<FaceSample> {
+        let identity = self.get_mut(identity_id)?;
+        if !identity.samples.iter().any(|s| s.id == sample_id) {
+            bail!("identity {} has no sample {}", identity_id, sample_id);
+        }
+        if identity.samples.len() == 1 {
+            bail!("sample {} is the last one of identity {}", sample_id, identity_id);
+        }
+        self.update_identity(identity_id, |identity| identity.remove_sample(sample_id))
+            .flatten()
+            .ok_or_else(|| anyhow!("identity {} has no sample {}", identity_id, sample_id))
+    }
+
+    pub fn recompute_template(&mut self, identity_id: u32) {
+        self.update_identity(identity_id, |identity| identity.recompute_template());
+    }
+
+    pub fn get(&self, id: u32) -> Option<&FaceIdentity> {
+        self.identities.iter().find(|i| i.id == id)
+    }
+
+    /// Add an identity with an optional template, which has to match the
+    /// dimension of the embeddings already stored
+    pub fn add_identity(&mut self, name: String, embedding: Option<Vec<f32>>) -> Result<FaceIdentity> {
+        if let Some(emb) = &embedding {
+            match self.dim() {
+                _ if emb.is_empty() => bail!("identity {} has an empty embedding", name),
+                Some(dim) if emb.len() != dim => bail!("expected a {}-dim embedding, got {}", dim, emb.len()),
+                _ => {}
+            }
+        }
+
+        let mut identity = FaceIdentity::new(self.next_id, name, self.clock.now_ms());
+        self.next_id += 1;
+        
+        if let Some(emb) = embedding {
+            identity = identity.with_embedding(emb);
+        }
+        
+        index_identity(&mut self.index, &mut self.unindexed, &identity, self.strategy);
+        self.identities.push(identity.clone());
+        Ok(identity)
+    }
+
+    pub fn get_all(&self) -> Vec<FaceIdentity> {
+        self.identities.clone()
+    }
+
+    pub fn find_by_embedding(&self, query_embedding: &[f32], threshold: f32) -> Option<(FaceIdentity, f32)> {
+        let (id, similarity) = self.search(query_embedding, 1).into_iter().next()?;
+        if similarity <= threshold {
+            return None;
+        }
+        Some((self.get(id)?.clone(), similarity))
+    }
+
+    /// Rank the closest identities and apply the open-set `rules`
+    pub fn recognize(&self, query: &[f32], rules: &DecisionRules) -> RecognitionResult {
+        let candidates = self
+            .search(query, rules.top_k.max(1))
+            .into_iter()
+            .filter_map(|(id, similarity)| {
+                Some(Candidate {
+                    identity_id: id,
+                    name: self.get(id)?.name.clone(),
+                    similarity,
+                })
+            })
+            .collect();
+        rules.decide(candidates)
+    }
+
+    /// The `k` identities most similar to `query` as `(identity id, score)`,
+    /// best first, scored according to the match strategy
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<(u32, f32)> {
+        // Several entries can belong to one identity; fetch enough to fill k
+        let per_identity = match self.strategy {
+            MatchStrategy::MaxOverSamples => self.identities.iter().map(|i| i.samples.len()).max().unwrap_or(1).max(1),
+            MatchStrategy::MeanTemplate => 1,
+        };
+
+        let mut results: Vec<(u32, f32)> = Vec::with_capacity(k);
+        for (key, score) in self.index.search(query, k.saturating_mul(per_identity)) {
+            if results.len() == k {
+                break;
+            }
+            if !results.iter().any(|(id, _)| *id == key.identity_id) {
+                results.push((key.identity_id, score));
+            }
+        }
+        results
+    }
+
+    pub fn update_last_seen(&mut self, id: u32) {
+        let now = self.clock.now_ms();
+        if let Some(identity) = self.identities.iter_mut().find(|i| i.id == id) {
+            identity.last_seen = now;
+        }
+    }
+
+    pub fn remove_identity(&mut self, id: u32) -> Option<FaceIdentity> {
+        let index = self.identities.iter().position(|i| i.id == id)?;
+        let identity = self.identities.remove(index);
+        unindex_identity(&mut self.index, &identity, self.strategy);
+        self.unindexed.retain(|id| *id != identity.id);
+        Some(identity)
+    }
+
+    pub fn rename(&mut self, id: u32, name: String) -> Result<()> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            bail!("identity name can't be empty");
+        }
+        let identity = self.get_mut(id)?;
+        identity.name = name;
+        Ok(())
+    }
+
+    pub fn set_metadata(&mut self, id: u32, metadata: IdentityMetadata) -> Result<()> {
+        self.get_mut(id)?.metadata = metadata;
+        Ok(())
+    }
+
+    /// Fold two identities into one. The older keeps its id, name and
+    /// metadata and gains the other's samples; of the two roles and validity
+    /// windows the stricter ones survive, so merging never lifts a
+    /// blacklisting. Events that referred to the younger are rewritten to
+    /// point at the survivor. Returns the kept id.
+    pub fn merge(&mut self, a: u32, b: u32, events: &mut EventLog) -> Result<u32> {
+        if a == b {
+            bail!("can't merge identity {} with itself", a);
+        }
+        let created = |id| {
+            self.get(id)
+                .map(|i| i.created_at)
+                .ok_or_else(|| anyhow!("no identity with id {}", id))
+        };
+        let (first, second) = (created(a)?, created(b)?);
+        let (keep, absorb) = if (first, a) <= (second, b) { (a, b) } else { (b, a) };
+
+        let mut absorbed = self.remove_identity(absorb).expect("checked above");
+        let kept_name = self
+            .update_identity(keep, |identity| {
+                // Legacy identities carry only a template
+                identity.template_to_sample();
+                absorbed.template_to_sample();
+                for sample in absorbed.samples {
+                    identity.add_sample(sample);
+                }
+                identity.last_seen = identity.last_seen.max(absorbed.last_seen);
+                identity.metadata.absorb(absorbed.metadata);
+                identity.name.clone()
+            })
+            .expect("checked above");
+
+        events.reassign_identity(absorb, keep, &kept_name);
+        Ok(keep)
+    }
+
+    /// Move `sample_ids` out of identity `id` into a new identity called
+    /// `name`. At least one sample has to stay behind.
+    pub fn split(&mut self, id: u32, sample_ids: &[u32], name: String) -> Result<FaceIdentity> {
+        let source = self.get_mut(id)?;
+        if sample_ids.is_empty() {
+            bail!("no samples selected to split off");
+        }
+        if let Some(missing) = sample_ids.iter().find(|s| !source.samples.iter().any(|x| x.id == **s)) {
+            bail!("identity {} has no sample {}", id, missing);
+        }
+        if source.samples.iter().all(|s| sample_ids.contains(&s.id)) {
+            bail!("splitting off every sample would leave identity {} empty", id);
+        }
+
+        let moved = self
+            .update_identity(id, |identity| {
+                sample_ids
+                    .iter()
+                    .filter_map(|&sample_id| identity.remove_sample(sample_id))
+                    .collect::<Vec<_>>()
+            })
+            .expect("checked above");
+
+        let mut identity = FaceIdentity::new(self.next_id, name, self.clock.now_ms());
+        self.next_id += 1;
+        for sample in moved {
+            identity.add_sample(sample);
+        }
+        index_identity(&mut self.index, &mut self.unindexed, &identity, self.strategy);
+        self.identities.push(identity.clone());
+        Ok(identity)
+    }
+
+    fn get_mut(&mut self, id: u32) -> Result<&mut FaceIdentity> {
+        self.identities
+            .iter_mut()
+            .find(|i| i.id == id)
+            .ok_or_else(|| anyhow!("no identity with id {}", id))
+    }
+}
+
+/// Vectors an identity contributes to the index under `strategy`
+fn index_entries(identity: &FaceIdentity, strategy: MatchStrategy) -> Vec<(VectorKey, &[f32])> {
+    let key = |sample_id| VectorKey {
+        identity_id: identity.id,
+        sample_id,
+    };
+    match strategy {
+        MatchStrategy::MaxOverSamples if !identity.samples.is_empty() => identity
+            .samples
+            .iter()
+            .map(|s| (key(s.id), s.embedding.as_slice()))
+            .collect(),
+        _ => identity.embedding.iter().map(|emb| (key(0), emb.as_slice())).collect(),
+    }
+}
+
+/// Index an identity's vectors, or none of them if any is rejected, and
+/// keep `unindexed` in step
+fn index_identity(index: &mut AnyIndex, unindexed: &mut Vec<u32>, identity: &FaceIdentity, strategy: MatchStrategy) {
+    unindexed.retain(|id| *id != identity.id);
+    let entries = index_entries(identity, strategy);
+    if entries.iter().any(|(key, vector)| index.insert(*key, vector).is_err()) {
+        unindex_identity(index, identity, strategy);
+        unindexed.push(identity.id);
+    }
+}
+
+fn unindex_identity(index: &mut AnyIndex, identity: &FaceIdentity, strategy: MatchStrategy) {
+    for (key, _) in index_entries(identity, strategy) {
+        index.remove(key);
+    }
+}
+
+fn check_samples(samples: &[FaceSample], model: &EmbeddingModel) -> Result<()> {
+    if let Some(sample) = samples.iter().find(|s| s.embedding.len() != model.dim) {
+        bail!("expected a {}-dim embedding from {}, got {}", model.dim, model, sample.embedding.len());
+    }
+    Ok(())
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    let dot: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+    let mag_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let mag_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if mag_a == 0.0 || mag_b == 0.0 {
+        return 0.0;
+    }
+
+    dot / (mag_a * mag_b)
+}
+
+// Legacy function for compatibility
+pub fn recognize_faces() -> Vec<FaceIdentity> {
+    vec![]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use std::sync::Arc;
+
+    fn axis(i: usize) -> Vec<f32> {
+        let mut v = vec![0.0; 4];
+        v[i] = 1.0;
+        v
+    }
+
+    /// A template-only identity and an enrolled one, in either age order
+    fn legacy_and_sampled(legacy_first: bool) -> (IdentityDatabase, u32, u32) {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let mut db = IdentityDatabase::new().with_clock(clock.clone());
+        let model = EmbeddingModel::new("test", "1", 4);
+        let enroll = |db: &mut IdentityDatabase| {
+            let samples = vec![FaceSample::new(axis(1), 1.0, 0), FaceSample::new(axis(2), 1.0, 0)];
+            db.enroll("sampled".into(), samples, &model).unwrap().id
+        };
+        let (legacy, sampled) = if legacy_first {
+            let legacy = db.add_identity("legacy".into(), Some(axis(0))).unwrap().id;
+            clock.advance(1_000);
+            (legacy, enroll(&mut db))
+        } else {
+            let sampled = enroll(&mut db);
+            clock.advance(1_000);
+            (db.add_identity("legacy".into(), Some(axis(0))).unwrap().id, sampled)
+        };
+        (db, legacy, sampled)
+    }
+
+    #[test]
+    fn merge_keeps_legacy_templates() {
+        for legacy_first in [true, false] {
+            let (mut db, legacy, sampled) = legacy_and_sampled(legacy_first);
+            let kept = db.merge(legacy, sampled, &mut EventLog::new(10)).unwrap();
+            assert_eq!(kept, if legacy_first { legacy } else { sampled });
+
+            let identity = db.get(kept).unwrap();
+            assert_eq!(identity.samples.len(), 3);
+            let template = identity.embedding.as_ref().unwrap();
+            assert!(template.iter().take(3).all(|&x| x > 0.5), "{:?}", template);
+            // Every original face still matches the merged identity
+            for i in 0..3 {
+                assert_eq!(db.search(&axis(i), 1), vec![(kept, 1.0)], "axis {}", i);
+            }
+        }
+    }
+
+    #[test]
+    fn template_follows_the_samples() {
+        let model = EmbeddingModel::new("test", "1", 4);
+        let mut db = IdentityDatabase::new();
+        let id = db.enroll("a".into(), vec![FaceSample::new(axis(0), 1.0, 0)], &model).unwrap().id;
+        assert_eq!(db.get(id).unwrap().embedding, Some(axis(0)));
+        assert_eq!(db.search(&axis(1), 1), vec![(id, 0.0)]);
+
+        let added = db.add_sample(id, FaceSample::new(axis(1), 1.0, 0), &model).unwrap();
+        let template = db.get(id).unwrap().embedding.clone().unwrap();
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((template[0] - half).abs() < 1e-6 && (template[1] - half).abs() < 1e-6, "{:?}", template);
+        assert_eq!(db.search(&axis(1), 1), vec![(id, 1.0)]);
+
+        // Lower-quality samples count for less
+        db.add_sample(id, FaceSample::new(axis(2), 0.1, 0), &model).unwrap();
+        let template = db.get(id).unwrap().embedding.clone().unwrap();
+        assert!(template[2] > 0.0 && template[2] < template[0] / 5.0, "{:?}", template);
+
+        let removed = db.remove_sample(id, added).unwrap();
+        assert_eq!(removed.embedding, axis(1));
+        assert_eq!(db.get(id).unwrap().samples.len(), 2);
+        assert_eq!(db.search(&axis(1), 1), vec![(id, 0.0)]);
+        assert!(db.get(id).unwrap().embedding.as_ref().unwrap()[1].abs() < 1e-6);
+
+        assert!(db.add_sample(id, FaceSample::new(vec![1.0; 3], 1.0, 0), &model).is_err());
+        assert!(db.add_sample(99, FaceSample::new(axis(0), 1.0, 0), &model).is_err());
+    }
+
+    #[test]
+    fn last_sample_cant_be_removed() {
+        let model = EmbeddingModel::new("test", "1", 4);
+        let mut db = IdentityDatabase::new();
+        let identity = db.enroll("a".into(), vec![FaceSample::new(axis(0), 1.0, 0)], &model).unwrap();
+        let only = identity.samples[0].id;
+
+        assert!(db.remove_sample(identity.id, only).is_err());
+        assert!(db.remove_sample(identity.id, only + 1).is_err());
+        assert!(db.remove_sample(identity.id + 1, only).is_err());
+        assert_eq!(db.get(identity.id).unwrap().samples.len(), 1);
+        assert_eq!(db.search(&axis(0), 1), vec![(identity.id, 1.0)]);
+
+        // On a bare identity the template goes with the last sample
+        let mut bare = identity;
+        assert!(bare.remove_sample(only).is_some());
+        assert_eq!(bare.embedding, None);
+        assert_eq!(bare.similarity(&axis(0), MatchStrategy::MaxOverSamples), None);
+    }
+
+    #[test]
+    fn wrong_dimension_embeddings_are_rejected() {
+        let model = EmbeddingModel::new("test", "1", 4);
+        let mut db = IdentityDatabase::new();
+        assert!(db.add_identity("empty".into(), Some(Vec::new())).is_err());
+        let a = db.add_identity("a".into(), Some(axis(0))).unwrap().id;
+        assert!(db.add_identity("short".into(), Some(vec![1.0; 3])).is_err());
+        assert!(db.add_identity("none".into(), None).is_ok());
+        assert!(db.enroll("short".into(), vec![FaceSample::new(vec![1.0; 3], 1.0, 0)], &model).is_err());
+        assert!(db.unindexed().is_empty());
+        assert_eq!(db.search(&axis(0), 1), vec![(a, 1.0)]);
+    }
+
+    #[test]
+    fn mixed_dimensions_on_load_are_reported() {
+        let mut db = IdentityDatabase::new();
+        let ids: Vec<_> = (0..3).map(|i| db.add_identity(format!("{}", i), Some(axis(i))).unwrap().id).collect();
+        let mut stored = serde_json::to_value(&db).unwrap();
+        stored["identities"][0]["embedding"] = serde_json::json!([1.0, 0.0]);
+
+        let loaded: IdentityDatabase = serde_json::from_value(stored).unwrap();
+        assert_eq!(loaded.unindexed(), &[ids[0]]);
+        assert_eq!(loaded.search(&axis(1), 1), vec![(ids[1], 1.0)]);
+        assert_eq!(loaded.search(&axis(2), 1), vec![(ids[2], 1.0)]);
+
+        let mut loaded = loaded;
+        loaded.remove_identity(ids[0]);
+        assert!(loaded.unindexed().is_empty());
+    }
+
+    #[test]
+    fn merge_keeps_a_blacklisting() {
+        let clock = Arc::new(ManualClock::new(1_000));
+        let mut db = IdentityDatabase::new().with_clock(clock.clone());
+        let resident = db.add_identity("resident".into(), Some(axis(0))).unwrap().id;
+        clock.advance(1_000);
+        let banned = db.add_identity("banned".into(), Some(axis(1))).unwrap().id;
+        let metadata = IdentityMetadata {
+            valid_until: Some(10_000),
+            ..IdentityMetadata::default().with_role(IdentityRole::Blacklisted)
+        };
+        db.set_metadata(banned, metadata).unwrap();
+
+        let mut events = EventLog::new(10);
+        events.add_event(EventType::Blacklisted, "banned".into(), 0.9, None, Some(banned));
+        let kept = db.merge(banned, resident, &mut events).unwrap();
+        assert_eq!(kept, resident);
+
+        let identity = db.get(kept).unwrap();
+        assert_eq!(identity.name, "resident");
+        assert_eq!(identity.metadata.role, IdentityRole::Blacklisted);
+        assert_eq!(identity.metadata.valid_until, Some(10_000));
+        assert_eq!(identity.event_type_at(5_000), EventType::Blacklisted);
+        assert_eq!(events.for_identity(kept).len(), 1);
+    }
+
+    #[test]
+    fn merge_two_legacy_identities() {
+        let mut db = IdentityDatabase::new().with_clock(Arc::new(ManualClock::new(0)));
+        let a = db.add_identity("a".into(), Some(axis(0))).unwrap().id;
+        let b = db.add_identity("b".into(), Some(axis(1))).unwrap().id;
+        let kept = db.merge(a, b, &mut EventLog::new(10)).unwrap();
+        assert_eq!(kept, a);
+        assert_eq!(db.get(kept).unwrap().samples.len(), 2);
+        assert_eq!(db.search(&axis(1), 1)[0].0, kept);
+    }
+}
@@ -0,0 +1,93 @@
+use super::embedding::{l2_normalize, EmbeddingModel, FaceEmbedder};
+use crate::alignment::ALIGNED_SIZE;
+use crate::frame::{Frame, PixelFormat};
+use crate::onnx::{OnnxModel, TensorLayout};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OnnxEmbedderConfig {
+    /// `(v - mean) / std` applied to 0-255 inputs; 127.5/127.5 suits the
+    /// InsightFace ArcFace and MobileFaceNet exports
+    pub mean: f32,
+    pub std: f32,
+    pub layout: TensorLayout,
+}
+
+impl Default for OnnxEmbedderConfig {
+    fn default() -> Self {
+        Self {
+            mean: 127.5,
+            std: 127.5,
+            layout: TensorLayout::Nchw,
+        }
+    }
+}
+
+/// Face embedder running an ArcFace-style ONNX model (MobileFaceNet,
+/// ResNet ArcFace, ...) with a 112x112 RGB input on the CPU
+pub struct OnnxFaceEmbedder {
+    model: OnnxModel,
+    info: EmbeddingModel,
+    config: OnnxEmbedderConfig,
+}
+
+impl OnnxFaceEmbedder {
+    pub fn from_path(
+        path: impl AsRef<Path>,
+        name: &str,
+        version: &str,
+        config: OnnxEmbedderConfig,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        Self::from_bytes(&bytes, name, version, config)
+    }
+
+    /// Load the model and probe it once to learn its embedding dimension
+    pub fn from_bytes(bytes: &[u8], name: &str, version: &str, config: OnnxEmbedderConfig) -> Result<Self> {
+        let model = OnnxModel::load(bytes, ALIGNED_SIZE, ALIGNED_SIZE, config.layout)?;
+        let blank = Frame::new(
+            ALIGNED_SIZE,
+            ALIGNED_SIZE,
+            PixelFormat::Rgb8,
+            vec![0; (ALIGNED_SIZE * ALIGNED_SIZE * 3) as usize],
+            0,
+        )?;
+        let dim = first_output(model.run(&blank, config.mean, config.std)?)?.len();
+
+        Ok(Self {
+            model,
+            info: EmbeddingModel::new(name, version, dim),
+            config,
+        })
+    }
+}
+
+impl FaceEmbedder for OnnxFaceEmbedder {
+    fn model(&self) -> &EmbeddingModel {
+        &self.info
+    }
+
+    fn embed(&mut self, face: &Frame) -> Result<Vec<f32>> {
+        let mut input = face.convert(PixelFormat::Rgb8);
+        if input.width() != ALIGNED_SIZE || input.height() != ALIGNED_SIZE {
+            input = input.resize(ALIGNED_SIZE, ALIGNED_SIZE)?;
+        }
+
+        let mut embedding = first_output(self.model.run(&input, self.config.mean, self.config.std)?)?;
+        if embedding.len() != self.info.dim {
+            bail!("expected a {}-dim embedding, got {}", self.info.dim, embedding.len());
+        }
+        l2_normalize(&mut embedding);
+        Ok(embedding)
+    }
+}
+
+fn first_output(outputs: Vec<(Vec<usize>, Vec<f32>)>) -> Result<Vec<f32>> {
+    match outputs.into_iter().next() {
+        Some((_, values)) if !values.is_empty() => Ok(values),
+        _ => bail!("embedding model produced no output"),
+    }
+}
@@ -0,0 +1,55 @@
+use super::embedding::l2_normalize;
+use serde::{Deserialize, Serialize};
+
+/// One enrolment capture of an identity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaceSample {
+    pub id: u32,
+    pub embedding: Vec<f32>,
+    pub captured_at: u64,
+    pub quality: f32, // 0.0 - 1.0
+    pub thumbnail: Option<String>, // data URL of the aligned crop
+}
+
+impl FaceSample {
+    /// A sample with no id yet; the owning identity assigns one
+    pub fn new(embedding: Vec<f32>, quality: f32, captured_at: u64) -> Self {
+        Self {
+            id: 0,
+            embedding,
+            captured_at,
+            quality: quality.clamp(0.0, 1.0),
+            thumbnail: None,
+        }
+    }
+
+    pub fn with_thumbnail(mut self, thumbnail: String) -> Self {
+        self.thumbnail = Some(thumbnail);
+        self
+    }
+}
+
+/// How a query embedding is scored against an identity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MatchStrategy {
+    /// Best similarity over the individual samples
+    #[default]
+    MaxOverSamples,
+    /// Similarity to the mean of the samples
+    MeanTemplate,
+}
+
+/// Quality-weighted mean of the sample embeddings, L2-normalised
+pub(crate) fn mean_template(samples: &[FaceSample]) -> Option<Vec<f32>> {
+    let dim = samples.first()?.embedding.len();
+    let mut template = vec![0.0; dim];
+    for sample in samples.iter().filter(|s| s.embedding.len() == dim) {
+        // Keep zero-quality samples from vanishing entirely
+        let weight = sample.quality.max(0.05);
+        for (t, x) in template.iter_mut().zip(&sample.embedding) {
+            *t += weight * x;
+        }
+    }
+    l2_normalize(&mut template);
+    Some(template)
+}
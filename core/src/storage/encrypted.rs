@@ -0,0 +1,579 @@
+use super::Store;
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD};
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Plaintext record with the key derivation parameters and the data keys,
+/// each wrapped with the passphrase key
+pub const KEYRING_KEY: &str = "faceguard_keyring";
+
+const SEALED_PREFIX: &str = "fgenc1:";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+const SALT_LEN: usize = 16;
+
+type Cipher = XChaCha20Poly1305;
+
+/// Argon2id parameters, kept with the keyring so they can be raised later
+/// without breaking existing stores
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KdfParams {
+    salt: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+impl KdfParams {
+    fn generate() -> Result<Self> {
+        Ok(Self {
+            salt: BASE64.encode(random_bytes::<SALT_LEN>()?),
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        })
+    }
+
+    fn derive(&self, passphrase: &str) -> Result<Cipher> {
+        if passphrase.is_empty() {
+            bail!("passphrase is empty");
+        }
+        let salt = BASE64.decode(&self.salt).context("reading keyring salt")?;
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(KEY_LEN))
+            .map_err(|e| anyhow!("invalid key derivation parameters: {}", e))?;
+        let mut key = [0u8; KEY_LEN];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| anyhow!("deriving key: {}", e))?;
+        let cipher = Cipher::new(&key.into());
+        key.fill(0);
+        Ok(cipher)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WrappedKey {
+    id: String,
+    key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Keyring {
+    kdf: KdfParams,
+    /// Data key new records are sealed with
+    current: String,
+    /// More than one only while a rotation is under way
+    keys: Vec<WrappedKey>,
+    /// Set while `enable` seals the existing records; until then the ones
+    /// it hasn't reached yet are read as plaintext
+    #[serde(default)]
+    sealing: bool,
+}
+
+/// Keys held in memory while the store is unlocked
+struct Unlocked {
+    kek: Cipher,
+    keyring: Keyring,
+    keys: HashMap<String, Cipher>,
+}
+
+impl Unlocked {
+    fn open(keyring: Keyring, passphrase: &str) -> Result<Self> {
+        let kek = keyring.kdf.derive(passphrase)?;
+        let mut keys = HashMap::new();
+        for wrapped in &keyring.keys {
+            let mut bytes = unseal(&kek, &wrap_aad(&wrapped.id), &wrapped.key).context("wrong passphrase")?;
+            keys.insert(wrapped.id.clone(), cipher_from(&bytes)?);
+            bytes.fill(0);
+        }
+        if !keys.contains_key(&keyring.current) {
+            bail!("keyring has no key {}", keyring.current);
+        }
+        Ok(Self { kek, keyring, keys })
+    }
+
+    /// Start a keyring with one fresh data key
+    fn create(passphrase: &str) -> Result<Self> {
+        let kdf = KdfParams::generate()?;
+        let kek = kdf.derive(passphrase)?;
+        let mut unlocked = Self {
+            kek,
+            keyring: Keyring {
+                kdf,
+                current: String::new(),
+                keys: Vec::new(),
+                sealing: true,
+            },
+            keys: HashMap::new(),
+        };
+        unlocked.add_key()?;
+        Ok(unlocked)
+    }
+
+    /// Generate a data key, wrap it and make it current
+    fn add_key(&mut self) -> Result<()> {
+        let id = URL_SAFE_NO_PAD.encode(random_bytes::<6>()?);
+        let mut bytes = random_bytes::<KEY_LEN>()?;
+        let wrapped = seal(&self.kek, &wrap_aad(&id), &bytes)?;
+        self.keys.insert(id.clone(), cipher_from(&bytes)?);
+        bytes.fill(0);
+        self.keyring.keys.push(WrappedKey { id: id.clone(), key: wrapped });
+        self.keyring.current = id;
+        Ok(())
+    }
+
+    fn retain_current(&mut self) {
+        let current = self.keyring.current.clone();
+        self.keyring.keys.retain(|k| k.id == current);
+        self.keys.retain(|id, _| *id == current);
+    }
+
+    fn seal_record(&self, key: &str, value: &str) -> Result<String> {
+        let id = &self.keyring.current;
+        let body = seal(&self.keys[id], key, value.as_bytes())?;
+        Ok(format!("{}{}:{}", SEALED_PREFIX, id, body))
+    }
+
+    fn open_record(&self, key: &str, stored: &str) -> Result<String> {
+        let Some((id, body)) = key_id(stored) else {
+            if self.keyring.sealing {
+                return Ok(stored.to_string());
+            }
+            bail!("{} is stored unencrypted", key);
+        };
+        let cipher = self
+            .keys
+            .get(id)
+            .ok_or_else(|| anyhow!("{} was encrypted with an unknown key {}", key, id))?;
+        let plaintext = unseal(cipher, key, body).with_context(|| format!("decrypting {}", key))?;
+        String::from_utf8(plaintext).with_context(|| format!("decrypting {}", key))
+    }
+}
+
+/// Wraps another store and seals every value with XChaCha20-Poly1305 under
+/// a data key unlocked by an admin passphrase (Argon2id). The record key is
+/// bound in as associated data, so sealed values can't be swapped around.
+///
+/// Until [`EncryptedStore::enable`] is called values pass through as-is.
+/// Once encrypted, reads and writes fail while locked; deletes don't need
+/// the key. Writes are always sealed, but while sealing is unfinished (see
+/// [`EncryptedStore::is_sealing`]) reads also accept plaintext, so values
+/// put straight into the inner store in that window aren't caught.
+pub struct EncryptedStore<S> {
+    inner: S,
+    unlocked: Option<Unlocked>,
+}
+
+impl<S: Store> EncryptedStore<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner, unlocked: None }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn is_encrypted(&self) -> Result<bool> {
+        Ok(self.keyring()?.is_some())
+    }
+
+    /// Encrypted and no key loaded
+    pub fn is_locked(&self) -> bool {
+        self.unlocked.is_none() && self.is_encrypted().unwrap_or(true)
+    }
+
+    /// Forget the keys; they're needed again to read or write anything
+    pub fn lock(&mut self) {
+        self.unlocked = None;
+    }
+
+    pub fn unlock(&mut self, passphrase: &str) -> Result<()> {
+        let keyring = self.keyring()?.ok_or_else(|| anyhow!("{} is not encrypted", self.inner.name()))?;
+        self.unlocked = Some(Unlocked::open(keyring, passphrase)?);
+        Ok(())
+    }
+
+    /// An earlier `enable` stopped before sealing every record; run it
+    /// again to finish
+    pub fn is_sealing(&self) -> bool {
+        self.unlocked.as_ref().is_some_and(|u| u.keyring.sealing)
+    }
+
+    /// Encrypt the store under `passphrase`, sealing everything already in
+    /// it. Running it again while unlocked finishes an interrupted run, but
+    /// only with the same passphrase; use [`Self::change_passphrase`] to
+    /// change it.
+    pub fn enable(&mut self, passphrase: &str) -> Result<usize> {
+        match self.keyring()? {
+            Some(keyring) => {
+                if self.unlocked.is_none() {
+                    bail!("already encrypted; unlock it first");
+                }
+                Unlocked::open(keyring, passphrase).context("already encrypted under a different passphrase")?;
+            }
+            None => {
+                let unlocked = Unlocked::create(passphrase)?;
+                self.write_keyring(&unlocked.keyring)?;
+                self.unlocked = Some(unlocked);
+            }
+        }
+        self.finish_sealing()
+    }
+
+    /// Seal the records an interrupted [`Self::enable`] didn't reach,
+    /// returning how many there were
+    pub fn finish_sealing(&mut self) -> Result<usize> {
+        if self.unlocked.is_none() {
+            bail!("store is locked");
+        }
+        let mut sealed = 0;
+        for key in self.keys()? {
+            let Some(value) = self.inner.get(&key)? else {
+                continue;
+            };
+            if key_id(&value).is_none() {
+                self.put(&key, &value)?;
+                sealed += 1;
+            }
+        }
+
+        let unlocked = self.unlocked.as_mut().expect("checked above");
+        if unlocked.keyring.sealing {
+            unlocked.keyring.sealing = false;
+            let keyring = unlocked.keyring.clone();
+            self.write_keyring(&keyring)?;
+        }
+        Ok(sealed)
+    }
+
+    /// Re-wrap the data keys under a new passphrase and salt. Records are
+    /// untouched, so this is a single write.
+    pub fn change_passphrase(&mut self, passphrase: &str) -> Result<()> {
+        let unlocked = self.unlocked.as_ref().ok_or_else(|| anyhow!("store is locked"))?;
+        let kdf = KdfParams::generate()?;
+        let kek = kdf.derive(passphrase)?;
+        let mut keys = Vec::new();
+        for wrapped in &unlocked.keyring.keys {
+            let mut bytes = unseal(&unlocked.kek, &wrap_aad(&wrapped.id), &wrapped.key)?;
+            keys.push(WrappedKey {
+                id: wrapped.id.clone(),
+                key: seal(&kek, &wrap_aad(&wrapped.id), &bytes)?,
+            });
+            bytes.fill(0);
+        }
+        let keyring = Keyring {
+            kdf,
+            current: unlocked.keyring.current.clone(),
+            keys,
+            sealing: unlocked.keyring.sealing,
+        };
+        self.write_keyring(&keyring)?;
+
+        let unlocked = self.unlocked.as_mut().expect("checked above");
+        unlocked.kek = kek;
+        unlocked.keyring = keyring;
+        Ok(())
+    }
+
+    /// Switch to a new data key and re-encrypt every record with it,
+    /// returning how many were rewritten. The old key stays in the keyring
+    /// until all records are moved, so an interrupted rotation can simply
+    /// be run again.
+    pub fn rotate_key(&mut self) -> Result<usize> {
+        let unlocked = self.unlocked.as_mut().ok_or_else(|| anyhow!("store is locked"))?;
+        unlocked.add_key()?;
+        let keyring = unlocked.keyring.clone();
+        self.write_keyring(&keyring)?;
+
+        let mut rewritten = 0;
+        for key in self.keys()? {
+            let Some(stored) = self.inner.get(&key)? else {
+                continue;
+            };
+            if key_id(&stored).is_some_and(|(id, _)| id != keyring.current) {
+                let value = self
+                    .get(&key)?
+                    .ok_or_else(|| anyhow!("{} was deleted during key rotation", key))?;
+                self.put(&key, &value)?;
+                rewritten += 1;
+            }
+        }
+
+        let unlocked = self.unlocked.as_mut().expect("checked above");
+        unlocked.retain_current();
+        let keyring = unlocked.keyring.clone();
+        self.write_keyring(&keyring)?;
+        Ok(rewritten)
+    }
+
+    fn keyring(&self) -> Result<Option<Keyring>> {
+        self.inner
+            .get(KEYRING_KEY)?
+            .map(|json| serde_json::from_str(&json).context("reading keyring"))
+            .transpose()
+    }
+
+    fn write_keyring(&mut self, keyring: &Keyring) -> Result<()> {
+        self.inner.put(KEYRING_KEY, &serde_json::to_string(keyring)?)
+    }
+
+    fn unlocked(&self) -> Result<Option<&Unlocked>> {
+        match &self.unlocked {
+            Some(unlocked) => Ok(Some(unlocked)),
+            None if self.is_encrypted()? => bail!("{} is locked", self.inner.name()),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<S: Store> Store for EncryptedStore<S> {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        let Some(stored) = self.inner.get(key)? else {
+            return Ok(None);
+        };
+        match self.unlocked()? {
+            Some(unlocked) => unlocked.open_record(key, &stored).map(Some),
+            None => Ok(Some(stored)),
+        }
+    }
+
+    fn put(&mut self, key: &str, value: &str) -> Result<()> {
+        if key == KEYRING_KEY {
+            bail!("{} is reserved", KEYRING_KEY);
+        }
+        let sealed = match self.unlocked()? {
+            Some(unlocked) => unlocked.seal_record(key, value)?,
+            None => value.to_string(),
+        };
+        self.inner.put(key, &sealed)
+    }
+
+    fn delete(&mut self, key: &str) -> Result<()> {
+        self.inner.delete(key)
+    }
+
+    fn keys(&self) -> Result<Vec<String>> {
+        Ok(self.inner.keys()?.into_iter().filter(|key| key != KEYRING_KEY).collect())
+    }
+}
+
+fn wrap_aad(id: &str) -> String {
+    format!("{}/{}", KEYRING_KEY, id)
+}
+
+/// Split a sealed value into its key id and body
+fn key_id(stored: &str) -> Option<(&str, &str)> {
+    stored.strip_prefix(SEALED_PREFIX)?.split_once(':')
+}
+
+fn cipher_from(bytes: &[u8]) -> Result<Cipher> {
+    Cipher::new_from_slice(bytes).map_err(|_| anyhow!("data key has the wrong length"))
+}
+
+/// Base64 of a random nonce followed by the ciphertext
+fn seal(cipher: &Cipher, aad: &str, plaintext: &[u8]) -> Result<String> {
+    let nonce = random_bytes::<NONCE_LEN>()?;
+    let payload = Payload {
+        msg: plaintext,
+        aad: aad.as_bytes(),
+    };
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), payload)
+        .map_err(|_| anyhow!("encryption failed"))?;
+    let mut out = nonce.to_vec();
+    out.extend_from_slice(&ciphertext);
+    Ok(BASE64.encode(out))
+}
+
+fn unseal(cipher: &Cipher, aad: &str, sealed: &str) -> Result<Vec<u8>> {
+    let bytes = BASE64.decode(sealed).context("sealed value is not base64")?;
+    if bytes.len() < NONCE_LEN {
+        bail!("sealed value is truncated");
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let payload = Payload {
+        msg: ciphertext,
+        aad: aad.as_bytes(),
+    };
+    cipher
+        .decrypt(XNonce::from_slice(nonce), payload)
+        .map_err(|_| anyhow!("authentication failed (wrong key or tampered data)"))
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes).map_err(|e| anyhow!("no secure random source: {}", e))?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    /// In-memory store whose writes can be made to fail after a while
+    #[derive(Default)]
+    struct MemoryStore {
+        records: BTreeMap<String, String>,
+        puts_left: Option<usize>,
+    }
+
+    impl Store for MemoryStore {
+        fn name(&self) -> &'static str {
+            "memory"
+        }
+
+        fn get(&self, key: &str) -> Result<Option<String>> {
+            Ok(self.records.get(key).cloned())
+        }
+
+        fn put(&mut self, key: &str, value: &str) -> Result<()> {
+            if let Some(left) = self.puts_left.as_mut() {
+                if *left == 0 {
+                    bail!("quota exceeded");
+                }
+                *left -= 1;
+            }
+            self.records.insert(key.to_string(), value.to_string());
+            Ok(())
+        }
+
+        fn delete(&mut self, key: &str) -> Result<()> {
+            self.records.remove(key);
+            Ok(())
+        }
+
+        fn keys(&self) -> Result<Vec<String>> {
+            Ok(self.records.keys().cloned().collect())
+        }
+    }
+
+    fn store_with(records: &[(&str, &str)]) -> EncryptedStore<MemoryStore> {
+        let mut store = EncryptedStore::new(MemoryStore::default());
+        for (key, value) in records {
+            store.put(key, value).unwrap();
+        }
+        store
+    }
+
+    const RECORDS: [(&str, &str); 3] = [("a", "alpha"), ("b", "beta"), ("c", "gamma")];
+
+    fn assert_readable(store: &EncryptedStore<MemoryStore>) {
+        for (key, value) in RECORDS {
+            assert_eq!(store.get(key).unwrap().as_deref(), Some(value));
+        }
+    }
+
+    #[test]
+    fn round_trip_and_wrong_passphrase() {
+        let mut store = store_with(&RECORDS);
+        assert_eq!(store.enable("secret").unwrap(), 3);
+        assert!(store.inner().records.values().all(|v| !v.contains("alpha") && !v.contains("gamma")));
+        assert_readable(&store);
+
+        store.lock();
+        assert!(store.is_locked());
+        assert!(store.get("a").is_err());
+        assert!(store.put("d", "delta").is_err());
+        assert!(store.unlock("wrong").is_err());
+        assert!(store.is_locked());
+        store.unlock("secret").unwrap();
+        assert_readable(&store);
+
+        // Sealed values are bound to their key
+        let sealed = store.inner.records["a"].clone();
+        store.inner.records.insert("b".into(), sealed);
+        assert!(store.get("b").is_err());
+        // and plaintext slipped in afterwards is refused
+        store.inner.records.insert("b".into(), "beta".into());
+        assert!(store.get("b").is_err());
+    }
+
+    #[test]
+    fn change_passphrase_and_rotate_key() {
+        let mut store = store_with(&RECORDS);
+        store.enable("old").unwrap();
+        let before = store.inner().records["a"].clone();
+        store.change_passphrase("new").unwrap();
+        // Re-wrapping leaves the records alone
+        assert_eq!(store.inner().records["a"], before);
+        store.lock();
+        assert!(store.unlock("old").is_err());
+        store.unlock("new").unwrap();
+        assert_readable(&store);
+
+        assert_eq!(store.rotate_key().unwrap(), 3);
+        let keyring = store.keyring().unwrap().unwrap();
+        assert_eq!(keyring.keys.len(), 1);
+        assert!(store.inner().records.values().all(|v| key_id(v).is_none_or(|(id, _)| id == keyring.current)));
+        store.lock();
+        store.unlock("new").unwrap();
+        assert_readable(&store);
+    }
+
+    #[test]
+    fn interrupted_enable_resumes() {
+        let mut store = store_with(&RECORDS);
+        // Keyring plus one record, then the backend runs out of space
+        store.inner.puts_left = Some(2);
+        assert!(store.enable("secret").is_err());
+        assert!(store.is_sealing());
+        assert_readable(&store);
+
+        // Still readable after a reload, and the next run finishes
+        store.inner.puts_left = None;
+        store.lock();
+        store.unlock("secret").unwrap();
+        assert_readable(&store);
+        assert_eq!(store.enable("secret").unwrap(), 2);
+        assert!(!store.is_sealing());
+        store.lock();
+        store.unlock("secret").unwrap();
+        assert!(!store.is_sealing());
+        assert_readable(&store);
+    }
+
+    #[test]
+    fn enable_checks_the_existing_passphrase() {
+        let mut store = store_with(&RECORDS);
+        store.enable("secret").unwrap();
+        let keyring = store.inner().records[KEYRING_KEY].clone();
+
+        assert!(store.enable("other").is_err());
+        assert_eq!(store.inner().records[KEYRING_KEY], keyring);
+        assert_eq!(store.enable("secret").unwrap(), 0);
+
+        store.lock();
+        assert!(store.enable("secret").is_err());
+        assert!(store.finish_sealing().is_err());
+        assert!(store.unlock("other").is_err());
+        store.unlock("secret").unwrap();
+        assert_readable(&store);
+    }
+
+    #[test]
+    fn plaintext_is_only_read_while_sealing() {
+        let mut store = store_with(&RECORDS);
+        store.inner.puts_left = Some(2);
+        assert!(store.enable("secret").is_err());
+        store.inner.puts_left = None;
+
+        // Writes during the window are sealed regardless
+        store.put("d", "delta").unwrap();
+        assert!(key_id(&store.inner().records["d"]).is_some());
+        assert_eq!(store.finish_sealing().unwrap(), 2);
+        assert!(store.inner().records.iter().all(|(k, v)| k == KEYRING_KEY || key_id(v).is_some()));
+
+        store.inner.records.insert("e".into(), "epsilon".into());
+        assert!(store.get("e").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Version written by this build. Bump it together with a new step in
+/// [`migrate_step`].
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Tag identifying the writer, kept in every envelope
+pub const CREATED_BY: &str = concat!("faceguard ", env!("CARGO_PKG_VERSION"));
+
+/// What a stored blob contains; migrations differ per collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collection {
+    Identities,
+    Events,
+}
+
+/// Wrapper around every persisted payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope<T> {
+    pub schema_version: u32,
+    pub created_by: String,
+    /// Embedding model the payload's vectors came from, if any
+    #[serde(default)]
+    pub model_id: Option<String>,
+    pub payload: T,
+}
+
+/// A decoded payload and where it came from
+#[derive(Debug, Clone)]
+pub struct Loaded<T> {
+    pub payload: T,
+    pub created_by: String,
+    pub model_id: Option<String>,
+    /// Version the blob was migrated from, `None` if it was current
+    pub migrated_from: Option<u32>,
+}
+
+pub fn encode<T: Serialize>(payload: &T, model_id: Option<String>) -> Result<String> {
+    let envelope = Envelope {
+        schema_version: SCHEMA_VERSION,
+        created_by: CREATED_BY.to_string(),
+        model_id,
+        payload,
+    };
+    serde_json::to_string(&envelope).context("encoding payload")
+}
+
+/// Parse a stored blob, migrating it to [`SCHEMA_VERSION`] first if needed.
+/// Blobs without an envelope are treated as version 0.
+pub fn decode<T: DeserializeOwned>(collection: Collection, json: &str) -> Result<Loaded<T>> {
+    let value: Value = serde_json::from_str(json).context("stored data is not valid JSON")?;
+    let envelope = match value {
+        Value::Object(ref map) if map.contains_key("schema_version") && map.contains_key("payload") => {
+            serde_json::from_value::<Envelope<Value>>(value).context("malformed storage envelope")?
+        }
+        legacy => Envelope {
+            schema_version: 0,
+            created_by: String::from("unknown (pre-envelope)"),
+            model_id: None,
+            payload: legacy,
+        },
+    };
+
+    let from = envelope.schema_version;
+    if from > SCHEMA_VERSION {
+        bail!(
+            "{:?} data has schema version {} (written by {}), newer than this build's {}",
+            collection,
+            from,
+            envelope.created_by,
+            SCHEMA_VERSION
+        );
+    }
+
+    let mut payload = envelope.payload;
+    for version in from..SCHEMA_VERSION {
+        payload = migrate_step(collection, version, payload)
+            .with_context(|| format!("migrating {:?} data from version {}", collection, version))?;
+    }
+
+    let payload = serde_json::from_value(payload)
+        .map_err(|e| anyhow!("{:?} data doesn't match schema version {}: {}", collection, SCHEMA_VERSION, e))?;
+    Ok(Loaded {
+        payload,
+        created_by: envelope.created_by,
+        model_id: envelope.model_id,
+        migrated_from: (from < SCHEMA_VERSION).then_some(from),
+    })
+}
+
+/// Rewrite a payload from `version` to `version + 1`
+fn migrate_step(collection: Collection, version: u32, payload: Value) -> Result<Value> {
+    match (collection, version) {
+        // Version 0 is the bare JSON the UI stored before envelopes. Fields
+        // added since (samples, metadata, identity_id, ...) all deserialize
+        // with defaults, so the payload carries over unchanged.
+        (_, 0) => Ok(payload),
+        _ => bail!("no migration from version {}", version),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{EventLog, EventType};
+    use crate::recognition::IdentityDatabase;
+
+    /// Identity database as the UI stored it before envelopes existed
+    const BASELINE_IDENTITIES: &str = r#"{"identities":[{"id":1,"name":"Alice","confidence":0.0,"embedding":[0.6,0.8],"created_at":1000,"last_seen":2000}],"next_id":2}"#;
+
+    const BASELINE_EVENTS: &str = r#"{"events":[{"id":0,"event_type":"FaceRecognized","name":"Alice","confidence":0.9,"timestamp":1500,"track_id":3}],"next_id":1,"max_events":1000}"#;
+
+    #[test]
+    fn baseline_identities_are_migrated() {
+        let loaded: Loaded<IdentityDatabase> = decode(Collection::Identities, BASELINE_IDENTITIES).unwrap();
+        assert_eq!(loaded.migrated_from, Some(0));
+        assert_eq!(loaded.model_id, None);
+
+        let alice = loaded.payload.get(1).unwrap();
+        assert_eq!((alice.name.as_str(), alice.created_at, alice.last_seen), ("Alice", 1000, 2000));
+        assert_eq!(alice.embedding.as_deref(), Some(&[0.6, 0.8][..]));
+        assert!(alice.samples.is_empty());
+        assert!(loaded.payload.unindexed().is_empty());
+        assert_eq!(loaded.payload.search(&[0.6, 0.8], 1).first().map(|m| m.0), Some(1));
+    }
+
+    #[test]
+    fn baseline_events_are_migrated() {
+        let loaded: Loaded<EventLog> = decode(Collection::Events, BASELINE_EVENTS).unwrap();
+        assert_eq!(loaded.migrated_from, Some(0));
+        let events = loaded.payload.get_all();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].event_type, events[0].track_id, events[0].identity_id), (EventType::FaceRecognized, Some(3), None));
+        assert_eq!(events[0].snapshot, None);
+    }
+
+    #[test]
+    fn current_envelopes_round_trip() {
+        let mut events = EventLog::new(10);
+        events.add_event_at(EventType::UnknownFace, "Unknown".into(), 0.2, Some(4), None, 42);
+        let json = encode(&events, Some("model-a".into())).unwrap();
+
+        let loaded: Loaded<EventLog> = decode(Collection::Events, &json).unwrap();
+        assert_eq!(loaded.migrated_from, None);
+        assert_eq!((loaded.created_by.as_str(), loaded.model_id.as_deref()), (CREATED_BY, Some("model-a")));
+        assert_eq!(serde_json::to_string(&loaded.payload).unwrap(), serde_json::to_string(&events).unwrap());
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let json = format!(
+            r#"{{"schema_version":{},"created_by":"faceguard 9.0.0","payload":{}}}"#,
+            SCHEMA_VERSION + 1,
+            BASELINE_EVENTS
+        );
+        let err = decode::<EventLog>(Collection::Events, &json).unwrap_err();
+        assert!(err.to_string().contains("newer than this build"), "{}", err);
+    }
+
+    #[test]
+    fn garbage_is_an_error() {
+        for json in ["", "not json", "{\"identities\":", "[1, 2, 3]", "{\"schema_version\":\"one\",\"payload\":{}}"] {
+            assert!(decode::<IdentityDatabase>(Collection::Identities, json).is_err(), "{}", json);
+        }
+        // Valid JSON of the wrong shape fails after migration
+        assert!(decode::<IdentityDatabase>(Collection::Identities, BASELINE_EVENTS).is_err());
+    }
+}
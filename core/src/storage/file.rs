@@ -0,0 +1,163 @@
+use super::Store;
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// One file per key in a directory, for native and Tauri builds. Writes go
+/// through a temporary file and a rename so a crash can't truncate a record.
+pub struct FileStore {
+    root: PathBuf,
+}
+
+impl FileStore {
+    pub fn open(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(&root).with_context(|| format!("creating {}", root.display()))?;
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.root.join(encode_key(key))
+    }
+}
+
+impl Store for FileStore {
+    fn name(&self) -> &'static str {
+        "files"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        let path = self.path(key);
+        match fs::read_to_string(&path) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    fn put(&mut self, key: &str, value: &str) -> Result<()> {
+        let path = self.path(key);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, value).with_context(|| format!("writing {}", tmp.display()))?;
+        fs::rename(&tmp, &path).with_context(|| format!("replacing {}", path.display()))
+    }
+
+    fn delete(&mut self, key: &str) -> Result<()> {
+        let path = self.path(key);
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("removing {}", path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn keys(&self) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        for entry in fs::read_dir(&self.root).with_context(|| format!("listing {}", self.root.display()))? {
+            let name = entry?.file_name();
+            if let Some(key) = name.to_str().and_then(decode_key) {
+                keys.push(key);
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+/// Percent-encode anything but `[A-Za-z0-9_-]` so keys map to flat,
+/// portable file names; `.rec` keeps them apart from temporary files
+fn encode_key(key: &str) -> String {
+    let mut name = String::with_capacity(key.len() + 4);
+    for byte in key.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-' {
+            name.push(byte as char);
+        } else {
+            name.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    name.push_str(".rec");
+    name
+}
+
+fn decode_key(name: &str) -> Option<String> {
+    let encoded = name.strip_suffix(".rec")?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        if encoded[i] == b'%' {
+            let hex = std::str::from_utf8(encoded.get(i + 1..i + 3)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            bytes.push(encoded[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(name: &str) -> FileStore {
+        let root = std::env::temp_dir().join(format!("faceguard-files-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&root);
+        FileStore::open(root).unwrap()
+    }
+
+    #[test]
+    fn keys_survive_encoding() {
+        for key in ["faceguard_identities", "faceguard_snapshot/0000000000042-7", "a.unreadable.5", "spaces and %25", "ünïcode"] {
+            let name = encode_key(key);
+            assert!(name.bytes().all(|b| b.is_ascii_alphanumeric() || b"_-%.".contains(&b)), "{}", name);
+            assert_eq!(name.matches('.').count(), 1, "{}", name);
+            assert_eq!(decode_key(&name).as_deref(), Some(key));
+        }
+        for name in ["plain", "x.tmp", "bad%zz.rec", "cut%4.rec", "%FF.rec"] {
+            assert_eq!(decode_key(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn round_trips_lists_and_deletes() {
+        let mut store = temp_store("roundtrip");
+        assert_eq!(store.get("missing").unwrap(), None);
+        store.put("b/two", "2").unwrap();
+        store.put("a.one", "1").unwrap();
+        store.put("b/two", "22").unwrap();
+        assert_eq!(store.get("b/two").unwrap().as_deref(), Some("22"));
+        assert_eq!(store.keys().unwrap(), ["a.one", "b/two"]);
+
+        // Reopening reads the same records, and stray files aren't keys
+        fs::write(store.root().join("leftover.tmp"), "x").unwrap();
+        let mut store = FileStore::open(store.root()).unwrap();
+        assert_eq!(store.keys().unwrap(), ["a.one", "b/two"]);
+        assert_eq!(store.get("a.one").unwrap().as_deref(), Some("1"));
+
+        store.delete("a.one").unwrap();
+        store.delete("a.one").unwrap();
+        assert_eq!(store.get("a.one").unwrap(), None);
+        assert_eq!(store.keys().unwrap(), ["b/two"]);
+    }
+
+    #[test]
+    fn writes_replace_through_a_temporary_file() {
+        let mut store = temp_store("atomic");
+        store.put("key", "old").unwrap();
+        // A temporary file left by an interrupted write doesn't touch the record
+        fs::write(store.path("key").with_extension("tmp"), "half").unwrap();
+        assert_eq!(store.get("key").unwrap().as_deref(), Some("old"));
+
+        store.put("key", "new").unwrap();
+        assert_eq!(store.get("key").unwrap().as_deref(), Some("new"));
+        assert!(!store.path("key").with_extension("tmp").exists());
+        assert_eq!(fs::read_dir(store.root()).unwrap().count(), 1);
+    }
+}
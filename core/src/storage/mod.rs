@@ -0,0 +1,267 @@
+use crate::events::EventLog;
+use crate::recognition::IdentityDatabase;
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+mod encrypted;
+mod envelope;
+#[cfg(not(target_arch = "wasm32"))]
+mod file;
+#[cfg(all(not(target_arch = "wasm32"), feature = "sqlite"))]
+mod sqlite;
+
+pub use encrypted::{EncryptedStore, KEYRING_KEY};
+pub use envelope::{decode, encode, Collection, Envelope, Loaded, CREATED_BY, SCHEMA_VERSION};
+#[cfg(not(target_arch = "wasm32"))]
+pub use file::FileStore;
+#[cfg(all(not(target_arch = "wasm32"), feature = "sqlite"))]
+pub use sqlite::SqliteStore;
+
+pub const IDENTITIES_KEY: &str = "faceguard_identities";
+pub const EVENTS_KEY: &str = "faceguard_events";
+pub const SNAPSHOT_PREFIX: &str = "faceguard_snapshot/";
+/// Sizes of the stored snapshots, so pruning doesn't have to read them all
+pub const SNAPSHOT_SIZES_KEY: &str = "faceguard_snapshot_sizes";
+
+/// Key an unreadable blob stored under `key` is moved to, so it can be
+/// inspected or recovered instead of being overwritten
+pub fn quarantine_key(key: &str, timestamp: u64) -> String {
+    format!("{}.unreadable.{}", key, timestamp)
+}
+
+pub fn is_quarantine_key(key: &str) -> bool {
+    key.contains(".unreadable.")
+}
+
+/// Key/value persistence for identities (with their samples), events and
+/// snapshots. Backends implement the string-level methods; the typed ones
+/// are provided on top of the versioned envelope.
+pub trait Store {
+    /// Short backend name for status displays
+    fn name(&self) -> &'static str;
+
+    fn get(&self, key: &str) -> Result<Option<String>>;
+
+    fn put(&mut self, key: &str, value: &str) -> Result<()>;
+
+    fn delete(&mut self, key: &str) -> Result<()>;
+
+    fn keys(&self) -> Result<Vec<String>>;
+
+    /// `None` when nothing is stored yet. Unreadable data is moved to a
+    /// quarantine key and reported as an error.
+    fn load_identities(&mut self, now: u64) -> Result<Option<Loaded<IdentityDatabase>>> {
+        load_record(self, IDENTITIES_KEY, Collection::Identities, now)
+    }
+
+    fn save_identities(&mut self, db: &IdentityDatabase) -> Result<()> {
+        save_record(self, IDENTITIES_KEY, db, db.model().map(|m| m.to_string()))
+    }
+
+    fn load_events(&mut self, now: u64) -> Result<Option<Loaded<EventLog>>> {
+        load_record(self, EVENTS_KEY, Collection::Events, now)
+    }
+
+    fn save_events(&mut self, events: &EventLog) -> Result<()> {
+        save_record(self, EVENTS_KEY, events, None)
+    }
+
+    /// Snapshots are stored as data URLs under [`SNAPSHOT_PREFIX`]
+    fn put_snapshot(&mut self, id: &str, data_url: &str) -> Result<()> {
+        self.put(&format!("{}{}", SNAPSHOT_PREFIX, id), data_url)
+    }
+
+    fn get_snapshot(&self, id: &str) -> Result<Option<String>> {
+        self.get(&format!("{}{}", SNAPSHOT_PREFIX, id))
+    }
+
+    fn delete_snapshot(&mut self, id: &str) -> Result<()> {
+        self.delete(&format!("{}{}", SNAPSHOT_PREFIX, id))
+    }
+
+    fn snapshot_ids(&self) -> Result<Vec<String>> {
+        Ok(self
+            .keys()?
+            .into_iter()
+            .filter_map(|key| key.strip_prefix(SNAPSHOT_PREFIX).map(str::to_string))
+            .collect())
+    }
+
+    /// Delete the oldest snapshots until the rest fit in `budget_bytes`,
+    /// returning how many went. Relies on snapshot ids sorting oldest first
+    /// and never being reused.
+    ///
+    /// Sizes are kept under [`SNAPSHOT_SIZES_KEY`]; only snapshots added
+    /// since the last prune are read to measure them.
+    fn prune_snapshots(&mut self, budget_bytes: usize) -> Result<usize> {
+        let known: BTreeMap<String, usize> = self
+            .get(SNAPSHOT_SIZES_KEY)?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        let mut sizes = BTreeMap::new();
+        for id in self.snapshot_ids()? {
+            let size = match known.get(&id) {
+                Some(&size) => size,
+                None => self.get_snapshot(&id)?.map_or(0, |s| s.len()),
+            };
+            sizes.insert(id, size);
+        }
+
+        let mut total: usize = sizes.values().sum();
+        let mut deleted = Vec::new();
+        for (id, &size) in &sizes {
+            if total <= budget_bytes {
+                break;
+            }
+            self.delete_snapshot(id)?;
+            total -= size;
+            deleted.push(id.clone());
+        }
+        for id in &deleted {
+            sizes.remove(id);
+        }
+        if sizes != known {
+            self.put(SNAPSHOT_SIZES_KEY, &serde_json::to_string(&sizes)?)?;
+        }
+        Ok(deleted.len())
+    }
+
+    fn quarantined_keys(&self) -> Result<Vec<String>> {
+        Ok(self.keys()?.into_iter().filter(|key| is_quarantine_key(key)).collect())
+    }
+}
+
+impl<S: Store + ?Sized> Store for Box<S> {
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        (**self).get(key)
+    }
+
+    fn put(&mut self, key: &str, value: &str) -> Result<()> {
+        (**self).put(key, value)
+    }
+
+    fn delete(&mut self, key: &str) -> Result<()> {
+        (**self).delete(key)
+    }
+
+    fn keys(&self) -> Result<Vec<String>> {
+        (**self).keys()
+    }
+}
+
+fn load_record<S: Store + ?Sized, T: DeserializeOwned>(
+    store: &mut S,
+    key: &str,
+    collection: Collection,
+    now: u64,
+) -> Result<Option<Loaded<T>>> {
+    let Some(blob) = store.get(key)? else {
+        return Ok(None);
+    };
+    match decode(collection, &blob) {
+        Ok(loaded) => Ok(Some(loaded)),
+        Err(e) => {
+            let aside = quarantine_key(key, now);
+            store.put(&aside, &blob)?;
+            store.delete(key)?;
+            Err(e.context(format!("{} could not be read and was kept as {}", key, aside)))
+        }
+    }
+}
+
+fn save_record<S: Store + ?Sized, T: Serialize>(
+    store: &mut S,
+    key: &str,
+    payload: &T,
+    model_id: Option<String>,
+) -> Result<()> {
+    let json = encode(payload, model_id)?;
+    store.put(key, &json).with_context(|| format!("saving {} to {}", key, store.name()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use std::cell::Cell;
+
+    #[derive(Default)]
+    struct CountingStore {
+        records: BTreeMap<String, String>,
+        reads: Cell<usize>,
+    }
+
+    impl Store for CountingStore {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        fn get(&self, key: &str) -> Result<Option<String>> {
+            self.reads.set(self.reads.get() + 1);
+            Ok(self.records.get(key).cloned())
+        }
+
+        fn put(&mut self, key: &str, value: &str) -> Result<()> {
+            self.records.insert(key.to_string(), value.to_string());
+            Ok(())
+        }
+
+        fn delete(&mut self, key: &str) -> Result<()> {
+            self.records.remove(key);
+            Ok(())
+        }
+
+        fn keys(&self) -> Result<Vec<String>> {
+            Ok(self.records.keys().cloned().collect())
+        }
+    }
+
+    #[test]
+    fn prune_reads_only_new_snapshots() {
+        let mut store = CountingStore::default();
+        for i in 0..10 {
+            store.put_snapshot(&format!("{:03}", i), &"x".repeat(100)).unwrap();
+        }
+        assert_eq!(store.prune_snapshots(550).unwrap(), 5);
+        assert_eq!(store.snapshot_ids().unwrap(), ["005", "006", "007", "008", "009"]);
+
+        // One new snapshot: the size index plus that snapshot are read
+        store.put_snapshot("010", &"x".repeat(100)).unwrap();
+        store.reads.set(0);
+        assert_eq!(store.prune_snapshots(550).unwrap(), 1);
+        assert_eq!(store.reads.get(), 2);
+        assert_eq!(store.snapshot_ids().unwrap().first().map(String::as_str), Some("006"));
+
+        // Snapshots deleted elsewhere drop out of the index
+        store.delete_snapshot("006").unwrap();
+        store.reads.set(0);
+        assert_eq!(store.prune_snapshots(550).unwrap(), 0);
+        assert_eq!(store.reads.get(), 1);
+        let sizes: BTreeMap<String, usize> = serde_json::from_str(&store.records[SNAPSHOT_SIZES_KEY]).unwrap();
+        assert_eq!(sizes.len(), 4);
+    }
+
+    #[test]
+    fn unreadable_records_are_quarantined() {
+        let mut store = CountingStore::default();
+        assert!(store.load_identities(1).unwrap().is_none());
+
+        store.put(IDENTITIES_KEY, "{\"identities\": [").unwrap();
+        let err = store.load_identities(77).unwrap_err();
+        assert!(format!("{:#}", err).contains(&quarantine_key(IDENTITIES_KEY, 77)), "{:#}", err);
+        assert_eq!(store.quarantined_keys().unwrap(), [quarantine_key(IDENTITIES_KEY, 77)]);
+        assert_eq!(store.records[&quarantine_key(IDENTITIES_KEY, 77)], "{\"identities\": [");
+
+        // The bad blob is out of the way, so the next load starts fresh
+        assert!(store.load_identities(78).unwrap().is_none());
+        store.save_identities(&IdentityDatabase::new()).unwrap();
+        assert!(store.load_identities(79).unwrap().is_some());
+        assert_eq!(store.quarantined_keys().unwrap().len(), 1);
+    }
+}
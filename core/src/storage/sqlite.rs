@@ -0,0 +1,98 @@
+use super::Store;
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+
+/// Records in a single SQLite table, for native and Tauri builds
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let conn = Connection::open(path).with_context(|| format!("opening {}", path.display()))?;
+        Self::with_connection(conn)
+    }
+
+    pub fn in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS records (key TEXT PRIMARY KEY, value TEXT NOT NULL);",
+        )
+        .context("initialising SQLite store")?;
+        Ok(Self { conn })
+    }
+}
+
+impl Store for SqliteStore {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        self.conn
+            .query_row("SELECT value FROM records WHERE key = ?1", params![key], |row| row.get(0))
+            .optional()
+            .with_context(|| format!("reading {}", key))
+    }
+
+    fn put(&mut self, key: &str, value: &str) -> Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO records (key, value) VALUES (?1, ?2)
+                 ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                params![key, value],
+            )
+            .with_context(|| format!("writing {}", key))?;
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM records WHERE key = ?1", params![key])
+            .with_context(|| format!("removing {}", key))?;
+        Ok(())
+    }
+
+    fn keys(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT key FROM records ORDER BY key")?;
+        let keys = stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?;
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_lists_and_deletes() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        assert_eq!(store.get("missing").unwrap(), None);
+        store.put("b/two", "2").unwrap();
+        store.put("a.one", "1").unwrap();
+        store.put("b/two", "22").unwrap();
+        assert_eq!(store.get("b/two").unwrap().as_deref(), Some("22"));
+        assert_eq!(store.keys().unwrap(), ["a.one", "b/two"]);
+
+        store.delete("a.one").unwrap();
+        store.delete("a.one").unwrap();
+        assert_eq!(store.get("a.one").unwrap(), None);
+        assert_eq!(store.keys().unwrap(), ["b/two"]);
+    }
+
+    #[test]
+    fn records_persist_across_reopening() {
+        let path = std::env::temp_dir().join(format!("faceguard-sqlite-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        SqliteStore::open(&path).unwrap().put("key", "value").unwrap();
+        let store = SqliteStore::open(&path).unwrap();
+        assert_eq!(store.get("key").unwrap().as_deref(), Some("value"));
+        assert_eq!(store.keys().unwrap(), ["key"]);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::recognition::l2_normalize;
+use serde::{Deserialize, Serialize};
+
+/// How face embeddings take part in tracking
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppearanceConfig {
+    /// Share of the association cost taken by cosine distance when both
+    /// sides have an embedding; the rest is the motion cost
+    pub weight: f32,
+    /// Pairs further apart than this cosine distance are never associated
+    pub max_distance: f32,
+    /// How long a lost track can be re-activated by a matching face
+    pub reactivation_window_ms: u64,
+    /// How far, in box diagonals, a face may be from where a lost track
+    /// was last estimated and still take it over
+    #[serde(default = "default_max_reactivation_distance")]
+    pub max_reactivation_distance: f32,
+    /// Weight of the previous rolling embedding when a new one arrives
+    pub momentum: f32,
+}
+
+impl Default for AppearanceConfig {
+    fn default() -> Self {
+        Self {
+            weight: 0.5,
+            max_distance: 0.4,
+            reactivation_window_ms: 5000,
+            max_reactivation_distance: default_max_reactivation_distance(),
+            momentum: 0.9,
+        }
+    }
+}
+
+fn default_max_reactivation_distance() -> f32 {
+    3.0
+}
+
+/// Fold `embedding` into a rolling, L2-normalised appearance
+pub(crate) fn blend(appearance: &mut Option<Vec<f32>>, embedding: &[f32], momentum: f32) {
+    match appearance {
+        Some(current) if current.len() == embedding.len() => {
+            for (a, e) in current.iter_mut().zip(embedding) {
+                *a = momentum * *a + (1.0 - momentum) * e;
+            }
+            l2_normalize(current);
+        }
+        _ => {
+            let mut fresh = embedding.to_vec();
+            l2_normalize(&mut fresh);
+            *appearance = Some(fresh);
+        }
+    }
+}
+
+/// 1 - cosine similarity of two L2-normalised embeddings, `None` if their
+/// dimensions differ
+pub(crate) fn cosine_distance(a: &[f32], b: &[f32]) -> Option<f32> {
+    (a.len() == b.len()).then(|| 1.0 - a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>())
+}
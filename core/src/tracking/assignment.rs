@@ -0,0 +1,200 @@
+/// Minimum-cost assignment of rows to columns (Hungarian method with
+/// potentials, O(n²m)). `None` entries are gated out and never assigned, as
+/// are NaN and infinite costs.
+/// Returns the column assigned to each row.
+pub fn solve(costs: &[Vec<Option<f32>>]) -> Vec<Option<usize>> {
+    let rows = costs.len();
+    let cols = costs.first().map_or(0, Vec::len);
+    if rows == 0 || cols == 0 {
+        return vec![None; rows];
+    }
+
+    // The solver wants no more rows than columns
+    if rows > cols {
+        let transposed: Vec<Vec<Option<f32>>> = (0..cols)
+            .map(|c| costs.iter().map(|row| row[c]).collect())
+            .collect();
+        let mut assignment = vec![None; rows];
+        for (c, r) in solve(&transposed).into_iter().enumerate() {
+            if let Some(r) = r {
+                assignment[r] = Some(c);
+            }
+        }
+        return assignment;
+    }
+
+    // A NaN cost would never compare below the slack and stall the search
+    let usable = |r: usize, c: usize| costs[r][c].filter(|c| c.is_finite());
+    // Gated pairs get a cost no real assignment can reach, then are dropped
+    let gated = 1.0
+        + costs
+            .iter()
+            .flatten()
+            .flatten()
+            .filter(|c| c.is_finite())
+            .fold(0.0f64, |sum, &c| sum + c.abs() as f64);
+    let cost = |r: usize, c: usize| usable(r, c).map_or(gated, f64::from);
+
+    // 1-based as in the textbook formulation; row 0 / column 0 are dummies
+    let mut u = vec![0.0; rows + 1];
+    let mut v = vec![0.0; cols + 1];
+    let mut owner = vec![0usize; cols + 1];
+    let mut way = vec![0usize; cols + 1];
+
+    for row in 1..=rows {
+        owner[0] = row;
+        let mut col0 = 0;
+        let mut min_slack = vec![f64::INFINITY; cols + 1];
+        let mut used = vec![false; cols + 1];
+        loop {
+            used[col0] = true;
+            let row0 = owner[col0];
+            let mut delta = f64::INFINITY;
+            let mut col1 = 0;
+            for col in 1..=cols {
+                if used[col] {
+                    continue;
+                }
+                let slack = cost(row0 - 1, col - 1) - u[row0] - v[col];
+                if slack < min_slack[col] {
+                    min_slack[col] = slack;
+                    way[col] = col0;
+                }
+                if min_slack[col] < delta {
+                    delta = min_slack[col];
+                    col1 = col;
+                }
+            }
+            for col in 0..=cols {
+                if used[col] {
+                    u[owner[col]] += delta;
+                    v[col] -= delta;
+                } else {
+                    min_slack[col] -= delta;
+                }
+            }
+            col0 = col1;
+            if owner[col0] == 0 {
+                break;
+            }
+        }
+        // Flip the augmenting path
+        loop {
+            let col1 = way[col0];
+            owner[col0] = owner[col1];
+            col0 = col1;
+            if col0 == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut assignment = vec![None; rows];
+    for (col, &row) in owner.iter().enumerate().skip(1) {
+        if row != 0 && usable(row - 1, col - 1).is_some() {
+            assignment[row - 1] = Some(col - 1);
+        }
+    }
+    assignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix(rows: &[&[f32]]) -> Vec<Vec<Option<f32>>> {
+        rows.iter()
+            .map(|row| row.iter().map(|&c| c.is_finite().then_some(c)).collect())
+            .collect()
+    }
+
+    /// Most pairs, then lowest total cost, by trying every assignment
+    fn brute_force(costs: &[Vec<Option<f32>>]) -> (usize, f32) {
+        fn go(costs: &[Vec<Option<f32>>], row: usize, used: &mut Vec<bool>) -> (usize, f32) {
+            if row == costs.len() {
+                return (0, 0.0);
+            }
+            let mut best = go(costs, row + 1, used);
+            for col in 0..used.len() {
+                if let (false, Some(cost)) = (used[col], costs[row][col]) {
+                    used[col] = true;
+                    let (pairs, total) = go(costs, row + 1, used);
+                    used[col] = false;
+                    let candidate = (pairs + 1, total + cost);
+                    if candidate.0 > best.0 || (candidate.0 == best.0 && candidate.1 < best.1) {
+                        best = candidate;
+                    }
+                }
+            }
+            best
+        }
+        go(costs, 0, &mut vec![false; costs.first().map_or(0, Vec::len)])
+    }
+
+    fn score(costs: &[Vec<Option<f32>>], assignment: &[Option<usize>]) -> (usize, f32) {
+        assignment
+            .iter()
+            .enumerate()
+            .filter_map(|(row, col)| costs[row][(*col)?])
+            .fold((0, 0.0), |(pairs, total), cost| (pairs + 1, total + cost))
+    }
+
+    #[test]
+    fn empty_input() {
+        assert!(solve(&[]).is_empty());
+        assert_eq!(solve(&[vec![], vec![]]), vec![None, None]);
+    }
+
+    #[test]
+    fn rectangular() {
+        // More columns than rows
+        let costs = matrix(&[&[0.9, 0.1, 0.5], &[0.2, 0.3, 0.8]]);
+        assert_eq!(solve(&costs), vec![Some(1), Some(0)]);
+        // More rows than columns: the costlier row goes unassigned
+        let costs = matrix(&[&[0.9, 0.4], &[0.1, 0.8], &[0.7, 0.2]]);
+        assert_eq!(solve(&costs), vec![None, Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn gated_entries_are_never_assigned() {
+        let inf = f32::INFINITY;
+        assert_eq!(solve(&matrix(&[&[inf, inf], &[inf, inf]])), vec![None, None]);
+        // Greedy would take the cheap 0.1, leaving row 1 with only a gated pair
+        let costs = matrix(&[&[0.1, 0.5], &[0.2, inf]]);
+        assert_eq!(solve(&costs), vec![Some(1), Some(0)]);
+        let costs = matrix(&[&[inf, 0.3, inf], &[inf, 0.2, inf]]);
+        assert_eq!(solve(&costs), vec![None, Some(1)]);
+    }
+
+    #[test]
+    fn non_finite_costs_are_gated() {
+        let (nan, inf) = (f32::NAN, f32::INFINITY);
+        let costs = vec![vec![Some(nan), Some(0.4)], vec![Some(0.2), Some(nan)]];
+        assert_eq!(solve(&costs), vec![Some(1), Some(0)]);
+        let costs = vec![vec![Some(nan), Some(inf)], vec![Some(-inf), Some(0.3)]];
+        assert_eq!(solve(&costs), vec![None, Some(1)]);
+        assert_eq!(solve(&vec![vec![Some(nan)]; 3]), vec![None; 3]);
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 40) as f32 / (1u64 << 24) as f32
+        };
+        for _ in 0..300 {
+            let rows = 1 + (next() * 5.0) as usize;
+            let cols = 1 + (next() * 5.0) as usize;
+            let costs: Vec<Vec<Option<f32>>> = (0..rows)
+                .map(|_| (0..cols).map(|_| (next() > 0.3).then(&mut next)).collect())
+                .collect();
+            let (pairs, total) = score(&costs, &solve(&costs));
+            let (best_pairs, best_total) = brute_force(&costs);
+            assert_eq!(pairs, best_pairs, "{:?}", costs);
+            assert!((total - best_total).abs() < 1e-4, "{:?}", costs);
+        }
+    }
+}
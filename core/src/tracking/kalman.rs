@@ -0,0 +1,228 @@
+use serde::{Deserialize, Serialize};
+
+// Noise, relative to the box scale so small and large faces behave alike
+const MEASUREMENT_STD: f64 = 0.05; // of the scale
+const ACCELERATION_STD: f64 = 2.0; // scales per s²
+const INITIAL_VELOCITY_STD: f64 = 1.0; // scales per s
+const ASPECT_MEASUREMENT_STD: f64 = 0.02;
+const ASPECT_DRIFT_STD: f64 = 0.05; // per √s
+
+/// Constant-velocity Kalman filter over a box's centre, scale (square root
+/// of its area) and aspect ratio, as in SORT. The aspect ratio has no
+/// velocity. With SORT's diagonal noise the 7-state filter splits exactly
+/// into one filter per axis, which is how it's computed here.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoxFilter {
+    u: Axis,
+    v: Axis,
+    s: Axis,
+    r: Level,
+    timestamp: u64,
+}
+
+impl BoxFilter {
+    pub fn new(bbox: (f32, f32, f32, f32), timestamp: u64) -> Self {
+        let (u, v, s, r) = to_state(bbox);
+        let position_var = (MEASUREMENT_STD * s).powi(2);
+        let velocity_var = (INITIAL_VELOCITY_STD * s).powi(2);
+        Self {
+            u: Axis::new(u, position_var, velocity_var),
+            v: Axis::new(v, position_var, velocity_var),
+            s: Axis::new(s, position_var, velocity_var),
+            r: Level::new(r, ASPECT_MEASUREMENT_STD.powi(2)),
+            timestamp,
+        }
+    }
+
+    /// Advance the estimate to `timestamp`; earlier times are ignored
+    pub fn predict(&mut self, timestamp: u64) {
+        if timestamp <= self.timestamp {
+            return;
+        }
+        let dt = (timestamp - self.timestamp) as f64 / 1000.0;
+        let accel_var = (ACCELERATION_STD * self.scale()).powi(2);
+        self.u.predict(dt, accel_var);
+        self.v.predict(dt, accel_var);
+        self.s.predict(dt, accel_var);
+        self.s.x = self.s.x.max(1.0);
+        self.r.predict(dt, ASPECT_DRIFT_STD.powi(2));
+        self.timestamp = timestamp;
+    }
+
+    /// Correct the estimate with a detected box
+    pub fn update(&mut self, bbox: (f32, f32, f32, f32)) {
+        let (u, v, s, r) = to_state(bbox);
+        let measurement_var = (MEASUREMENT_STD * self.scale()).powi(2);
+        self.u.update(u, measurement_var);
+        self.v.update(v, measurement_var);
+        self.s.update(s, measurement_var);
+        self.r.update(r, ASPECT_MEASUREMENT_STD.powi(2));
+    }
+
+    /// Estimated box as (x, y, w, h)
+    pub fn bbox(&self) -> (f32, f32, f32, f32) {
+        let ratio = self.r.x.max(0.05).sqrt();
+        let scale = self.scale();
+        let (w, h) = (scale * ratio, scale / ratio);
+        ((self.u.x - w / 2.0) as f32, (self.v.x - h / 2.0) as f32, w as f32, h as f32)
+    }
+
+    /// Estimated centre velocity in pixels per second
+    pub fn velocity(&self) -> (f32, f32) {
+        (self.u.dx as f32, self.v.dx as f32)
+    }
+
+    fn scale(&self) -> f64 {
+        self.s.x.max(1.0)
+    }
+}
+
+/// Centre x, centre y, scale and aspect ratio (w / h) of a box
+fn to_state((x, y, w, h): (f32, f32, f32, f32)) -> (f64, f64, f64, f64) {
+    let (x, y, w, h) = (x as f64, y as f64, (w as f64).max(1.0), (h as f64).max(1.0));
+    (x + w / 2.0, y + h / 2.0, (w * h).sqrt(), w / h)
+}
+
+/// Position and velocity along one axis, with their 2x2 covariance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Axis {
+    x: f64,
+    dx: f64,
+    p: [[f64; 2]; 2],
+}
+
+impl Axis {
+    fn new(x: f64, position_var: f64, velocity_var: f64) -> Self {
+        Self {
+            x,
+            dx: 0.0,
+            p: [[position_var, 0.0], [0.0, velocity_var]],
+        }
+    }
+
+    /// x' = x + dx·dt with white-noise acceleration of variance `accel_var`
+    fn predict(&mut self, dt: f64, accel_var: f64) {
+        self.x += self.dx * dt;
+        let [[p00, p01], [p10, p11]] = self.p;
+        // F P Fᵀ
+        let p00 = p00 + dt * (p10 + p01) + dt * dt * p11;
+        let p01 = p01 + dt * p11;
+        let p10 = p10 + dt * p11;
+        // + Q
+        let (dt2, dt3, dt4) = (dt * dt, dt * dt * dt, dt * dt * dt * dt);
+        self.p = [
+            [p00 + accel_var * dt4 / 4.0, p01 + accel_var * dt3 / 2.0],
+            [p10 + accel_var * dt3 / 2.0, p11 + accel_var * dt2],
+        ];
+    }
+
+    fn update(&mut self, z: f64, measurement_var: f64) {
+        let [[p00, p01], [p10, p11]] = self.p;
+        let innovation_var = p00 + measurement_var;
+        let (k0, k1) = (p00 / innovation_var, p10 / innovation_var);
+        let residual = z - self.x;
+        self.x += k0 * residual;
+        self.dx += k1 * residual;
+        self.p = [[(1.0 - k0) * p00, (1.0 - k0) * p01], [p10 - k1 * p00, p11 - k1 * p01]];
+    }
+}
+
+/// A value expected to stay put, drifting slowly
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Level {
+    x: f64,
+    p: f64,
+}
+
+impl Level {
+    fn new(x: f64, var: f64) -> Self {
+        Self { x, p: var }
+    }
+
+    fn predict(&mut self, dt: f64, drift_var: f64) {
+        self.p += drift_var * dt;
+    }
+
+    fn update(&mut self, z: f64, measurement_var: f64) {
+        let gain = self.p / (self.p + measurement_var);
+        self.x += gain * (z - self.x);
+        self.p *= 1.0 - gain;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: (f32, f32, f32, f32), b: (f32, f32, f32, f32), tolerance: f32) -> bool {
+        [(a.0, b.0), (a.1, b.1), (a.2, b.2), (a.3, b.3)].iter().all(|(x, y)| (x - y).abs() < tolerance)
+    }
+
+    #[test]
+    fn first_box_initialises_the_state() {
+        let bbox = (40.0, 60.0, 80.0, 100.0);
+        let mut filter = BoxFilter::new(bbox, 1_000);
+        assert!(close(filter.bbox(), bbox, 1e-3), "{:?}", filter.bbox());
+        assert_eq!(filter.velocity(), (0.0, 0.0));
+        let scale = (80.0f64 * 100.0).sqrt();
+        assert!((filter.u.p[0][0] - (MEASUREMENT_STD * scale).powi(2)).abs() < 1e-9);
+        assert!((filter.u.p[1][1] - (INITIAL_VELOCITY_STD * scale).powi(2)).abs() < 1e-9);
+        assert_eq!((filter.u.p[0][1], filter.u.p[1][0]), (0.0, 0.0));
+
+        // No velocity yet, so predicting leaves the box; going back is ignored
+        filter.predict(1_500);
+        assert!(close(filter.bbox(), bbox, 1e-3));
+        filter.predict(1_200);
+        assert_eq!(filter.timestamp, 1_500);
+
+        // The first update pulls the box most of the way to the measurement
+        filter.update((50.0, 60.0, 80.0, 100.0));
+        let (x, ..) = filter.bbox();
+        assert!(x > 45.0 && x < 50.0, "{}", x);
+        assert!(filter.velocity().0 > 0.0);
+    }
+
+    #[test]
+    fn converges_on_constant_velocity() {
+        // 120 px/s right, 60 px/s up, growing 30 px/s, at 30 fps
+        let at = |frame: u64| {
+            let t = frame as f32 / 30.0;
+            (100.0 + 120.0 * t, 300.0 - 60.0 * t, 80.0 + 30.0 * t, 96.0 + 36.0 * t)
+        };
+        let timestamp = |frame: u64| frame * 1000 / 30;
+        let mut filter = BoxFilter::new(at(0), 0);
+        for frame in 1..90 {
+            filter.predict(timestamp(frame));
+            filter.update(at(frame));
+        }
+        let (vx, vy) = filter.velocity();
+        assert!((vx - (120.0 + 15.0)).abs() < 3.0, "{}", vx);
+        assert!((vy - (-60.0 + 18.0)).abs() < 3.0, "{}", vy);
+
+        // Coasting a few frames lands close to where the box went
+        filter.predict(timestamp(95));
+        assert!(close(filter.bbox(), at(95), 1.0), "{:?} vs {:?}", filter.bbox(), at(95));
+    }
+
+    #[test]
+    fn uncertainty_grows_while_missed() {
+        let mut filter = BoxFilter::new((0.0, 0.0, 50.0, 50.0), 0);
+        for frame in 1..10 {
+            filter.predict(frame * 33);
+            filter.update((2.0 * frame as f32, 0.0, 50.0, 50.0));
+        }
+        let mut previous = (filter.u.p[0][0], filter.u.p[1][1], filter.s.p[0][0], filter.r.p);
+        for frame in 10..20 {
+            filter.predict(frame * 33);
+            let current = (filter.u.p[0][0], filter.u.p[1][1], filter.s.p[0][0], filter.r.p);
+            assert!(current.0 > previous.0 && current.1 > previous.1, "{:?} {:?}", current, previous);
+            assert!(current.2 > previous.2 && current.3 > previous.3, "{:?} {:?}", current, previous);
+            previous = current;
+        }
+        assert!((filter.u.p[0][0] - filter.v.p[0][0]).abs() < 1e-9);
+
+        // A detection brings it back down
+        filter.update((40.0, 0.0, 50.0, 50.0));
+        assert!(filter.u.p[0][0] < previous.0 && filter.r.p < previous.3);
+    }
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TrackState {
+    /// New, not yet seen often enough to be trusted
+    Tentative,
+    #[default]
+    Confirmed,
+    /// Confirmed but missed by the detector; coasting on its motion model
+    /// and, once stale, kept for re-activation by appearance
+    Lost,
+    /// Gone for good
+    Deleted,
+}
+
+/// A track changing state; `from` is `None` when the track is created
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackEvent {
+    pub track_id: u32,
+    pub from: Option<TrackState>,
+    pub to: TrackState,
+    pub timestamp: u64,
+}
+
+/// How many matches a new track needs, and within how many updates, before
+/// it's confirmed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Confirmation {
+    pub hits: u32,
+    pub window: u32,
+}
+
+impl Default for Confirmation {
+    fn default() -> Self {
+        Self { hits: 3, window: 5 }
+    }
+}
@@ -0,0 +1,557 @@
+use crate::clock::{default_clock, SharedClock};
+use crate::detection::FaceDetection;
+use appearance::{blend, cosine_distance};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+mod appearance;
+mod assignment;
+mod kalman;
+mod lifecycle;
+
+pub use appearance::AppearanceConfig;
+pub use assignment::solve as solve_assignment;
+pub use kalman::BoxFilter;
+pub use lifecycle::{Confirmation, TrackEvent, TrackState};
+
+// Transitions kept for `Tracker::drain_events` when nobody drains them
+const MAX_PENDING_EVENTS: usize = 1024;
+
+/// A detection and its face embedding, if one was computed
+pub type EmbeddedDetection = (FaceDetection, Option<Vec<f32>>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Track {
+    pub track_id: u32,
+    pub detection: FaceDetection,
+    pub frames_tracked: u32,
+    pub last_seen: u64, // timestamp
+    pub identity_id: Option<u32>,
+    /// When the track was last matched against the identity database
+    #[serde(default)]
+    pub identified_at: Option<u64>,
+    /// Consecutive updates without a matching detection; while above zero
+    /// the track coasts on its motion model
+    #[serde(default)]
+    pub missed: u32,
+    #[serde(default)]
+    motion: Option<BoxFilter>,
+    /// Rolling L2-normalised face embedding, for re-identification
+    #[serde(default)]
+    pub appearance: Option<Vec<f32>>,
+    #[serde(default)]
+    pub state: TrackState,
+    /// Tracker updates since the track was created, this one included
+    #[serde(default)]
+    pub age: u32,
+}
+
+impl Track {
+    pub fn new(track_id: u32, detection: FaceDetection, timestamp: u64) -> Self {
+        Self {
+            track_id,
+            frames_tracked: 1,
+            last_seen: timestamp,
+            identity_id: None,
+            identified_at: None,
+            missed: 0,
+            motion: Some(BoxFilter::new(detection.bbox, timestamp)),
+            appearance: None,
+            state: TrackState::Tentative,
+            age: 1,
+            detection,
+        }
+    }
+
+    pub fn update(&mut self, detection: FaceDetection, timestamp: u64) {
+        let motion = self.motion.get_or_insert_with(|| BoxFilter::new(detection.bbox, timestamp));
+        motion.predict(timestamp);
+        motion.update(detection.bbox);
+        self.detection = detection;
+        self.frames_tracked += 1;
+        self.last_seen = timestamp;
+        self.missed = 0;
+    }
+
+    /// Move the motion estimate forward to `timestamp`
+    pub fn predict(&mut self, timestamp: u64) {
+        if let Some(motion) = &mut self.motion {
+            motion.predict(timestamp);
+        }
+    }
+
+    /// Where the motion model puts the face now; the last detection for
+    /// tracks without one
+    pub fn predicted_bbox(&self) -> (f32, f32, f32, f32) {
+        self.motion.as_ref().map_or(self.detection.bbox, BoxFilter::bbox)
+    }
+
+    pub fn is_coasting(&self) -> bool {
+        self.missed > 0
+    }
+
+    /// Only confirmed tracks should raise events or be identified
+    pub fn is_confirmed(&self) -> bool {
+        self.state == TrackState::Confirmed
+    }
+
+    /// Whether the track has never been identified or its last match is
+    /// at least `refresh_ms` old
+    pub fn needs_identification(&self, timestamp: u64, refresh_ms: u64) -> bool {
+        self.identified_at
+            .is_none_or(|at| timestamp.saturating_sub(at) >= refresh_ms)
+    }
+}
+
+pub struct Tracker {
+    tracks: Vec<Track>,
+    next_id: u32,
+    iou_threshold: f32,
+    max_age: u64, // milliseconds
+    /// Weight of the centre distance term in the association cost, 0 for
+    /// IoU only
+    centre_weight: f32,
+    /// Centre distance, in box diagonals, up to which a pair below the IoU
+    /// threshold may still be associated when `centre_weight` is set
+    max_centre_distance: f32,
+    appearance: Option<AppearanceConfig>,
+    /// Stale tracks with an appearance, kept for re-activation
+    lost: Vec<Track>,
+    confirmation: Confirmation,
+    /// State transitions not yet drained
+    events: VecDeque<TrackEvent>,
+    /// Latest update time, which track times never run past
+    latest: Option<u64>,
+    timestamp_regressions: u64,
+    clock: SharedClock,
+}
+
+impl Tracker {
+    pub fn new(iou_threshold: f32, max_age_ms: u64) -> Self {
+        Self {
+            tracks: Vec::new(),
+            next_id: 1,
+            iou_threshold,
+            max_age: max_age_ms,
+            centre_weight: 0.0,
+            max_centre_distance: 0.0,
+            appearance: None,
+            lost: Vec::new(),
+            confirmation: Confirmation::default(),
+            events: VecDeque::new(),
+            latest: None,
+            timestamp_regressions: 0,
+            clock: default_clock(),
+        }
+    }
+
+    /// Confirm new tracks after `hits` matches within their first
+    /// `window` updates, deleting those that can't make it. One hit
+    /// confirms tracks straight away.
+    pub fn with_confirmation(mut self, hits: u32, window: u32) -> Self {
+        self.confirmation = Confirmation {
+            hits,
+            window: window.max(hits),
+        };
+        self
+    }
+
+    /// Add centre distance (in box diagonals) to the association cost,
+    /// which keeps fast-moving faces whose boxes no longer overlap
+    pub fn with_centre_distance(mut self, weight: f32, max_distance: f32) -> Self {
+        self.centre_weight = weight;
+        self.max_centre_distance = max_distance;
+        self
+    }
+
+    /// Use face embeddings in association and re-activate lost tracks
+    /// whose face reappears
+    pub fn with_appearance(mut self, config: AppearanceConfig) -> Self {
+        self.appearance = Some(config);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// `update` with the current time from the tracker's clock, for
+    /// sources whose frames carry no timestamp
+    pub fn update_now(&mut self, detections: Vec<FaceDetection>) -> Vec<Track> {
+        let timestamp = self.clock.now_ms();
+        self.update(detections, timestamp)
+    }
+
+    pub fn update(&mut self, detections: Vec<FaceDetection>, timestamp: u64) -> Vec<Track> {
+        let detections = detections.into_iter().map(|det| (det, None)).collect();
+        self.update_with_embeddings(detections, timestamp)
+    }
+
+    /// `update` for detections that may come with a face embedding, which
+    /// takes part in association when appearance is enabled
+    pub fn update_with_embeddings(
+        &mut self,
+        detections: Vec<EmbeddedDetection>,
+        timestamp: u64,
+    ) -> Vec<Track> {
+        let timestamp = self.monotonic(timestamp);
+        // A NaN or infinite box can't be matched, and would poison a new track
+        let detections: Vec<_> = detections.into_iter().filter(|(det, _)| is_finite_box(det)).collect();
+
+        // Remove stale tracks, keeping lost ones with an appearance for a while
+        let (live, stale): (Vec<_>, Vec<_>) = self
+            .tracks
+            .drain(..)
+            .partition(|t| timestamp.saturating_sub(t.last_seen) < self.max_age);
+        self.tracks = live;
+        for mut track in stale {
+            if self.appearance.is_some() && track.appearance.is_some() && track.state != TrackState::Tentative {
+                transition(&mut self.events, &mut track, TrackState::Lost, timestamp);
+                self.lost.push(track);
+            } else {
+                transition(&mut self.events, &mut track, TrackState::Deleted, timestamp);
+            }
+        }
+        if let Some(config) = &self.appearance {
+            let (kept, expired): (Vec<_>, Vec<_>) = self
+                .lost
+                .drain(..)
+                .partition(|t| timestamp.saturating_sub(t.last_seen) <= config.reactivation_window_ms);
+            self.lost = kept;
+            for mut track in expired {
+                transition(&mut self.events, &mut track, TrackState::Deleted, timestamp);
+            }
+        }
+
+        for track in &mut self.tracks {
+            track.predict(timestamp);
+        }
+
+        // Assign detections to predicted tracks jointly, so a track can't
+        // take a detection another track fits better
+        let costs: Vec<Vec<Option<f32>>> = self
+            .tracks
+            .iter()
+            .map(|track| {
+                let predicted = FaceDetection {
+                    bbox: track.predicted_bbox(),
+                    ..track.detection.clone()
+                };
+                detections
+                    .iter()
+                    .map(|(det, embedding)| {
+                        let motion = self.motion_cost(&predicted, det)?;
+                        self.combined_cost(motion, track.appearance.as_deref(), embedding.as_deref())
+                    })
+                    .collect()
+            })
+            .collect();
+        let assignment = solve_assignment(&costs);
+
+        let mut unmatched_detections: Vec<_> = detections.into_iter().map(Some).collect();
+        let mut matched_tracks = Vec::new();
+        let momentum = self.appearance.as_ref().map(|config| config.momentum);
+        let confirmation = self.confirmation;
+        for (track, det_idx) in self.tracks.iter_mut().zip(assignment) {
+            track.age += 1;
+            if let Some((detection, embedding)) = det_idx.and_then(|idx| unmatched_detections[idx].take()) {
+                track.update(detection, timestamp);
+                if let (Some(embedding), Some(momentum)) = (embedding, momentum) {
+                    blend(&mut track.appearance, &embedding, momentum);
+                }
+                let confirmed = match track.state {
+                    TrackState::Tentative => track.frames_tracked >= confirmation.hits,
+                    TrackState::Lost => true,
+                    _ => false,
+                };
+                if confirmed {
+                    transition(&mut self.events, track, TrackState::Confirmed, timestamp);
+                }
+                if track.is_confirmed() {
+                    matched_tracks.push(track.clone());
+                }
+            } else {
+                track.missed += 1;
+                match track.state {
+                    TrackState::Confirmed => transition(&mut self.events, track, TrackState::Lost, timestamp),
+                    // Delete as soon as the remaining updates can't bring enough hits
+                    TrackState::Tentative
+                        if track.frames_tracked + confirmation.window.saturating_sub(track.age) < confirmation.hits =>
+                    {
+                        transition(&mut self.events, track, TrackState::Deleted, timestamp)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.tracks.retain(|t| t.state != TrackState::Deleted);
+
+        // Bring back lost tracks whose face reappeared
+        matched_tracks.extend(self.reactivate(&mut unmatched_detections, timestamp));
+
+        // Create new tracks for unmatched detections
+        for (detection, embedding) in unmatched_detections.into_iter().flatten() {
+            let mut track = Track::new(self.next_id, detection, timestamp);
+            self.next_id += 1;
+            if let (Some(embedding), Some(momentum)) = (embedding, momentum) {
+                blend(&mut track.appearance, &embedding, momentum);
+            }
+            push_event(
+                &mut self.events,
+                TrackEvent {
+                    track_id: track.track_id,
+                    from: None,
+                    to: track.state,
+                    timestamp,
+                },
+            );
+            if confirmation.hits <= 1 {
+                transition(&mut self.events, &mut track, TrackState::Confirmed, timestamp);
+                matched_tracks.push(track.clone());
+            }
+            self.tracks.push(track);
+        }
+
+        matched_tracks
+    }
+
+    /// How many updates came with a timestamp earlier than one already seen
+    pub fn timestamp_regressions(&self) -> u64 {
+        self.timestamp_regressions
+    }
+
+    /// Keep time moving forward. A step back shorter than `max_age`
+    /// (frames out of order) is clamped to the latest time seen; a longer
+    /// one (clock change, replay starting over) deletes every track and
+    /// restarts from the new time. Both are counted in
+    /// `timestamp_regressions`.
+    fn monotonic(&mut self, timestamp: u64) -> u64 {
+        let latest = match self.latest {
+            Some(latest) if timestamp < latest => latest,
+            _ => {
+                self.latest = Some(timestamp);
+                return timestamp;
+            }
+        };
+        self.timestamp_regressions += 1;
+        if latest - timestamp < self.max_age {
+            return latest;
+        }
+        for mut track in self.tracks.drain(..).chain(self.lost.drain(..)) {
+            transition(&mut self.events, &mut track, TrackState::Deleted, timestamp);
+        }
+        self.latest = Some(timestamp);
+        timestamp
+    }
+
+    /// Take the state transitions since the last call, oldest first
+    pub fn drain_events(&mut self) -> Vec<TrackEvent> {
+        self.events.drain(..).collect()
+    }
+
+    /// Match unmatched detections that have an embedding against nearby
+    /// lost tracks on appearance, taking matched detections out
+    fn reactivate(
+        &mut self,
+        detections: &mut [Option<EmbeddedDetection>],
+        timestamp: u64,
+    ) -> Vec<Track> {
+        let Some(config) = self.appearance.clone() else {
+            return Vec::new();
+        };
+        if self.lost.is_empty() {
+            return Vec::new();
+        }
+
+        let costs: Vec<Vec<Option<f32>>> = self
+            .lost
+            .iter()
+            .map(|lost| {
+                detections
+                    .iter()
+                    .map(|slot| {
+                        let (detection, embedding) = slot.as_ref()?;
+                        if !near_lost(lost, detection, &config) {
+                            return None;
+                        }
+                        let distance = cosine_distance(lost.appearance.as_deref()?, embedding.as_deref()?)?;
+                        (distance <= config.max_distance).then_some(distance)
+                    })
+                    .collect()
+            })
+            .collect();
+        let assignment = solve_assignment(&costs);
+
+        let mut reactivated = Vec::new();
+        let mut still_lost = Vec::new();
+        for (mut track, det_idx) in self.lost.drain(..).zip(assignment) {
+            match det_idx.and_then(|idx| detections[idx].take()) {
+                Some((detection, embedding)) => {
+                    // The old motion estimate is meaningless after a gap
+                    track.motion = Some(BoxFilter::new(detection.bbox, timestamp));
+                    track.update(detection, timestamp);
+                    if let Some(embedding) = embedding {
+                        blend(&mut track.appearance, &embedding, config.momentum);
+                    }
+                    transition(&mut self.events, &mut track, TrackState::Confirmed, timestamp);
+                    self.tracks.push(track.clone());
+                    reactivated.push(track);
+                }
+                None => still_lost.push(track),
+            }
+        }
+        self.lost = still_lost;
+        reactivated
+    }
+
+    /// Fold a face embedding into a track's appearance. A track's first
+    /// embedding is also checked against recently lost tracks nearby; on a
+    /// match the track takes over the lost track's id, identity and
+    /// appearance, and that id is returned. Does nothing unless appearance
+    /// is enabled.
+    pub fn set_appearance(&mut self, track_id: u32, embedding: &[f32]) -> Option<u32> {
+        let config = self.appearance.as_ref()?;
+        let track = self.tracks.iter_mut().find(|t| t.track_id == track_id)?;
+        let first = track.appearance.is_none();
+        blend(&mut track.appearance, embedding, config.momentum);
+        if !first {
+            return None;
+        }
+
+        let appearance = track.appearance.as_deref()?;
+        let (index, _) = self
+            .lost
+            .iter()
+            .enumerate()
+            .filter(|(_, lost)| near_lost(lost, &track.detection, config))
+            .filter_map(|(i, lost)| Some((i, cosine_distance(lost.appearance.as_deref()?, appearance)?)))
+            .filter(|(_, distance)| *distance <= config.max_distance)
+            .min_by(|a, b| a.1.total_cmp(&b.1))?;
+        let lost = self.lost.remove(index);
+        let timestamp = track.last_seen;
+        // The new id ends here and the lost track carries on in its place
+        push_event(
+            &mut self.events,
+            TrackEvent {
+                track_id: track.track_id,
+                from: Some(track.state),
+                to: TrackState::Deleted,
+                timestamp,
+            },
+        );
+        track.track_id = lost.track_id;
+        track.identity_id = lost.identity_id;
+        track.identified_at = lost.identified_at;
+        track.frames_tracked += lost.frames_tracked;
+        // Keep the lost track's history, with the new face folded in
+        let mut merged = lost.appearance.clone();
+        blend(&mut merged, embedding, config.momentum);
+        track.appearance = merged;
+        track.state = TrackState::Confirmed;
+        push_event(
+            &mut self.events,
+            TrackEvent {
+                track_id: lost.track_id,
+                from: Some(lost.state),
+                to: track.state,
+                timestamp,
+            },
+        );
+        Some(lost.track_id)
+    }
+
+    /// Association cost of a track's box and a detection, `None` if gated
+    fn motion_cost(&self, track: &FaceDetection, det: &FaceDetection) -> Option<f32> {
+        let iou = track.iou(det);
+        let distance = centre_distance(track, det);
+        let centre_gate = self.centre_weight > 0.0 && distance <= self.max_centre_distance;
+        if iou <= self.iou_threshold && !centre_gate {
+            return None;
+        }
+        let cost = 1.0 - iou + self.centre_weight * distance;
+        cost.is_finite().then_some(cost)
+    }
+
+    /// Blend in cosine distance when both sides have an embedding
+    fn combined_cost(&self, motion: f32, track: Option<&[f32]>, det: Option<&[f32]>) -> Option<f32> {
+        let Some(config) = &self.appearance else {
+            return Some(motion);
+        };
+        let Some(distance) = track.zip(det).and_then(|(t, d)| cosine_distance(t, d)) else {
+            return Some(motion);
+        };
+        if distance.is_nan() || distance > config.max_distance {
+            return None;
+        }
+        let cost = (1.0 - config.weight) * motion + config.weight * distance;
+        cost.is_finite().then_some(cost)
+    }
+
+    /// Every live track: tentative, confirmed and coasting ones. Draw them
+    /// at `Track::predicted_bbox` to bridge missed detections.
+    pub fn get_active_tracks(&self) -> Vec<Track> {
+        self.tracks.clone()
+    }
+
+    /// Record the outcome of matching a track, `None` meaning unknown
+    pub fn set_identity(&mut self, track_id: u32, identity_id: Option<u32>, timestamp: u64) {
+        if let Some(track) = self.tracks.iter_mut().find(|t| t.track_id == track_id) {
+            track.identity_id = identity_id;
+            track.identified_at = Some(timestamp);
+        }
+    }
+}
+
+// Legacy function for compatibility
+pub fn track_faces() -> Vec<Track> {
+    vec![]
+}
+
+fn transition(events: &mut VecDeque<TrackEvent>, track: &mut Track, to: TrackState, timestamp: u64) {
+    if track.state == to {
+        return;
+    }
+    push_event(
+        events,
+        TrackEvent {
+            track_id: track.track_id,
+            from: Some(track.state),
+            to,
+            timestamp,
+        },
+    );
+    track.state = to;
+}
+
+fn push_event(events: &mut VecDeque<TrackEvent>, event: TrackEvent) {
+    if events.len() >= MAX_PENDING_EVENTS {
+        events.pop_front();
+    }
+    events.push_back(event);
+}
+
+/// Whether `detection` is close enough to a lost track's last estimate to
+/// take it over
+fn near_lost(lost: &Track, detection: &FaceDetection, config: &AppearanceConfig) -> bool {
+    let estimate = FaceDetection {
+        bbox: lost.predicted_bbox(),
+        ..lost.detection.clone()
+    };
+    centre_distance(&estimate, detection) <= config.max_reactivation_distance
+}
+
+fn is_finite_box(det: &FaceDetection) -> bool {
+    let (x, y, w, h) = det.bbox;
+    [x, y, w, h].iter().all(|v| v.is_finite())
+}
+
+/// Distance between box centres relative to the first box's diagonal
+fn centre_distance(a: &FaceDetection, b: &FaceDetection) -> f32 {
+    let (ax, ay, aw, ah) = a.bbox;
+    let (bx, by, bw, bh) = b.bbox;
+    let dx = (ax + aw / 2.0) - (bx + bw / 2.0);
+    let dy = (ay + ah / 2.0) - (by + bh / 2.0);
+    let diagonal = (aw * aw + ah * ah).sqrt().max(1.0);
+    (dx * dx + dy * dy).sqrt() / diagonal
+}
@@ -0,0 +1,57 @@
+//! Replaying a recording through detection, tracking and the event log.
+
+use faceguard_core::camera::{FrameSource, RawVideoHeader, RawVideoSource};
+use faceguard_core::detection::{Detector, EdgeDensityConfig, EdgeDensityDetector};
+use faceguard_core::events::{EventLog, EventType};
+use faceguard_core::tracking::Tracker;
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 48;
+const FRAMES: usize = 20;
+
+/// RGB frames with a checkered patch drifting right
+fn write_recording(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("faceguard-replay-{}-{}.rgb", std::process::id(), name));
+    let mut bytes = Vec::with_capacity(WIDTH * HEIGHT * FRAMES * 3);
+    for frame in 0..FRAMES {
+        let left = 4 + frame;
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let inside = (left..left + 20).contains(&x) && (12..32).contains(&y);
+                let value = if inside && (x + y) % 2 == 0 { 255 } else { 40 };
+                bytes.extend([value; 3]);
+            }
+        }
+    }
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+fn replay(path: &std::path::Path) -> String {
+    let header = RawVideoHeader::parse(&format!("width={}\nheight={}\nformat=rgb24\nfps=25", WIDTH, HEIGHT)).unwrap();
+    let mut source = RawVideoSource::new(path).with_header(header).with_start_timestamp(1_700_000_000_000);
+    let mut detector = EdgeDensityDetector::new(EdgeDensityConfig::default());
+    let mut tracker = Tracker::new(0.3, 1000);
+    let mut log = EventLog::new(100);
+
+    source.open().unwrap();
+    while let Some(frame) = source.next_frame().unwrap() {
+        let detections = detector.detect(&frame);
+        for track in tracker.update(detections, frame.timestamp()) {
+            let confidence = track.detection.confidence;
+            log.add_event_at(EventType::UnknownFace, "Unknown".into(), confidence, Some(track.track_id), None, frame.timestamp());
+        }
+    }
+    source.close();
+
+    assert!(!log.is_empty());
+    assert!(log.get_all().iter().all(|e| (e.timestamp - 1_700_000_000_000) % 40 == 0));
+    serde_json::to_string(&log).unwrap()
+}
+
+#[test]
+fn replays_give_identical_event_logs() {
+    let path = write_recording("identical");
+    let first = replay(&path);
+    assert_eq!(first, replay(&path));
+}
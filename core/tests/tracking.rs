@@ -0,0 +1,332 @@
+//! Tracker scenarios: faces crossing, occluded and re-appearing.
+
+use faceguard_core::detection::FaceDetection;
+use faceguard_core::tracking::{AppearanceConfig, Track, TrackEvent, TrackState, Tracker};
+
+const FRAME_MS: u64 = 33;
+
+fn face(x: f32, y: f32) -> FaceDetection {
+    FaceDetection::new(0, x, y, 80.0, 80.0, 0.9)
+}
+
+/// Id of the returned track that took detection `det` this frame
+fn id_of(tracks: &[Track], det: &FaceDetection) -> Option<u32> {
+    tracks.iter().find(|t| t.detection.bbox == det.bbox).map(|t| t.track_id)
+}
+
+/// Feed one frame and check each visible face keeps the id it had
+fn step(tracker: &mut Tracker, frame: u64, faces: &[(usize, FaceDetection)], ids: &mut [Option<u32>]) {
+    let dets = faces.iter().map(|(_, det)| det.clone()).collect();
+    let tracks = tracker.update(dets, frame * FRAME_MS);
+    for (person, det) in faces {
+        let Some(id) = id_of(&tracks, det) else {
+            // Not confirmed yet
+            assert!(ids[*person].is_none(), "frame {}: person {} lost its track", frame, person);
+            continue;
+        };
+        match ids[*person] {
+            None => ids[*person] = Some(id),
+            Some(expected) => assert_eq!(id, expected, "frame {}: person {} changed track", frame, person),
+        }
+    }
+}
+
+#[test]
+fn crossing_faces_keep_their_ids() {
+    let mut tracker = Tracker::new(0.1, 1000);
+    let mut ids = [None, None];
+    for frame in 0..=40 {
+        let t = frame as f32 / 40.0;
+        let a = face(100.0 + 300.0 * t, 100.0);
+        let b = face(400.0 - 300.0 * t, 110.0);
+        step(&mut tracker, frame, &[(0, a), (1, b)], &mut ids);
+    }
+    assert!(ids[0].is_some() && ids[1].is_some() && ids[0] != ids[1]);
+}
+
+#[test]
+fn crossing_with_one_face_hidden_behind_the_other() {
+    let mut tracker = Tracker::new(0.1, 1000);
+    let mut ids = [None, None];
+    for frame in 0..=40 {
+        let t = frame as f32 / 40.0;
+        let a = face(100.0 + 300.0 * t, 100.0);
+        let b = face(400.0 - 300.0 * t, 104.0);
+        // b passes behind a around the middle of the clip
+        let faces = if (18..=22).contains(&frame) { vec![(0, a)] } else { vec![(0, a), (1, b)] };
+        step(&mut tracker, frame, &faces, &mut ids);
+    }
+    assert!(ids[0].is_some() && ids[1].is_some() && ids[0] != ids[1]);
+}
+
+#[test]
+fn short_occlusions_keep_the_id() {
+    for gap in [1, 2, 4] {
+        let mut tracker = Tracker::new(0.3, 1000);
+        let mut ids = [None];
+        for frame in 0..30 {
+            let det = face(100.0 + 6.0 * frame as f32, 100.0);
+            let hidden = (10..10 + gap).contains(&frame);
+            let faces = if hidden { vec![] } else { vec![(0, det)] };
+            step(&mut tracker, frame, &faces, &mut ids);
+        }
+        assert!(ids[0].is_some(), "gap {}", gap);
+        assert_eq!(tracker.get_active_tracks().len(), 1, "gap {}", gap);
+    }
+}
+
+#[test]
+fn occlusion_longer_than_max_age_starts_a_new_track() {
+    let mut tracker = Tracker::new(0.3, 200).with_confirmation(1, 1);
+    let first = tracker.update(vec![face(100.0, 100.0)], 0)[0].track_id;
+    tracker.update(vec![], 100);
+    let again = tracker.update(vec![face(100.0, 100.0)], 400)[0].track_id;
+    assert_ne!(first, again);
+}
+
+fn appearance_tracker() -> Tracker {
+    Tracker::new(0.3, 200)
+        .with_confirmation(1, 1)
+        .with_appearance(AppearanceConfig::default())
+}
+
+/// Track one face with embedding `face_embedding` until it goes stale and
+/// is kept as lost; returns its id
+fn lose_face(tracker: &mut Tracker, face_embedding: &[f32]) -> u32 {
+    let id = tracker.update_with_embeddings(vec![(face(100.0, 100.0), Some(face_embedding.to_vec()))], 0)[0].track_id;
+    tracker.update_with_embeddings(vec![(face(100.0, 100.0), Some(face_embedding.to_vec()))], 100);
+    tracker.update(vec![], 400);
+    assert!(tracker.get_active_tracks().is_empty());
+    id
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+const ALICE: [f32; 4] = [1.0, 0.0, 0.0, 0.0];
+const ALICE_TURNED: [f32; 4] = [0.95, 0.312, 0.0, 0.0];
+
+#[test]
+fn reappearing_face_reactivates_its_lost_track() {
+    let mut tracker = appearance_tracker();
+    let id = lose_face(&mut tracker, &ALICE);
+    let tracks = tracker.update_with_embeddings(vec![(face(130.0, 110.0), Some(ALICE_TURNED.to_vec()))], 500);
+    assert_eq!(tracks.len(), 1);
+    assert_eq!(tracks[0].track_id, id);
+}
+
+#[test]
+fn first_embedding_takes_over_a_lost_track() {
+    let mut tracker = appearance_tracker();
+    let id = lose_face(&mut tracker, &ALICE);
+    // Detected without an embedding first, so it gets a fresh id
+    let fresh = tracker.update(vec![face(130.0, 110.0)], 500)[0].track_id;
+    assert_ne!(fresh, id);
+
+    assert_eq!(tracker.set_appearance(fresh, &ALICE_TURNED), Some(id));
+    let tracks = tracker.get_active_tracks();
+    assert_eq!(tracks.len(), 1);
+    assert_eq!(tracks[0].track_id, id);
+    // The lost track's appearance carries on, with the new face folded in
+    let appearance = tracks[0].appearance.as_deref().unwrap();
+    assert!(cosine(appearance, &ALICE) > cosine(appearance, &ALICE_TURNED));
+    assert!(cosine(appearance, &ALICE_TURNED) < 1.0 - 1e-4);
+}
+
+#[test]
+fn similar_face_far_away_does_not_take_over() {
+    let mut tracker = appearance_tracker();
+    let id = lose_face(&mut tracker, &ALICE);
+    let far = face(1200.0, 600.0);
+    let tracks = tracker.update_with_embeddings(vec![(far.clone(), Some(ALICE.to_vec()))], 500);
+    assert_ne!(tracks[0].track_id, id);
+
+    let mut tracker = appearance_tracker();
+    let id = lose_face(&mut tracker, &ALICE);
+    let fresh = tracker.update(vec![far], 500)[0].track_id;
+    assert_eq!(tracker.set_appearance(fresh, &ALICE), None);
+    assert_eq!(tracker.get_active_tracks()[0].track_id, fresh);
+    assert_ne!(fresh, id);
+}
+
+/// Drained events as `(track_id, from, to)`
+fn transitions(tracker: &mut Tracker) -> Vec<(u32, Option<TrackState>, TrackState)> {
+    tracker
+        .drain_events()
+        .into_iter()
+        .map(|TrackEvent { track_id, from, to, .. }| (track_id, from, to))
+        .collect()
+}
+
+#[test]
+fn tentative_track_is_confirmed_after_enough_hits() {
+    use TrackState::*;
+    let mut tracker = Tracker::new(0.3, 1000).with_confirmation(3, 5);
+    assert!(tracker.update(vec![face(100.0, 100.0)], 0).is_empty());
+    assert!(tracker.update(vec![face(102.0, 100.0)], 33).is_empty());
+    assert_eq!(tracker.get_active_tracks()[0].state, Tentative);
+    let tracks = tracker.update(vec![face(104.0, 100.0)], 66);
+    assert_eq!(tracks.len(), 1);
+    let id = tracks[0].track_id;
+    assert_eq!(transitions(&mut tracker), [(id, None, Tentative), (id, Some(Tentative), Confirmed)]);
+}
+
+#[test]
+fn tentative_track_is_deleted_once_it_cannot_be_confirmed() {
+    use TrackState::*;
+    let mut tracker = Tracker::new(0.3, 1000).with_confirmation(3, 5);
+    tracker.update(vec![face(100.0, 100.0)], 0);
+    let id = tracker.get_active_tracks()[0].track_id;
+    tracker.update(vec![], 33);
+    tracker.update(vec![], 66);
+    assert_eq!(tracker.get_active_tracks().len(), 1);
+    // One hit in four updates, so three are out of reach within five
+    tracker.update(vec![], 99);
+    assert!(tracker.get_active_tracks().is_empty());
+    let events = tracker.drain_events();
+    assert_eq!(events.last().map(|e| e.timestamp), Some(99));
+    assert_eq!(
+        events.into_iter().map(|e| (e.track_id, e.from, e.to)).collect::<Vec<_>>(),
+        [(id, None, Tentative), (id, Some(Tentative), Deleted)]
+    );
+}
+
+#[test]
+fn confirmed_track_is_lost_then_deleted() {
+    use TrackState::*;
+    let mut tracker = Tracker::new(0.3, 200).with_confirmation(1, 1);
+    let id = tracker.update(vec![face(100.0, 100.0)], 0)[0].track_id;
+    tracker.update(vec![], 33);
+    assert_eq!(tracker.get_active_tracks()[0].state, Lost);
+    tracker.update(vec![], 300);
+    assert!(tracker.get_active_tracks().is_empty());
+    assert_eq!(
+        transitions(&mut tracker),
+        [
+            (id, None, Tentative),
+            (id, Some(Tentative), Confirmed),
+            (id, Some(Confirmed), Lost),
+            (id, Some(Lost), Deleted),
+        ]
+    );
+    assert!(tracker.drain_events().is_empty());
+}
+
+#[test]
+fn lost_track_is_confirmed_again_when_matched() {
+    use TrackState::*;
+    let mut tracker = Tracker::new(0.3, 1000).with_confirmation(1, 1);
+    let id = tracker.update(vec![face(100.0, 100.0)], 0)[0].track_id;
+    tracker.update(vec![], 33);
+    tracker.drain_events();
+    assert_eq!(tracker.update(vec![face(100.0, 100.0)], 66)[0].track_id, id);
+    assert_eq!(transitions(&mut tracker), [(id, Some(Lost), Confirmed)]);
+}
+
+#[test]
+fn undrained_events_keep_the_latest() {
+    let mut tracker = Tracker::new(0.3, 10).with_confirmation(1, 1);
+    for frame in 0..1000 {
+        tracker.update(vec![face(100.0 * (frame % 2) as f32, 0.0)], frame * FRAME_MS);
+    }
+    let events = tracker.drain_events();
+    assert_eq!(events.len(), 1024);
+    assert_eq!(events.last().map(|e| e.timestamp), Some(999 * FRAME_MS));
+    assert!(events.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp));
+}
+
+#[test]
+fn short_timestamp_regression_is_clamped() {
+    let mut tracker = Tracker::new(0.3, 1000).with_confirmation(1, 1);
+    let id = tracker.update(vec![face(100.0, 100.0)], 5000)[0].track_id;
+    let tracks = tracker.update(vec![face(102.0, 100.0)], 4800);
+    assert_eq!(tracks[0].track_id, id);
+    assert_eq!(tracks[0].last_seen, 5000);
+    assert_eq!(tracker.timestamp_regressions(), 1);
+    // Time carries on from the latest seen
+    assert_eq!(tracker.update(vec![face(104.0, 100.0)], 5033)[0].track_id, id);
+    assert_eq!(tracker.timestamp_regressions(), 1);
+}
+
+#[test]
+fn long_timestamp_regression_resets_the_tracks() {
+    let mut tracker = Tracker::new(0.3, 1000).with_confirmation(1, 1);
+    let first = tracker.update(vec![face(100.0, 100.0)], 60_000)[0].track_id;
+    let second = tracker.update(vec![face(400.0, 100.0)], 60_033).last().unwrap().track_id;
+    tracker.drain_events();
+
+    let tracks = tracker.update(vec![face(100.0, 100.0)], 1000);
+    assert_eq!(tracker.timestamp_regressions(), 1);
+    assert_eq!(tracks.len(), 1);
+    assert!(tracks[0].track_id != first && tracks[0].track_id != second);
+    assert_eq!(tracks[0].last_seen, 1000);
+
+    let deleted: Vec<_> = tracker
+        .drain_events()
+        .into_iter()
+        .filter(|e| e.to == TrackState::Deleted)
+        .map(|e| (e.track_id, e.timestamp))
+        .collect();
+    assert_eq!(deleted, [(first, 1000), (second, 1000)]);
+}
+
+#[test]
+fn out_of_order_timestamps_are_counted() {
+    let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+
+    let max_age = 500;
+    let mut tracker = Tracker::new(0.3, max_age).with_confirmation(2, 3);
+    let mut latest = None;
+    let mut regressions = 0;
+    for frame in 0..2000u64 {
+        // Mostly forward, sometimes a little or a lot back
+        let timestamp = match next() % 20 {
+            0 => (frame * FRAME_MS).saturating_sub(next() % 400),
+            1 => next() % (frame * FRAME_MS + 1),
+            _ => frame * FRAME_MS,
+        };
+        let faces = (0..next() % 4)
+            .map(|i| face(150.0 * i as f32 + (next() % 20) as f32, 100.0))
+            .collect();
+        tracker.update(faces, timestamp);
+
+        match latest {
+            Some(l) if timestamp < l => {
+                regressions += 1;
+                if l - timestamp >= max_age {
+                    latest = Some(timestamp);
+                }
+            }
+            _ => latest = Some(timestamp),
+        }
+        assert_eq!(tracker.timestamp_regressions(), regressions);
+        let latest = latest.unwrap();
+        assert!(tracker.get_active_tracks().iter().all(|t| t.last_seen <= latest));
+    }
+    assert!(regressions > 0);
+}
+
+#[test]
+fn non_finite_detections_are_ignored() {
+    let mut tracker = Tracker::new(0.3, 1000).with_confirmation(1, 1);
+    let mut id = None;
+    for frame in 0..20 {
+        let det = face(100.0 + 6.0 * frame as f32, 100.0);
+        let mut broken = det.clone();
+        broken.bbox.2 = f32::NAN;
+        let tracks = tracker.update(vec![broken, face(f32::INFINITY, 100.0), det.clone()], frame * FRAME_MS);
+        assert_eq!(tracks.len(), 1, "frame {}", frame);
+        let this = id_of(&tracks, &det).unwrap();
+        assert_eq!(*id.get_or_insert(this), this, "frame {}", frame);
+    }
+    let active = tracker.get_active_tracks();
+    assert_eq!(active.len(), 1);
+    assert!(active[0].predicted_bbox().0.is_finite());
+}
@@ -1,10 +1,15 @@
 use dioxus::prelude::*;
-use faceguard_core::{camera, detection, events, recognition, tracking};
-use gloo_storage::{LocalStorage, Storage};
+use faceguard_core::detection::{self, Detector};
+use faceguard_core::frame::{Frame, PixelFormat};
+use faceguard_core::recognition::FaceEmbedder;
+use faceguard_core::{alignment, camera, clock, events, recognition, storage, tracking};
+use faceguard_core::storage::Store;
 use gloo_timers::callback::Interval;
 use js_sys::Array;
 use serde_json;
-use wasm_bindgen::JsCast;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use wasm_bindgen::{Clamped, JsCast};
 use wasm_bindgen::JsValue;
 use wasm_bindgen_futures::{spawn_local, JsFuture};
 use web_sys::{
@@ -12,9 +17,44 @@ use web_sys::{
     MediaStream, MediaStreamConstraints,
 };
 
-// Global state keys for persistence
-const IDENTITY_DB_KEY: &str = "faceguard_identities";
-const EVENT_LOG_KEY: &str = "faceguard_events";
+mod store;
+
+// Detection model served from ui/public; without it detection falls back
+// to MediaPipe from the CDN, then to the edge-density heuristic
+const DETECTION_MODEL_URL: &str = "/models/blaze_face_short_range.onnx";
+
+// Embedding model served from ui/public; enrolment needs it to be present
+const EMBEDDING_MODEL_URL: &str = "/models/mobilefacenet.onnx";
+const EMBEDDING_MODEL_NAME: &str = "mobilefacenet";
+const EMBEDDING_MODEL_VERSION: &str = "1";
+
+// How often tracks are re-checked against the identity database
+const IDENTITY_REFRESH_MS: u64 = 2000;
+
+// Least time between Dashboard saves of the identity database, which only
+// change there to update `last_seen`
+const IDENTITY_SAVE_INTERVAL_MS: u64 = 60_000;
+
+// Recognition: minimum similarity to accept a face, and its minimum lead
+// over the runner-up
+const MATCH_THRESHOLD: f32 = 0.45;
+const MATCH_MARGIN: f32 = 0.05;
+
+// Events page: timeline entries per page, and the longest pause between
+// events from one track that still collapses them into one entry
+const EVENTS_PAGE_SIZE: usize = 50;
+const COLLAPSE_GAP_MS: u64 = 10_000;
+
+thread_local! {
+    static DETECTOR: RefCell<Option<detection::OnnxFaceDetector>> = const { RefCell::new(None) };
+    static EMBEDDER: RefCell<Option<recognition::OnnxFaceEmbedder>> = const { RefCell::new(None) };
+    // Chosen once at startup, see store::open_store
+    static STORE: RefCell<AppStore> = RefCell::new(storage::EncryptedStore::new(Box::new(store::LocalStorageStore)));
+    // The one time source for the UI and everything it hands to core
+    static CLOCK: clock::SharedClock = clock::default_clock();
+}
+
+type AppStore = storage::EncryptedStore<Box<dyn Store>>;
 
 macro_rules! log {
     ($($arg:tt)*) => {
@@ -187,6 +227,13 @@ const GLOBAL_STYLE: &str = r#"
     }
     
     .muted { color: #94a9c3; }
+    .event-thumb {
+        height: 36px;
+        margin-left: 8px;
+        vertical-align: middle;
+        border-radius: 6px;
+        cursor: pointer;
+    }
     
     .stats { 
         display: grid; 
@@ -244,6 +291,7 @@ const GLOBAL_STYLE: &str = r#"
     }
     
     input[type="text"],
+    input[type="date"],
     input[type="range"],
     select {
         background: rgba(255,255,255,0.08);
@@ -296,6 +344,32 @@ const GLOBAL_STYLE: &str = r#"
         border: 1px solid rgba(255,255,255,0.1);
         margin: 12px 0;
     }
+
+    .sample-strip {
+        display: flex;
+        flex-wrap: wrap;
+        gap: 8px;
+        margin: 12px 0;
+    }
+
+    .sample-strip img {
+        width: 72px;
+        height: 72px;
+        border-radius: 8px;
+        border: 1px solid rgba(255,255,255,0.1);
+        cursor: pointer;
+    }
+
+    .sample-strip img.selected {
+        outline: 2px solid #3fb5ff;
+    }
+
+    .identity-row {
+        padding: 10px;
+        border: 1px solid rgba(255,255,255,0.08);
+        border-radius: 10px;
+        margin-bottom: 8px;
+    }
     
     @media (max-width: 900px) { 
         .dashboard-grid { 
@@ -318,6 +392,34 @@ fn main() {
 
 fn app() -> Element {
     let mut current_page = use_signal(|| Page::Dashboard);
+    let mut store_ready = use_signal(|| false);
+    let mut locked = use_signal(|| false);
+
+    use_hook(|| {
+        spawn_local(async move {
+            let opened = storage::EncryptedStore::new(store::open_store().await);
+            log!("Storage backend: {}", opened.name());
+            locked.set(opened.is_locked());
+            STORE.with(|slot| *slot.borrow_mut() = opened);
+            store_ready.set(true);
+        })
+    });
+
+    use_hook(|| {
+        spawn_local(async {
+            if let Err(e) = load_detector().await {
+                log!("Detection model unavailable: {:?}", e);
+            }
+        })
+    });
+
+    use_hook(|| {
+        spawn_local(async {
+            if let Err(e) = load_embedder().await {
+                log!("Embedding model unavailable: {:?}", e);
+            }
+        })
+    });
 
     rsx! {
         document::Script {
@@ -373,12 +475,18 @@ window.detectFacesMediaPipe = function(videoId) {{
         
         const results = detections.detections.map(detection => {{
             const bbox = detection.boundingBox;
+            // Keypoints are normalized; convert to video pixels like the bbox
+            const keypoints = (detection.keypoints || []).map(kp => ({{
+                x: kp.x * video.videoWidth,
+                y: kp.y * video.videoHeight
+            }}));
             return {{
                 x: bbox.originX,
                 y: bbox.originY,
                 width: bbox.width,
                 height: bbox.height,
-                score: detection.categories[0]?.score || 0.5
+                score: detection.categories[0]?.score || 0.5,
+                keypoints: keypoints
             }};
         }});
         
@@ -422,11 +530,17 @@ if (document.readyState === 'loading') {{
             }
             
             div { class: "content",
-                match current_page() {
-                    Page::Dashboard => rsx! { Dashboard {} },
-                    Page::Events => rsx! { EventsPage {} },
-                    Page::Register => rsx! { RegisterPage {} },
-                    Page::Settings => rsx! { Settings {} },
+                if !store_ready() {
+                    p { class: "muted", "Opening storage..." }
+                } else if locked() {
+                    UnlockScreen { locked }
+                } else {
+                    match current_page() {
+                        Page::Dashboard => rsx! { Dashboard {} },
+                        Page::Events => rsx! { EventsPage {} },
+                        Page::Register => rsx! { RegisterPage {} },
+                        Page::Settings => rsx! { Settings { locked } },
+                    }
                 }
             }
         }
@@ -435,9 +549,10 @@ if (document.readyState === 'loading') {{
 
 #[component]
 fn Dashboard() -> Element {
-    let identity_db = load_identity_db();
+    // Loaded once per visit; the frame loop updates and saves this copy
+    let mut identity_db = use_signal(load_identity_db);
     let event_log = load_event_log();
-    let identities = identity_db.get_all();
+    let identities = identity_db.read().get_all();
     let events_data = event_log.get_recent(20);
 
     let mut detections = use_signal(|| vec![]);
@@ -445,10 +560,25 @@ fn Dashboard() -> Element {
     let mut frame_count = use_signal(|| 0);
     let mut fps = use_signal(|| 0.0);
     let mut faces_detected = use_signal(|| 0);
-    let mut last_time = use_signal(|| js_sys::Date::now());
-    let mut tracker = use_signal(|| tracking::Tracker::new(0.4, 3000));
+    let mut last_time = use_signal(now_ms);
+    let mut tracker = use_signal(|| {
+        tracking::Tracker::new(0.4, 3000)
+            .with_appearance(tracking::AppearanceConfig::default())
+            .with_clock(shared_clock())
+    });
+    let mut identity_names = use_signal(HashMap::<u32, String>::new);
+    let mut track_results = use_signal(HashMap::<u32, recognition::RecognitionResult>::new);
     let mut _interval_handle = use_signal::<Option<Interval>>(|| None);
     let mut camera_ready = use_signal(|| false);
+    let mut identity_db_dirty = use_signal(|| false);
+    let mut identity_db_saved_at = use_signal(now_ms);
+
+    // Write the `last_seen` updates still waiting for the next save
+    use_drop(move || {
+        if *identity_db_dirty.peek() {
+            let _ = save_identity_db(&identity_db.peek());
+        }
+    });
 
     // Start camera
     use_effect(move || {
@@ -473,14 +603,19 @@ fn Dashboard() -> Element {
 
         log!("Starting frame processing loop...");
 
+        let rules = recognition::DecisionRules {
+            threshold: MATCH_THRESHOLD,
+            margin: MATCH_MARGIN,
+            ..Default::default()
+        };
         let interval = Interval::new(100, move || {
             frame_count.set(frame_count() + 1);
 
-            let now = js_sys::Date::now();
-            let mut last = last_time();
+            let now = now_ms();
+            let last = last_time();
             
             // Calculate FPS
-            let delta = (now - last) / 1000.0;
+            let delta = now.saturating_sub(last) as f64 / 1000.0;
             if delta > 0.0 && delta < 1.0 {
                 fps.set(1.0 / delta);
             }
@@ -500,44 +635,170 @@ fn Dashboard() -> Element {
                         log!("[Frame {}] After NMS (IOU=0.3): {} faces", current_count, filtered_dets.len());
                     }
 
-                    let timestamp = now as u64;
+                    let timestamp = now;
                     let mut t = tracker.write();
-                    let active_tracks = t.update(filtered_dets.clone(), timestamp);
+                    let regressions = t.timestamp_regressions();
+                    let mut active_tracks = t.update(filtered_dets.clone(), timestamp);
+                    if t.timestamp_regressions() > regressions {
+                        log!("Frame timestamp {} went backwards; tracker kept its clock monotonic", timestamp);
+                    }
+                    for event in t.drain_events() {
+                        match event.from {
+                            Some(from) => log!("Track #{}: {:?} -> {:?}", event.track_id, from, event.to),
+                            None => log!("Track #{}: new ({:?})", event.track_id, event.to),
+                        }
+                    }
                     drop(t);
 
+                    // Embed new tracks and re-check known ones now and then
+                    let mut identity_db = identity_db.write();
+                    let mut results = track_results();
+                    results.retain(|id, _| active_tracks.iter().any(|t| t.track_id == *id));
+                    let mut frame = None;
+                    let mut db_changed = false;
+                    for track in active_tracks.iter_mut() {
+                        if !track.needs_identification(timestamp, IDENTITY_REFRESH_MS) {
+                            continue;
+                        }
+                        if frame.is_none() {
+                            frame = grab_frame("camera-feed", "temp-canvas").map(|(_, f)| f);
+                        }
+                        let Some(frame) = frame.as_ref() else { break };
+                        let Some((embedding, model)) = embed_face(frame, &track.detection) else {
+                            continue;
+                        };
+                        // A face that was only briefly lost keeps its old track
+                        if let Some(restored) = tracker.write().set_appearance(track.track_id, &embedding) {
+                            log!("Track #{} re-activated as #{}", track.track_id, restored);
+                            if let Some(result) = results.remove(&track.track_id) {
+                                results.insert(restored, result);
+                            }
+                            track.track_id = restored;
+                        }
+                        if let Some(db_model) = identity_db.model().filter(|m| **m != model) {
+                            log!("Skipping recognition: database uses {}", db_model);
+                            continue;
+                        }
+
+                        let result = identity_db.recognize(&embedding, &rules);
+                        track.identity_id = result.accepted().map(|c| c.identity_id);
+                        track.identified_at = Some(timestamp);
+                        tracker.write().set_identity(track.track_id, track.identity_id, timestamp);
+                        if let Some(candidate) = result.accepted() {
+                            identity_db.update_last_seen(candidate.identity_id);
+                            db_changed = true;
+                            log!("Track #{} recognized as {} ({:.2})", track.track_id, candidate.name, candidate.similarity);
+                        } else if result.decision == recognition::Decision::Ambiguous {
+                            log!("Track #{} ambiguous (margin {:.2})", track.track_id, result.margin.unwrap_or_default());
+                        }
+                        results.insert(track.track_id, result);
+                    }
+                    if db_changed {
+                        identity_db_dirty.set(true);
+                    }
+                    // Saving re-encrypts the whole database, thumbnails and all,
+                    // so `last_seen` is written at most once a period
+                    if identity_db_dirty() && timestamp.saturating_sub(identity_db_saved_at()) >= IDENTITY_SAVE_INTERVAL_MS {
+                        // Failures are logged; the next period tries again
+                        if save_identity_db(&identity_db).is_ok() {
+                            identity_db_dirty.set(false);
+                        }
+                        identity_db_saved_at.set(timestamp);
+                    }
+                    track_results.set(results.clone());
+
                     faces_detected.set(filtered_dets.len());
                     detections.set(filtered_dets);
-                    tracks.set(active_tracks.clone());
-
-                    // Log unknown face events
-                    let identity_db = load_identity_db();
+                    // Coasting tracks too, so a missed detection doesn't flicker,
+                    // but not tentative ones that may be false detections
+                    tracks.set(
+                        tracker
+                            .read()
+                            .get_active_tracks()
+                            .into_iter()
+                            .filter(|t| t.state != tracking::TrackState::Tentative)
+                            .collect(),
+                    );
+                    let names: HashMap<u32, String> = identity_db
+                        .get_all()
+                        .into_iter()
+                        .map(|identity| (identity.id, identity.name))
+                        .collect();
+                    identity_names.set(names.clone());
+
+                    // Log recognized and unknown face events
                     let mut event_log = load_event_log();
+                    let snapshot_policy = events::SnapshotPolicy::default();
+                    let mut snapshot_taken = false;
                     
                     for track in &active_tracks {
                         // Only log tracks that are currently being detected
-                        if timestamp - track.last_seen < 1000 {  // Within last second
+                        if timestamp.saturating_sub(track.last_seen) < 1000 {  // Within last second
+                            let result = results.get(&track.track_id);
+                            let (event_type, name, confidence, identity_id) = if let Some(best) = result.and_then(|r| r.accepted()) {
+                                // Blacklisted or out-of-window identities raise alerts instead
+                                let event_type = identity_db
+                                    .get(best.identity_id)
+                                    .map_or(events::EventType::FaceRecognized, |identity| identity.event_type_at(timestamp));
+                                (event_type, best.name.clone(), best.similarity, Some(best.identity_id))
+                            } else if let Some(r) = result.filter(|r| r.decision == recognition::Decision::Ambiguous) {
+                                // Name the contenders rather than silently picking one
+                                let names: Vec<_> = r.candidates.iter().take(2).map(|c| c.name.as_str()).collect();
+                                let similarity = r.best().map_or(0.0, |c| c.similarity);
+                                (events::EventType::LowConfidence, format!("{}?", names.join(" / ")), similarity, None)
+                            } else {
+                                (events::EventType::UnknownFace, "Unknown".to_string(), track.detection.confidence, None)
+                            };
+
                             // Check if we already logged this track recently
                             let already_logged = event_log.get_all().iter().any(|e| {
-                                e.event_type == events::EventType::UnknownFace && 
+                                e.event_type == event_type && 
                                 e.track_id == Some(track.track_id) &&
-                                (js_sys::Date::now() as u64 - e.timestamp) < 5000  // Within last 5 seconds
+                                timestamp.saturating_sub(e.timestamp) < 5000  // Within last 5 seconds
                             });
                             
                             if !already_logged {
-                                event_log.add_event(
-                                    events::EventType::UnknownFace,
-                                    "Unknown".to_string(),
-                                    track.detection.confidence,
+                                let event = event_log.add_event(
+                                    event_type,
+                                    name.clone(),
+                                    confidence,
                                     Some(track.track_id),
+                                    identity_id,
                                 );
-                                log!("Event: Unknown face detected (Track #{})", track.track_id);
+                                log!("Event: {:?} {} (Track #{})", event_type, name, track.track_id);
+
+                                if snapshot_policy.applies_to(event_type) {
+                                    if frame.is_none() {
+                                        frame = grab_frame("camera-feed", "temp-canvas").map(|(_, f)| f);
+                                    }
+                                    if let Some(frame) = frame.as_ref() {
+                                        let captured = with_store(|store| {
+                                            events::capture_snapshot(store, &snapshot_policy, &event, frame, track.detection.bbox)
+                                        });
+                                        match captured {
+                                            Ok(Some(id)) => {
+                                                event_log.set_snapshot(event.id, id);
+                                                snapshot_taken = true;
+                                            }
+                                            Ok(None) => {}
+                                            Err(e) => log!("Snapshot failed: {:#}", e),
+                                        }
+                                    }
+                                }
                             }
                         }
                     }
                     
                     save_event_log(&event_log);
+                    if snapshot_taken {
+                        match with_store(|store| store.prune_snapshots(snapshot_policy.retention_bytes)) {
+                            Ok(0) => {}
+                            Ok(count) => log!("Pruned {} old snapshots", count),
+                            Err(e) => log!("Failed to prune snapshots: {:#}", e),
+                        }
+                    }
 
-                    draw_detections_and_tracks("camera-feed", "overlay-canvas", &tracks());
+                    draw_detections_and_tracks("camera-feed", "overlay-canvas", &tracks(), &names);
                 }
                 None => {
                     if current_count % 30 == 0 {
@@ -623,7 +884,7 @@ fn Dashboard() -> Element {
                     ul { class: "list",
                         for track in tracks().iter() {
                             li {
-                                "#{track.track_id} · {track.frames_tracked}f · {(track.detection.confidence*100.0):.0}%"
+                                "{track_label(track, &identity_names())} · {track.frames_tracked}f · {(track.detection.confidence*100.0):.0}%"
                             }
                         }
                         if tracks().is_empty() {
@@ -660,20 +921,64 @@ fn Dashboard() -> Element {
 fn RegisterPage() -> Element {
     let mut name = use_signal(|| String::new());
     let mut notes = use_signal(|| String::new());
-    let mut captured_image = use_signal::<Option<String>>(|| None);
+    let mut tags = use_signal(|| String::new());
+    let mut role = use_signal(recognition::IdentityRole::default);
+    let mut valid_from = use_signal(|| String::new());
+    let mut valid_until = use_signal(|| String::new());
+    let mut samples = use_signal::<Vec<recognition::FaceSample>>(Vec::new);
+    let mut sample_model = use_signal::<Option<recognition::EmbeddingModel>>(|| None);
     let mut capture_status = use_signal(|| String::from("Ready"));
+    let mut revision = use_signal(|| 0u32);
+
+    use_effect(move || {
+        spawn_local(async move {
+            if let Err(err) = start_camera("camera-feed").await {
+                log!("✗ Camera error: {:?}", err);
+                capture_status.set(String::from("✗ Camera unavailable"));
+            }
+        });
+    });
 
     let capture_face = {
         move |_| {
             spawn_local(async move {
-                if let Some(image_data) = capture_face_from_video("camera-feed", "capture-canvas") {
-                    captured_image.set(Some(image_data));
-                    capture_status.set(String::from("✓ Face captured"));
-                    log!("Face captured successfully");
-                } else {
+                let Some((_, frame)) = grab_frame("camera-feed", "capture-canvas") else {
                     capture_status.set(String::from("✗ Capture failed"));
                     log!("Failed to capture face");
+                    return;
+                };
+
+                let face = detect_faces_from_video("camera-feed", "capture-canvas")
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|d| d.landmarks.is_some())
+                    .max_by(|a, b| a.area().total_cmp(&b.area()));
+                let Some(det) = face else {
+                    capture_status.set(String::from("✗ No face landmarks"));
+                    log!("Captured frame has no face with landmarks");
+                    return;
+                };
+                let Some(aligned) = det
+                    .landmarks
+                    .as_deref()
+                    .and_then(|lm| alignment::align_face(&frame, lm).ok())
+                else {
+                    capture_status.set(String::from("✗ Alignment failed"));
+                    return;
+                };
+                let Some((embedding, model)) = embed_aligned(&aligned) else {
+                    capture_status.set(String::from("✗ Face model not loaded"));
+                    return;
+                };
+
+                let mut sample = recognition::FaceSample::new(embedding, det.confidence, now_ms());
+                if let Some(thumbnail) = frame_to_data_url(&aligned, "capture-canvas") {
+                    sample = sample.with_thumbnail(thumbnail);
                 }
+                samples.write().push(sample);
+                sample_model.set(Some(model));
+                capture_status.set(format!("✓ {} sample(s) captured", samples().len()));
+                log!("Face sample captured");
             });
         }
     };
@@ -685,16 +990,42 @@ fn RegisterPage() -> Element {
                 capture_status.set(String::from("✗ Name required"));
                 return;
             }
-            
+            let Some(model) = sample_model() else {
+                capture_status.set(String::from("✗ Capture a face first"));
+                return;
+            };
+
+            let mut metadata = recognition::IdentityMetadata::default().with_role(role());
+            metadata.notes = notes().trim().to_string();
+            for tag in tags().split(',') {
+                metadata.add_tag(tag);
+            }
+            metadata.valid_from = parse_date(&valid_from());
+            // Inclusive of the whole last day
+            metadata.valid_until = parse_date(&valid_until()).map(|t| t + 86_400_000 - 1);
+
             let mut db = load_identity_db();
-            db.add_identity(n.clone(), None);
-            save_identity_db(&db);
-            
-            log!("Saved identity: {}", n);
+            let saved = db
+                .enroll(n.clone(), samples(), &model)
+                .and_then(|identity| db.set_metadata(identity.id, metadata))
+                .and_then(|()| save_identity_db(&db));
+            if let Err(e) = saved {
+                log!("Enrolment failed: {:#}", e);
+                capture_status.set(format!("✗ {}", e));
+                return;
+            }
+
+            log!("Saved identity: {} ({} samples)", n, samples().len());
             capture_status.set(String::from("✓ Identity saved!"));
             name.set(String::new());
             notes.set(String::new());
-            captured_image.set(None);
+            tags.set(String::new());
+            role.set(recognition::IdentityRole::default());
+            valid_from.set(String::new());
+            valid_until.set(String::new());
+            samples.set(Vec::new());
+            sample_model.set(None);
+            revision += 1;
         }
     };
 
@@ -716,18 +1047,75 @@ fn RegisterPage() -> Element {
                 div { class: "form-group",
                     label { "Capture Face" }
                     p { class: "muted", style: "margin: 0 0 8px 0; font-size: 13px;", 
-                        "Make sure your face is clearly visible in the video feed"
+                        "Make sure your face is clearly visible in the video feed. Capture a few samples with slightly different angles; click a sample to drop it."
+                    }
+                    video {
+                        id: "camera-feed",
+                        class: "capture-preview",
+                        autoplay: true,
+                        playsinline: true,
+                        muted: true,
+                        controls: false,
+                    }
+                    canvas {
+                        id: "capture-canvas",
+                        style: "display: none;",
                     }
                     button { onclick: capture_face, "Capture Face" }
-                    if let Some(img) = captured_image() {
-                        img {
-                            src: "{img}",
-                            class: "capture-preview",
-                            alt: "Captured face"
+                    div { class: "sample-strip",
+                        for (index, sample) in samples().into_iter().enumerate() {
+                            img {
+                                src: "{sample.thumbnail.clone().unwrap_or_default()}",
+                                alt: "Sample {index + 1}",
+                                title: "Quality {(sample.quality * 100.0):.0}%",
+                                onclick: move |_| {
+                                    samples.write().remove(index);
+                                },
+                            }
                         }
                     }
                 }
                 
+                div { class: "form-group",
+                    label { "Role" }
+                    select {
+                        onchange: move |e| {
+                            if let Some(r) = role_from_label(&e.value()) {
+                                role.set(r);
+                            }
+                        },
+                        for r in recognition::IdentityRole::ALL {
+                            option { value: "{r.label()}", selected: role() == r, "{r.label()}" }
+                        }
+                    }
+                }
+
+                div { class: "form-group",
+                    label { "Tags (comma separated)" }
+                    input {
+                        r#type: "text",
+                        placeholder: "e.g. night-shift, floor-3",
+                        value: "{tags()}",
+                        oninput: move |e| tags.set(e.value()),
+                    }
+                }
+
+                div { class: "form-group",
+                    label { "Valid From / Until (Optional)" }
+                    div { class: "controls",
+                        input {
+                            r#type: "date",
+                            value: "{valid_from()}",
+                            oninput: move |e| valid_from.set(e.value()),
+                        }
+                        input {
+                            r#type: "date",
+                            value: "{valid_until()}",
+                            oninput: move |e| valid_until.set(e.value()),
+                        }
+                    }
+                }
+
                 div { class: "form-group",
                     label { "Notes (Optional)" }
                     textarea {
@@ -742,6 +1130,170 @@ fn RegisterPage() -> Element {
                     span { class: "pill", "{capture_status()}" }
                 }
             }
+
+            IdentityManager { revision }
+        }
+    }
+}
+
+/// Rename, delete, merge and split registered identities
+#[component]
+fn IdentityManager(revision: Signal<u32>) -> Element {
+    let mut merge_selection = use_signal(Vec::<u32>::new);
+    // Identity whose samples are selected for splitting, and those samples
+    let mut split_selection = use_signal::<Option<(u32, Vec<u32>)>>(|| None);
+    let mut drafts = use_signal(HashMap::<u32, String>::new);
+    let mut status = use_signal(String::new);
+
+    let _ = revision();
+    let identities = load_identity_db().get_all();
+
+    // Apply a change to the stored database and refresh the list
+    let mut update = move |change: &mut dyn FnMut(&mut recognition::IdentityDatabase) -> anyhow::Result<String>| {
+        let mut db = load_identity_db();
+        match change(&mut db) {
+            Ok(message) => match save_identity_db(&db) {
+                Ok(()) => {
+                    status.set(format!("✓ {}", message));
+                    revision += 1;
+                }
+                Err(e) => status.set(format!("✗ Not saved: {}", e)),
+            },
+            Err(e) => status.set(format!("✗ {}", e)),
+        }
+    };
+
+    let merge = move |_| {
+        let selected = merge_selection();
+        let &[a, b] = selected.as_slice() else {
+            status.set(String::from("✗ Select exactly two identities to merge"));
+            return;
+        };
+        let mut event_log = load_event_log();
+        update(&mut |db| {
+            let kept = db.merge(a, b, &mut event_log)?;
+            Ok(format!("Merged into #{}", kept))
+        });
+        save_event_log(&event_log);
+        merge_selection.set(Vec::new());
+    };
+
+    rsx! {
+        div { class: "card registration-form", style: "margin-top: 16px;",
+            h2 { "Manage Identities" }
+            if identities.is_empty() {
+                p { class: "muted", "No identities registered" }
+            }
+            for identity in identities {
+                div { class: "identity-row", key: "{identity.id}",
+                    div { class: "controls",
+                        input {
+                            r#type: "checkbox",
+                            title: "Select for merge",
+                            checked: merge_selection().contains(&identity.id),
+                            onchange: move |_| {
+                                let mut selected = merge_selection.write();
+                                match selected.iter().position(|id| *id == identity.id) {
+                                    Some(i) => { selected.remove(i); }
+                                    None => selected.push(identity.id),
+                                }
+                            },
+                        }
+                        input {
+                            r#type: "text",
+                            value: "{drafts().get(&identity.id).cloned().unwrap_or(identity.name.clone())}",
+                            oninput: move |e| { drafts.write().insert(identity.id, e.value()); },
+                        }
+                        button {
+                            class: "secondary",
+                            onclick: move |_| {
+                                let Some(name) = drafts.write().remove(&identity.id) else { return };
+                                update(&mut |db| {
+                                    db.rename(identity.id, name.clone())?;
+                                    Ok(format!("Renamed to {}", name.trim()))
+                                });
+                            },
+                            "Rename"
+                        }
+                        button {
+                            class: "secondary",
+                            onclick: move |_| {
+                                update(&mut |db| {
+                                    let removed = db
+                                        .remove_identity(identity.id)
+                                        .ok_or_else(|| anyhow::anyhow!("identity #{} not found", identity.id))?;
+                                    Ok(format!("Deleted {}", removed.name))
+                                });
+                            },
+                            "Delete"
+                        }
+                        select {
+                            onchange: move |e| {
+                                let Some(r) = role_from_label(&e.value()) else { return };
+                                update(&mut |db| {
+                                    let mut metadata = db.get(identity.id).map(|i| i.metadata.clone()).unwrap_or_default();
+                                    metadata.role = r;
+                                    db.set_metadata(identity.id, metadata)?;
+                                    Ok(format!("Role set to {}", r.label()))
+                                });
+                            },
+                            for r in recognition::IdentityRole::ALL {
+                                option { value: "{r.label()}", selected: identity.metadata.role == r, "{r.label()}" }
+                            }
+                        }
+                        span { class: "muted", "#{identity.id} · {identity.samples.len()} samples" }
+                    }
+                    if !identity.metadata.tags.is_empty() || !identity.metadata.notes.is_empty() {
+                        p { class: "muted", style: "margin: 6px 0 0 0; font-size: 12px;",
+                            "{metadata_summary(&identity.metadata)}"
+                        }
+                    }
+                    div { class: "sample-strip",
+                        for sample in identity.samples.clone() {
+                            img {
+                                key: "{sample.id}",
+                                class: if split_selection().is_some_and(|(id, s)| id == identity.id && s.contains(&sample.id)) { "selected" } else { "" },
+                                src: "{sample.thumbnail.clone().unwrap_or_default()}",
+                                alt: "Sample {sample.id}",
+                                title: "Select to split off",
+                                onclick: move |_| {
+                                    let mut selection = split_selection.write();
+                                    match selection.as_mut() {
+                                        Some((id, selected)) if *id == identity.id => {
+                                            match selected.iter().position(|s| *s == sample.id) {
+                                                Some(i) => { selected.remove(i); }
+                                                None => selected.push(sample.id),
+                                            }
+                                        }
+                                        _ => *selection = Some((identity.id, vec![sample.id])),
+                                    }
+                                },
+                            }
+                        }
+                    }
+                    if split_selection().is_some_and(|(id, s)| id == identity.id && !s.is_empty()) {
+                        button {
+                            class: "secondary",
+                            onclick: move |_| {
+                                let Some((id, selected)) = split_selection() else { return };
+                                update(&mut |db| {
+                                    let name = db.get(id).map(|i| format!("{} (split)", i.name)).unwrap_or_default();
+                                    let created = db.split(id, &selected, name)?;
+                                    Ok(format!("Split {} samples into #{}", selected.len(), created.id))
+                                });
+                                split_selection.set(None);
+                            },
+                            "Split selected samples into new identity"
+                        }
+                    }
+                }
+            }
+            div { class: "controls",
+                button { onclick: merge, "Merge Selected" }
+                if !status().is_empty() {
+                    span { class: "pill", "{status()}" }
+                }
+            }
         }
     }
 }
@@ -753,11 +1305,88 @@ enum EventFilter {
     Unknowns,
 }
 
+/// One collapsed run of events, formatted for the timeline
+struct TimelineEntry {
+    id: u32,
+    time: String,
+    name: String,
+    identity_id: Option<u32>,
+    detail: String,
+    snapshot: Option<String>,
+    /// Face crop data URL, loaded from the store
+    thumbnail: Option<String>,
+}
+
+impl TimelineEntry {
+    fn new(run: events::EventRun) -> Self {
+        let mut detail = format!("{:?} · conf {:.2}", run.last.event_type, run.last.confidence);
+        if run.count > 1 {
+            detail.push_str(&format!(" · ×{} since {}", run.count, local_time(run.first.timestamp).time_label()));
+        }
+        Self {
+            id: run.last.id,
+            time: local_time(run.last.timestamp).time_label(),
+            name: run.last.name,
+            identity_id: run.last.identity_id,
+            detail,
+            thumbnail: run
+                .snapshot
+                .as_ref()
+                .and_then(|id| with_store(|store| store.get_snapshot(id)).ok().flatten()),
+            snapshot: run.snapshot,
+        }
+    }
+}
+
 #[component]
 fn EventsPage() -> Element {
     let mut filter = use_signal(|| EventFilter::All);
+    // Identity whose history is shown, all people when unset
+    let mut person = use_signal::<Option<u32>>(|| None);
+    let mut page = use_signal(|| 0usize);
+    let mut viewing = use_signal::<Option<String>>(|| None);
+
     let event_log = load_event_log();
-    let events_data = event_log.get_all();
+    let identities = load_identity_db().get_all();
+
+    let events_data: Vec<_> = filter_events(filter(), &event_log.get_all())
+        .into_iter()
+        .filter(|e| person().is_none() || e.identity_id == person())
+        .collect();
+    let mut runs = events::collapse_runs(&events_data, COLLAPSE_GAP_MS);
+    runs.sort_by_key(|run| std::cmp::Reverse(run.last.timestamp));
+    let page_count = runs.len().div_ceil(EVENTS_PAGE_SIZE).max(1);
+    let current_page = page().min(page_count - 1);
+    let page_runs: Vec<_> = runs
+        .into_iter()
+        .skip(current_page * EVENTS_PAGE_SIZE)
+        .take(EVENTS_PAGE_SIZE)
+        .collect();
+    let days: Vec<_> = events::group_by_day_hour(page_runs, utc_offset_minutes)
+        .into_iter()
+        .map(|day| {
+            let heading = format!("{} · {} events", day.date, day.event_count());
+            let hours: Vec<_> = day
+                .hours
+                .into_iter()
+                .map(|hour| (format!("{:02}:00", hour.hour), hour.runs.into_iter().map(TimelineEntry::new).collect::<Vec<_>>()))
+                .collect();
+            (heading, hours)
+        })
+        .collect();
+
+    let history = person().and_then(|id| {
+        let identity = identities.iter().find(|i| i.id == id)?;
+        let summary = match event_log.last_seen(id) {
+            Some(event) => format!(
+                "Last seen {} · {} events",
+                local_time(event.timestamp),
+                event_log.for_identity(id).len()
+            ),
+            None => String::from("Not seen yet"),
+        };
+        Some((identity.name.clone(), summary))
+    });
 
     rsx! {
         div { class: "page",
@@ -775,34 +1404,202 @@ fn EventsPage() -> Element {
                                 "Unknowns" => filter.set(EventFilter::Unknowns),
                                 _ => {}
                             }
+                            page.set(0);
                         },
                         option { value: "All", selected: filter() == EventFilter::All, "All Events" }
                         option { value: "Alerts", selected: filter() == EventFilter::Alerts, "Alerts" }
                         option { value: "Unknowns", selected: filter() == EventFilter::Unknowns, "Unknown Faces" }
                     }
+                    label { "Person:" }
+                    select {
+                        onchange: move |e| {
+                            person.set(e.value().parse().ok());
+                            page.set(0);
+                        },
+                        option { value: "", selected: person().is_none(), "Everyone" }
+                        for identity in identities.iter() {
+                            option {
+                                key: "{identity.id}",
+                                value: "{identity.id}",
+                                selected: person() == Some(identity.id),
+                                "{identity.name}"
+                            }
+                        }
+                    }
                 }
-                
-                ul { class: "list",
-                    for event in filter_events(filter(), &events_data).iter().take(50) {
-                        li { 
-                            "#{event.id} · {event.name} · conf {event.confidence:.2} · {event.timestamp}"
+
+                if let Some((name, summary)) = history {
+                    div { style: "margin: 12px 0;",
+                        h3 { style: "margin: 0;", "{name}" }
+                        p { class: "muted", style: "margin: 4px 0 0 0; font-size: 13px;", "{summary}" }
+                    }
+                }
+
+                if let Some(snapshot) = viewing() {
+                    div { style: "margin: 12px 0;",
+                        img { src: "{snapshot}", style: "max-width: 100%; max-height: 320px; border-radius: 10px;" }
+                        div { class: "controls",
+                            button { class: "secondary", onclick: move |_| viewing.set(None), "Close" }
                         }
                     }
-                    if filter_events(filter(), &events_data).is_empty() {
-                        li { class: "muted", "No events for this filter" }
+                }
+
+                for (heading, hours) in days {
+                    div { key: "{heading}", style: "margin-top: 12px;",
+                        h3 { style: "margin: 0 0 6px 0;", "{heading}" }
+                        for (hour, entries) in hours {
+                            div { key: "{hour}",
+                                p { class: "muted", style: "margin: 6px 0; font-size: 12px;", "{hour}" }
+                                ul { class: "list", style: "max-height: none;",
+                                    for entry in entries {
+                                        li { key: "{entry.id}",
+                                            "{entry.time} · "
+                                            if let Some(id) = entry.identity_id {
+                                                a {
+                                                    style: "cursor: pointer; text-decoration: underline;",
+                                                    onclick: move |_| {
+                                                        person.set(Some(id));
+                                                        page.set(0);
+                                                    },
+                                                    "{entry.name}"
+                                                }
+                                            } else {
+                                                "{entry.name}"
+                                            }
+                                            " · {entry.detail}"
+                                            if let (Some(snapshot_id), Some(thumbnail)) = (entry.snapshot, entry.thumbnail) {
+                                                img {
+                                                    class: "event-thumb",
+                                                    src: "{thumbnail}",
+                                                    title: "View snapshot",
+                                                    onclick: {
+                                                        let thumbnail = thumbnail.clone();
+                                                        move |_| {
+                                                            // Prefer the full frame when one was kept
+                                                            let frame_id = events::frame_snapshot_id(&snapshot_id);
+                                                            match with_store(|store| store.get_snapshot(&frame_id)) {
+                                                                Ok(Some(data_url)) => viewing.set(Some(data_url)),
+                                                                Ok(None) => viewing.set(Some(thumbnail.clone())),
+                                                                Err(e) => log!("Failed to load snapshot: {:#}", e),
+                                                            }
+                                                        }
+                                                    },
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                if events_data.is_empty() {
+                    p { class: "muted", "No events for this filter" }
+                }
+
+                if page_count > 1 {
+                    div { class: "controls", style: "margin-top: 12px;",
+                        button {
+                            class: "secondary",
+                            disabled: current_page == 0,
+                            onclick: move |_| page.set(current_page.saturating_sub(1)),
+                            "Newer"
+                        }
+                        span { class: "muted", "Page {current_page + 1} of {page_count}" }
+                        button {
+                            class: "secondary",
+                            disabled: current_page + 1 >= page_count,
+                            onclick: move |_| page.set(current_page + 1),
+                            "Older"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn UnlockScreen(locked: Signal<bool>) -> Element {
+    let mut passphrase = use_signal(String::new);
+    let mut status = use_signal(String::new);
+
+    let unlock = move |_| {
+        match with_store(|store| store.unlock(&passphrase())) {
+            Ok(()) => {
+                // Finish sealing records if encrypting was interrupted
+                if with_store(|store| store.is_sealing()) {
+                    match with_store(|store| store.finish_sealing()) {
+                        Ok(count) => log!("Finished encrypting {} records", count),
+                        Err(e) => log!("Encrypting stored data is still unfinished: {:#}", e),
+                    }
+                }
+                passphrase.set(String::new());
+                locked.set(false);
+            }
+            Err(e) => status.set(format!("✗ {:#}", e)),
+        }
+    };
+
+    rsx! {
+        div { class: "page",
+            div { class: "card registration-form",
+                h2 { "Storage Locked" }
+                p { class: "muted", "Identities and snapshots are encrypted. Enter the admin passphrase to continue." }
+                div { class: "form-group",
+                    label { "Passphrase" }
+                    input {
+                        r#type: "password",
+                        value: "{passphrase()}",
+                        oninput: move |e| passphrase.set(e.value()),
                     }
                 }
+                div { class: "controls",
+                    button { onclick: unlock, "Unlock" }
+                }
+                if !status().is_empty() {
+                    p { class: "muted", "{status()}" }
+                }
             }
         }
     }
 }
 
 #[component]
-fn Settings() -> Element {
+fn Settings(locked: Signal<bool>) -> Element {
     let db = load_identity_db();
     let log = load_event_log();
     let identity_count = db.get_all().len();
     let event_count = log.get_all().len();
+    let quarantined_keys = || with_store(|store| store.quarantined_keys()).unwrap_or_default();
+    let mut quarantined = use_signal(quarantined_keys);
+    let backend = with_store(|store| store.name());
+    let encrypted = with_store(|store| store.is_encrypted()).unwrap_or(false);
+    let mut passphrase = use_signal(String::new);
+    let mut confirm = use_signal(String::new);
+    let mut crypto_status = use_signal(String::new);
+    // Background IndexedDB writes finish outside Dioxus, so poll them
+    let mut pending_writes = use_signal(store::pending_writes);
+    let mut failed_writes = use_signal(store::failed_writes);
+    let mut _write_poll = use_signal::<Option<Interval>>(|| None);
+    use_effect(move || {
+        _write_poll.set(Some(Interval::new(1000, move || {
+            pending_writes.set(store::pending_writes());
+            failed_writes.set(store::failed_writes());
+        })));
+    });
+
+    // Check the two passphrase fields agree before using them
+    let mut new_passphrase = move || -> Option<String> {
+        let value = passphrase();
+        if value.is_empty() || value != confirm() {
+            crypto_status.set(String::from("✗ Enter the same passphrase twice"));
+            return None;
+        }
+        passphrase.set(String::new());
+        confirm.set(String::new());
+        Some(value)
+    };
     
     rsx! {
         div { class: "page",
@@ -813,7 +1610,14 @@ fn Settings() -> Element {
                 ul { class: "list",
                     li { "Platform: WebAssembly (Dioxus)" }
                     li { "Version: 0.1.0-dev" }
-                    li { "Storage: LocalStorage (JSON)" }
+                    li { "Storage: {backend} (JSON, schema v{storage::SCHEMA_VERSION})" }
+                    li {
+                        if encrypted {
+                            "Encryption: XChaCha20-Poly1305, key from admin passphrase (Argon2id)"
+                        } else {
+                            "Encryption: off"
+                        }
+                    }
                     li { "Detection: Brightness-based (placeholder)" }
                     li { "Build Date: 2026-01-01" }
                 }
@@ -822,14 +1626,140 @@ fn Settings() -> Element {
                 ul { class: "list",
                     li { "Identities: {identity_count}" }
                     li { "Events Logged: {event_count}" }
+                    if pending_writes() > 0 {
+                        li { "Saving: {pending_writes()} writes in progress" }
+                    }
+                }
+
+                if !failed_writes().is_empty() {
+                    h3 { style: "margin-top: 16px;", "Unsaved Data" }
+                    p { class: "muted", style: "font-size: 13px;",
+                        "Saving these records failed (storage full or unavailable). They are kept for this session only and will be lost on reload; free up space and save them again."
+                    }
+                    ul { class: "list",
+                        for key in failed_writes() {
+                            li { key: "{key}", "{key}" }
+                        }
+                    }
+                }
+
+                if !quarantined().is_empty() {
+                    h3 { style: "margin-top: 16px;", "Unreadable Data" }
+                    p { class: "muted", style: "font-size: 13px;",
+                        "These stored blobs could not be read or migrated and were set aside instead of being discarded."
+                    }
+                    ul { class: "list",
+                        for key in quarantined() {
+                            li { key: "{key}",
+                                "{key} "
+                                button {
+                                    class: "secondary",
+                                    onclick: {
+                                        let key = key.clone();
+                                        move |_| {
+                                            let _ = with_store(|store| store.delete(&key));
+                                            quarantined.set(quarantined_keys());
+                                        }
+                                    },
+                                    "Discard"
+                                }
+                            }
+                        }
+                    }
                 }
                 
+                h3 { style: "margin-top: 16px;", "Encryption" }
+                p { class: "muted", style: "font-size: 13px;",
+                    if encrypted {
+                        "Changing the passphrase re-wraps the data key; rotating the key re-encrypts every stored record."
+                    } else {
+                        "Encrypt identities, samples, events and snapshots at rest. There is no recovery if the passphrase is lost."
+                    }
+                }
+                div { class: "form-group",
+                    label { if encrypted { "New Passphrase" } else { "Passphrase" } }
+                    input {
+                        r#type: "password",
+                        value: "{passphrase()}",
+                        oninput: move |e| passphrase.set(e.value()),
+                    }
+                    input {
+                        r#type: "password",
+                        placeholder: "Confirm",
+                        style: "margin-top: 8px;",
+                        value: "{confirm()}",
+                        oninput: move |e| confirm.set(e.value()),
+                    }
+                }
+                div { class: "controls",
+                    if encrypted {
+                        if with_store(|store| store.is_sealing()) {
+                            button {
+                                onclick: move |_| {
+                                    match with_store(|store| store.finish_sealing()) {
+                                        Ok(count) => crypto_status.set(format!("✓ Encrypted the remaining {} records", count)),
+                                        Err(e) => crypto_status.set(format!("✗ {:#}", e)),
+                                    }
+                                },
+                                "Finish Encrypting"
+                            }
+                        }
+                        button {
+                            onclick: move |_| {
+                                let Some(value) = new_passphrase() else { return };
+                                match with_store(|store| store.change_passphrase(&value)) {
+                                    Ok(()) => crypto_status.set(String::from("✓ Passphrase changed")),
+                                    Err(e) => crypto_status.set(format!("✗ {:#}", e)),
+                                }
+                            },
+                            "Change Passphrase"
+                        }
+                        button {
+                            class: "secondary",
+                            onclick: move |_| {
+                                match with_store(|store| store.rotate_key()) {
+                                    Ok(count) => crypto_status.set(format!("✓ Re-encrypted {} records with a new key", count)),
+                                    Err(e) => crypto_status.set(format!("✗ {:#}", e)),
+                                }
+                            },
+                            "Rotate Key"
+                        }
+                        button {
+                            class: "secondary",
+                            onclick: move |_| {
+                                with_store(|store| store.lock());
+                                locked.set(true);
+                            },
+                            "Lock Now"
+                        }
+                    } else {
+                        button {
+                            onclick: move |_| {
+                                let Some(value) = new_passphrase() else { return };
+                                match with_store(|store| store.enable(&value)) {
+                                    Ok(count) => crypto_status.set(format!("✓ Encrypted {} records", count)),
+                                    Err(e) => crypto_status.set(format!("✗ {:#}", e)),
+                                }
+                            },
+                            "Encrypt Stored Data"
+                        }
+                    }
+                }
+                if !crypto_status().is_empty() {
+                    p { class: "muted", "{crypto_status()}" }
+                }
+
                 h3 { style: "margin-top: 16px;", "Actions" }
                 div { class: "controls",
                     button { 
                         onclick: move |_| {
-                            let _ = LocalStorage::delete(IDENTITY_DB_KEY);
-                            let _ = LocalStorage::delete(EVENT_LOG_KEY);
+                            with_store(|store| {
+                                let _ = store.delete(storage::IDENTITIES_KEY);
+                                let _ = store.delete(storage::EVENTS_KEY);
+                                for id in store.snapshot_ids().unwrap_or_default() {
+                                    let _ = store.delete_snapshot(&id);
+                                }
+                            });
                             log!("Database cleared");
                         },
                         class: "secondary",
@@ -849,39 +1779,111 @@ fn filter_events(filter: EventFilter, events: &[events::FaceEvent]) -> Vec<event
             EventFilter::All => true,
             EventFilter::Alerts => {
                 event.confidence < 0.4
-                    || matches!(event.event_type, events::EventType::Blacklisted | events::EventType::AfterHours)
+                    || matches!(
+                        event.event_type,
+                        events::EventType::LowConfidence | events::EventType::Blacklisted | events::EventType::AfterHours
+                    )
             }
             EventFilter::Unknowns => event.event_type == events::EventType::UnknownFace,
         })
         .collect()
 }
 
+fn metadata_summary(metadata: &recognition::IdentityMetadata) -> String {
+    let tags = metadata.tags.iter().map(|t| format!("#{}", t)).collect::<Vec<_>>().join(" ");
+    [tags, metadata.notes.clone()].into_iter().filter(|s| !s.is_empty()).collect::<Vec<_>>().join(" · ")
+}
+
+fn role_from_label(label: &str) -> Option<recognition::IdentityRole> {
+    recognition::IdentityRole::ALL.into_iter().find(|r| r.label() == label)
+}
+
+/// Minutes east of UTC in the browser's time zone at `timestamp`
+fn utc_offset_minutes(timestamp: u64) -> i32 {
+    -(js_sys::Date::new(&JsValue::from_f64(timestamp as f64)).get_timezone_offset() as i32)
+}
+
+fn local_time(timestamp: u64) -> events::LocalTime {
+    events::LocalTime::from_timestamp(timestamp, utc_offset_minutes(timestamp))
+}
+
+/// `YYYY-MM-DD` from a date input to ms since the epoch (UTC midnight)
+fn parse_date(value: &str) -> Option<u64> {
+    if value.is_empty() {
+        return None;
+    }
+    let ms = js_sys::Date::parse(value);
+    (!ms.is_nan()).then_some(ms as u64)
+}
+
+fn shared_clock() -> clock::SharedClock {
+    CLOCK.with(|clock| clock.clone())
+}
+
+/// Milliseconds since the epoch from the shared clock
+fn now_ms() -> u64 {
+    CLOCK.with(|clock| clock.now_ms())
+}
+
 // Persistence helpers
+fn with_store<R>(f: impl FnOnce(&mut AppStore) -> R) -> R {
+    STORE.with(|slot| f(&mut slot.borrow_mut()))
+}
+
 fn load_identity_db() -> recognition::IdentityDatabase {
-    LocalStorage::get(IDENTITY_DB_KEY).unwrap_or_else(|_| {
-        log!("Creating new identity database");
-        recognition::IdentityDatabase::new()
-    })
+    let loaded = with_store(|store| store.load_identities(now_ms()));
+    let mut db = loaded_or_default(loaded, "identity database", recognition::IdentityDatabase::new);
+    db.set_clock(shared_clock());
+    if !db.unindexed().is_empty() {
+        log!("✗ Identities {:?} have embeddings of the wrong size and can't be recognized", db.unindexed());
+    }
+    db
 }
 
-fn save_identity_db(db: &recognition::IdentityDatabase) {
-    if let Err(e) = LocalStorage::set(IDENTITY_DB_KEY, db) {
-        log!("Error saving identity database: {:?}", e);
-    } else {
-        log!("Identity database saved");
+fn save_identity_db(db: &recognition::IdentityDatabase) -> anyhow::Result<()> {
+    let saved = with_store(|store| store.save_identities(db));
+    match &saved {
+        Ok(()) => log!("Identity database saved"),
+        Err(e) => log!("Error saving identity database: {:#}", e),
     }
+    saved
 }
 
 fn load_event_log() -> events::EventLog {
-    LocalStorage::get(EVENT_LOG_KEY).unwrap_or_else(|_| {
-        log!("Creating new event log");
-        events::EventLog::new(1000)
-    })
+    let loaded = with_store(|store| store.load_events(now_ms()));
+    let mut event_log = loaded_or_default(loaded, "event log", || events::EventLog::new(1000));
+    event_log.set_clock(shared_clock());
+    event_log
 }
 
 fn save_event_log(event_log: &events::EventLog) {
-    if let Err(e) = LocalStorage::set(EVENT_LOG_KEY, event_log) {
-        log!("Failed to save event log: {:?}", e);
+    if let Err(e) = with_store(|store| store.save_events(event_log)) {
+        log!("Failed to save event log: {:#}", e);
+    }
+}
+
+/// Unwrap a load, reporting migrations and unreadable data (which the
+/// store has already set aside, see Settings)
+fn loaded_or_default<T>(
+    loaded: anyhow::Result<Option<storage::Loaded<T>>>,
+    what: &str,
+    default: impl FnOnce() -> T,
+) -> T {
+    match loaded {
+        Ok(Some(loaded)) => {
+            if let Some(version) = loaded.migrated_from {
+                log!("Migrated {} from schema version {}", what, version);
+            }
+            loaded.payload
+        }
+        Ok(None) => {
+            log!("Creating new {}", what);
+            default()
+        }
+        Err(e) => {
+            log!("✗ {:#}", e);
+            default()
+        }
     }
 }
 
@@ -928,7 +1930,24 @@ fn detect_faces_from_video(
         fn detect_faces_mediapipe(video_id: &str) -> JsValue;
     }
     
-    // Try MediaPipe first
+    // The local model works offline and gives landmarks, so try it first
+    let onnx = DETECTOR.with(|slot| {
+        let mut slot = slot.borrow_mut();
+        let detector = slot.as_mut()?;
+        let (_, frame) = grab_frame(video_id, canvas_id)?;
+        let failures = detector.failures();
+        let detections = detector.detect(&frame);
+        if detector.failures() > failures {
+            log!("ONNX detection failed: {}", detector.last_error().unwrap_or("unknown error"));
+            return None;
+        }
+        Some(detections)
+    });
+    if let Some(detections) = onnx {
+        return Some(detections);
+    }
+
+    // Then MediaPipe, if it loaded from the CDN
     let js_result = detect_faces_mediapipe(video_id);
     
     if !js_result.is_null() && !js_result.is_undefined() {
@@ -951,14 +1970,17 @@ fn detect_faces_from_video(
                             h_val.as_f64(),
                             score_val.as_f64(),
                         ) {
-                            detections.push(detection::FaceDetection::new(
+                            let mut det = detection::FaceDetection::new(
                                 i,
                                 x as f32,
                                 y as f32,
                                 w as f32,
                                 h as f32,
                                 score as f32,
-                            ));
+                            );
+                            det.landmarks = read_keypoints(&det_obj)
+                                .and_then(|kps| detection::five_point_from_blazeface(&kps));
+                            detections.push(det);
                         }
                     }
                 }
@@ -974,38 +1996,31 @@ fn detect_faces_from_video(
     // Fallback to simple detection if MediaPipe not available
     log!("Falling back to simple edge detection");
     
-    let window = web_sys::window()?;
-    let document = window.document()?;
-
-    let video: HtmlVideoElement = document.get_element_by_id(video_id)?.dyn_into().ok()?;
-    let canvas: HtmlCanvasElement = document.get_element_by_id(canvas_id)?.dyn_into().ok()?;
-
-    let video_width = video.video_width();
-    let video_height = video.video_height();
-    
-    if video_width == 0 || video_height == 0 {
-        return None;
-    }
+    let (_, frame) = grab_frame(video_id, canvas_id)?;
 
-    canvas.set_width(video_width);
-    canvas.set_height(video_height);
+    let detections = detection::EdgeDensityDetector::default().detect(&frame);
+    log!("Detection: final {} face regions detected", detections.len());
+    Some(detections)
+}
 
-    let ctx: CanvasRenderingContext2d = canvas
-        .get_context("2d")
-        .ok()??
+fn read_keypoints(det_obj: &js_sys::Object) -> Option<Vec<(f32, f32)>> {
+    let keypoints: js_sys::Array = js_sys::Reflect::get(det_obj, &JsValue::from_str("keypoints"))
+        .ok()?
         .dyn_into()
         .ok()?;
 
-    ctx.draw_image_with_html_video_element(&video, 0.0, 0.0).ok()?;
-    let image_data = ctx.get_image_data(0.0, 0.0, video_width as f64, video_height as f64).ok()?;
-
-    Some(simple_face_detection(&image_data, video_width, video_height))
+    keypoints
+        .iter()
+        .map(|kp| {
+            let x = js_sys::Reflect::get(&kp, &JsValue::from_str("x")).ok()?.as_f64()?;
+            let y = js_sys::Reflect::get(&kp, &JsValue::from_str("y")).ok()?.as_f64()?;
+            Some((x as f32, y as f32))
+        })
+        .collect()
 }
 
-fn capture_face_from_video(
-    video_id: &str,
-    canvas_id: &str,
-) -> Option<String> {
+/// Draw the current video frame onto `canvas_id` and read it back
+fn grab_frame(video_id: &str, canvas_id: &str) -> Option<(HtmlCanvasElement, Frame)> {
     let window = web_sys::window()?;
     let document = window.document()?;
 
@@ -1029,203 +2044,115 @@ fn capture_face_from_video(
         .ok()?;
 
     ctx.draw_image_with_html_video_element(&video, 0.0, 0.0).ok()?;
-
-    canvas.to_data_url().ok()
+    let image_data = ctx.get_image_data(0.0, 0.0, video_width as f64, video_height as f64).ok()?;
+    let frame = frame_from_image_data(&image_data)?;
+    Some((canvas, frame))
 }
 
-fn simple_face_detection(
-    image_data: &ImageData,
-    width: u32,
-    height: u32,
-) -> Vec<detection::FaceDetection> {
-    let data = image_data.data();
-    let mut detections = Vec::new();
-    let mut detection_id = 1;
-
-    let grid_size = 8;  // Coarser grid for larger region detection
-    let step_x = (width / grid_size).max(1);
-    let step_y = (height / grid_size).max(1);
-
-    log!("Detection: scanning {}x{} grid ({}x{} pixels per cell) for edge density", grid_size, grid_size, step_x, step_y);
-
-    let mut edge_cells = Vec::new();
-    let mut max_density = 0.0_f32;
-    let mut densities = Vec::new();
-
-    for gy in 0..grid_size {
-        for gx in 0..grid_size {
-            let x = (gx * step_x) as u32;
-            let y = (gy * step_y) as u32;
-            let w = step_x;
-            let h = step_y;
-
-            // Calculate edge density (how many high-contrast transitions)
-            let edge_density = calculate_edge_density(&data, x, y, w, h, width);
-            densities.push(edge_density);
-            max_density = max_density.max(edge_density);
-            
-            // Faces have features (eyes, nose, mouth) = high edge density
-            // Uniform areas (wall, plain background) = low edge density
-            if edge_density > 0.04 {  // Lowered from 0.08
-                edge_cells.push((gx, gy, edge_density));
-            }
-        }
-    }
-
-    let avg_density = if !densities.is_empty() { densities.iter().sum::<f32>() / densities.len() as f32 } else { 0.0 };
-    log!("Detection: found {} cells with edge density > 0.04 (avg={:.3}, max={:.3})", edge_cells.len(), avg_density, max_density);
-
-    // Group connected high-edge cells into face regions
-    let mut visited = std::collections::HashSet::new();
-    
-    for (gx, gy, density) in edge_cells.iter() {
-        if visited.contains(&(*gx, *gy)) {
-            continue;
-        }
-
-        // Flood fill to find connected region
-        let mut region = Vec::new();
-        let mut queue = vec![(*gx, *gy)];
-        
-        while let Some((cx, cy)) = queue.pop() {
-            if visited.contains(&(cx, cy)) || cx >= grid_size || cy >= grid_size {
-                continue;
-            }
-            visited.insert((cx, cy));
-            region.push((cx, cy));
-
-            // Check 4 neighbors
-            for (dx, dy) in &[(-1, 0), (1, 0), (0, -1), (0, 1)] {
-                let nx = (cx as i32 + dx) as u32;
-                let ny = (cy as i32 + dy) as u32;
-                if nx < grid_size && ny < grid_size && !visited.contains(&(nx, ny)) {
-                    // Check if neighbor is also high edge
-                    if edge_cells.iter().any(|(gx, gy, _)| *gx == nx && *gy == ny) {
-                        queue.push((nx, ny));
-                    }
-                }
-            }
-        }
-
-        // If region is large enough, treat as a face
-        if region.len() >= 2 {
-            let min_x = region.iter().map(|(x, _)| x).min().unwrap();
-            let min_y = region.iter().map(|(_, y)| y).min().unwrap();
-            let max_x = region.iter().map(|(x, _)| x).max().unwrap();
-            let max_y = region.iter().map(|(_, y)| y).max().unwrap();
-
-            let x = (*min_x * step_x) as f32;
-            let y = (*min_y * step_y) as f32;
-            let w = ((*max_x - *min_x + 1) * step_x) as f32;
-            let h = ((*max_y - *min_y + 1) * step_y) as f32;
-
-            let avg_density = region.iter()
-                .map(|(gx, gy)| edge_cells.iter().find(|(egx, egy, _)| *egx == *gx && *egy == *gy).map(|(_, _, d)| d).unwrap_or(&0.0))
-                .sum::<f32>() / region.len() as f32;
-            
-            let confidence = (avg_density * 1.5).min(0.95).max(0.4);
-
-            log!("Detection: found face region {} cells, density={:.3}, confidence={:.2}", region.len(), avg_density, confidence);
-
-            detections.push(detection::FaceDetection::new(
-                detection_id,
-                x,
-                y,
-                w,
-                h,
-                confidence,
-            ));
-            detection_id += 1;
-        }
+async fn fetch_model(url: &str) -> Result<Vec<u8>, JsValue> {
+    let window = web_sys::window().ok_or("no window")?;
+    let response: web_sys::Response = JsFuture::from(window.fetch_with_str(url)).await?.dyn_into()?;
+    if !response.ok() {
+        return Err(format!("{} returned {}", url, response.status()).into());
     }
-
-    log!("Detection: final {} face regions detected", detections.len());
-    detections
+    let buffer = JsFuture::from(response.array_buffer()?).await?;
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
 }
 
-fn calculate_edge_density(data: &[u8], x: u32, y: u32, w: u32, h: u32, img_width: u32) -> f32 {
-    let mut edge_count = 0;
-    let mut total_count = 0;
-
-    // Sample edges by looking at pixel differences (both horizontal and vertical)
-    for dy in 0..h.saturating_sub(1) {
-        for dx in 0..w.saturating_sub(1) {
-            let px = x + dx;
-            let py = y + dy;
+async fn load_detector() -> Result<(), JsValue> {
+    let bytes = fetch_model(DETECTION_MODEL_URL).await?;
+    let detector = detection::OnnxFaceDetector::from_bytes(
+        &bytes,
+        detection::FaceModel::BlazeFaceShortRange,
+        detection::OnnxDetectorConfig::default(),
+    )
+    .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    log!("Detection model loaded: {:?}", detector.kind());
+    DETECTOR.with(|slot| *slot.borrow_mut() = Some(detector));
+    Ok(())
+}
 
-            if px + 1 < img_width && py + 1 < img_width {
-                let idx1 = ((py * img_width + px) * 4) as usize;
-                let idx2_h = ((py * img_width + (px + 1)) * 4) as usize; // horizontal
-                let idx2_v = (((py + 1) * img_width + px) * 4) as usize; // vertical
+async fn load_embedder() -> Result<(), JsValue> {
+    let bytes = fetch_model(EMBEDDING_MODEL_URL).await?;
 
-                // Check horizontal edge
-                if idx1 + 2 < data.len() && idx2_h + 2 < data.len() {
-                    let b1 = (data[idx1] as f32 + data[idx1 + 1] as f32 + data[idx1 + 2] as f32) / 3.0;
-                    let b2 = (data[idx2_h] as f32 + data[idx2_h + 1] as f32 + data[idx2_h + 2] as f32) / 3.0;
+    let embedder = recognition::OnnxFaceEmbedder::from_bytes(
+        &bytes,
+        EMBEDDING_MODEL_NAME,
+        EMBEDDING_MODEL_VERSION,
+        recognition::OnnxEmbedderConfig::default(),
+    )
+    .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    log!("Embedding model loaded: {}", embedder.model());
+    EMBEDDER.with(|slot| *slot.borrow_mut() = Some(embedder));
+    Ok(())
+}
 
-                    if (b1 - b2).abs() > 15.0 {  // Lowered from 30.0
-                        edge_count += 1;
-                    }
-                    total_count += 1;
-                }
-                
-                // Check vertical edge
-                if idx1 + 2 < data.len() && idx2_v + 2 < data.len() {
-                    let b1 = (data[idx1] as f32 + data[idx1 + 1] as f32 + data[idx1 + 2] as f32) / 3.0;
-                    let b2 = (data[idx2_v] as f32 + data[idx2_v + 1] as f32 + data[idx2_v + 2] as f32) / 3.0;
+/// Align and embed one detection; `None` without landmarks or a loaded model
+fn embed_face(
+    frame: &Frame,
+    det: &detection::FaceDetection,
+) -> Option<(Vec<f32>, recognition::EmbeddingModel)> {
+    let face = alignment::align_face(frame, det.landmarks.as_deref()?).ok()?;
+    embed_aligned(&face)
+}
 
-                    if (b1 - b2).abs() > 15.0 {  // Lowered from 30.0
-                        edge_count += 1;
-                    }
-                    total_count += 1;
-                }
+/// Embed an already aligned crop; `None` until the model has loaded
+fn embed_aligned(face: &Frame) -> Option<(Vec<f32>, recognition::EmbeddingModel)> {
+    EMBEDDER.with(|slot| {
+        let mut slot = slot.borrow_mut();
+        let embedder = slot.as_mut()?;
+        match embedder.embed(face) {
+            Ok(embedding) => Some((embedding, embedder.model().clone())),
+            Err(e) => {
+                log!("Embedding failed: {}", e);
+                None
             }
         }
-    }
-
-    if total_count > 0 {
-        edge_count as f32 / total_count as f32
-    } else {
-        0.0
-    }
+    })
 }
 
-fn sample_brightness(data: &[u8], x: u32, y: u32, w: u32, h: u32, img_width: u32) -> f32 {
-    let mut total = 0.0;
-    let mut count = 0;
+/// Encode a frame as a JPEG data URL by way of a scratch canvas
+fn frame_to_data_url(frame: &Frame, canvas_id: &str) -> Option<String> {
+    let document = web_sys::window()?.document()?;
+    let canvas: HtmlCanvasElement = document.get_element_by_id(canvas_id)?.dyn_into().ok()?;
+    canvas.set_width(frame.width());
+    canvas.set_height(frame.height());
 
-    let sample_step = w / 4;
-    let sample_step = sample_step.max(1);
+    let ctx: CanvasRenderingContext2d = canvas
+        .get_context("2d")
+        .ok()??
+        .dyn_into()
+        .ok()?;
+    let rgba = frame.convert(PixelFormat::Rgba8).packed().into_data();
+    let image_data =
+        ImageData::new_with_u8_clamped_array_and_sh(Clamped(&rgba), frame.width(), frame.height()).ok()?;
+    ctx.put_image_data(&image_data, 0.0, 0.0).ok()?;
+    canvas.to_data_url_with_type("image/jpeg").ok()
+}
 
-    for dy in (0..h.min(20)).step_by(sample_step as usize) {
-        for dx in (0..w.min(20)).step_by(sample_step as usize) {
-            let px = x + dx;
-            let py = y + dy;
-            
-            if px < img_width && py < img_width {
-                let idx = ((py * img_width + px) * 4) as usize;
-                if idx + 2 < data.len() {
-                    let r = data[idx] as f32;
-                    let g = data[idx + 1] as f32;
-                    let b = data[idx + 2] as f32;
-                    total += (r + g + b) / 3.0 / 255.0;
-                    count += 1;
-                }
-            }
-        }
-    }
+fn frame_from_image_data(image_data: &ImageData) -> Option<Frame> {
+    Frame::from_rgba(
+        image_data.width(),
+        image_data.height(),
+        image_data.data().to_vec(),
+        now_ms(),
+    )
+    .ok()
+}
 
-    if count > 0 {
-        total / count as f32
-    } else {
-        0.0
-    }
+/// Identity name for recognized tracks, `#id` otherwise
+fn track_label(track: &tracking::Track, names: &HashMap<u32, String>) -> String {
+    track
+        .identity_id
+        .and_then(|id| names.get(&id).cloned())
+        .unwrap_or_else(|| format!("#{}", track.track_id))
 }
 
 fn draw_detections_and_tracks(
     video_id: &str,
     canvas_id: &str,
     tracks: &[tracking::Track],
+    names: &HashMap<u32, String>,
 ) {
     let window = match web_sys::window() {
         Some(w) => w,
@@ -1280,15 +2207,16 @@ fn draw_detections_and_tracks(
     ctx.set_font("12px monospace");
 
     for track in tracks {
-        let (x, y, w, h) = track.detection.bbox;
+        let (x, y, w, h) = track.predicted_bbox();
         
         ctx.stroke_rect(x as f64, y as f64, w as f64, h as f64);
         
-        let label = format!("#{} {:.0}%", track.track_id, track.detection.confidence * 100.0);
+        let label = format!("{} {:.0}%", track_label(track, names), track.detection.confidence * 100.0);
         let text_y = if y > 20.0 { y - 5.0 } else { y + h + 15.0 };
         
         ctx.set_fill_style_str("rgba(76, 175, 80, 0.9)");
-        ctx.fill_rect(x as f64, (text_y - 15.0) as f64, 80.0, 16.0);
+        let label_width = ctx.measure_text(&label).map(|m| m.width() + 6.0).unwrap_or(80.0);
+        ctx.fill_rect(x as f64, (text_y - 15.0) as f64, label_width.max(80.0), 16.0);
         
         ctx.set_fill_style_str("#ffffff");
         let _ = ctx.fill_text(&label, x as f64 + 3.0, text_y as f64);
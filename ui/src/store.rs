@@ -0,0 +1,224 @@
+//! Browser implementations of `faceguard_core::storage::Store`.
+
+use anyhow::{anyhow, Result};
+use faceguard_core::storage::Store;
+use gloo_storage::{LocalStorage, Storage};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbOpenDbRequest, IdbRequest, IdbTransactionMode};
+
+const DB_NAME: &str = "faceguard";
+const OBJECT_STORE: &str = "records";
+
+thread_local! {
+    // Background IndexedDB writes still in flight, and keys whose latest
+    // write failed (the in-memory copy is then newer than what's stored)
+    static PENDING_WRITES: Cell<usize> = const { Cell::new(0) };
+    static FAILED_WRITES: RefCell<BTreeSet<String>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+/// IndexedDB writes that haven't finished yet
+pub fn pending_writes() -> usize {
+    PENDING_WRITES.with(Cell::get)
+}
+
+/// Keys whose last IndexedDB write failed and will be lost on reload
+pub fn failed_writes() -> Vec<String> {
+    FAILED_WRITES.with(|failed| failed.borrow().iter().cloned().collect())
+}
+
+fn js_error(context: &str, err: JsValue) -> anyhow::Error {
+    anyhow!("{}: {:?}", context, err)
+}
+
+/// The original `window.localStorage` backend, limited to ~5MB
+pub struct LocalStorageStore;
+
+impl Store for LocalStorageStore {
+    fn name(&self) -> &'static str {
+        "LocalStorage"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        LocalStorage::raw().get_item(key).map_err(|e| js_error("reading LocalStorage", e))
+    }
+
+    fn put(&mut self, key: &str, value: &str) -> Result<()> {
+        LocalStorage::raw()
+            .set_item(key, value)
+            .map_err(|e| js_error("writing LocalStorage (quota exceeded?)", e))
+    }
+
+    fn delete(&mut self, key: &str) -> Result<()> {
+        LocalStorage::raw().remove_item(key).map_err(|e| js_error("removing from LocalStorage", e))
+    }
+
+    fn keys(&self) -> Result<Vec<String>> {
+        let raw = LocalStorage::raw();
+        let count = raw.length().map_err(|e| js_error("listing LocalStorage", e))?;
+        Ok((0..count).filter_map(|i| raw.key(i).ok().flatten()).collect())
+    }
+}
+
+/// IndexedDB backend. Everything is read into memory when it opens so the
+/// `Store` calls stay synchronous; writes update the cache and are sent to
+/// IndexedDB in the background, see `pending_writes` and `failed_writes`.
+pub struct IndexedDbStore {
+    db: IdbDatabase,
+    cache: HashMap<String, String>,
+}
+
+impl IndexedDbStore {
+    pub async fn open() -> Result<Self> {
+        let factory = web_sys::window()
+            .ok_or_else(|| anyhow!("no window"))?
+            .indexed_db()
+            .map_err(|e| js_error("IndexedDB unavailable", e))?
+            .ok_or_else(|| anyhow!("IndexedDB unavailable"))?;
+
+        let open: IdbOpenDbRequest = factory.open_with_u32(DB_NAME, 1).map_err(|e| js_error("opening IndexedDB", e))?;
+        let upgrade = wasm_bindgen::closure::Closure::once_into_js(move |event: web_sys::Event| {
+            let request: Option<IdbOpenDbRequest> = event.target().and_then(|t| t.dyn_into().ok());
+            if let Some(db) = request.and_then(|r| r.result().ok()).and_then(|r| r.dyn_into::<IdbDatabase>().ok()) {
+                let _ = db.create_object_store(OBJECT_STORE);
+            }
+        });
+        open.set_onupgradeneeded(Some(upgrade.unchecked_ref()));
+        let db: IdbDatabase = request_result(&open)
+            .await
+            .map_err(|e| js_error("opening IndexedDB", e))?
+            .dyn_into()
+            .map_err(|e| js_error("opening IndexedDB", e))?;
+
+        let store = db
+            .transaction_with_str(OBJECT_STORE)
+            .and_then(|tx| tx.object_store(OBJECT_STORE))
+            .map_err(|e| js_error("reading IndexedDB", e))?;
+        let keys = request_result(&store.get_all_keys().map_err(|e| js_error("reading IndexedDB", e))?)
+            .await
+            .map_err(|e| js_error("reading IndexedDB keys", e))?;
+        let values = request_result(&store.get_all().map_err(|e| js_error("reading IndexedDB", e))?)
+            .await
+            .map_err(|e| js_error("reading IndexedDB values", e))?;
+
+        let keys = js_sys::Array::from(&keys);
+        let values = js_sys::Array::from(&values);
+        let cache = keys
+            .iter()
+            .zip(values.iter())
+            .filter_map(|(k, v)| Some((k.as_string()?, v.as_string()?)))
+            .collect();
+
+        Ok(Self { db, cache })
+    }
+
+    fn write(&self, key: &str, value: Option<&str>) -> Result<()> {
+        let tx = self
+            .db
+            .transaction_with_str_and_mode(OBJECT_STORE, IdbTransactionMode::Readwrite)
+            .map_err(|e| js_error("writing IndexedDB", e))?;
+        let store = tx.object_store(OBJECT_STORE).map_err(|e| js_error("writing IndexedDB", e))?;
+        match value {
+            Some(value) => store.put_with_key(&JsValue::from_str(value), &JsValue::from_str(key)),
+            None => store.delete(&JsValue::from_str(key)),
+        }
+        .map_err(|e| js_error("writing IndexedDB", e))?;
+
+        // A failed request (quota, disk) aborts its transaction. Exactly one
+        // of complete/abort fires, so one closure serves both and is freed
+        // after that call.
+        PENDING_WRITES.with(|pending| pending.set(pending.get() + 1));
+        let written = key.to_string();
+        let finished = wasm_bindgen::closure::Closure::once_into_js(move |event: web_sys::Event| {
+            PENDING_WRITES.with(|pending| pending.set(pending.get().saturating_sub(1)));
+            if event.type_() == "complete" {
+                FAILED_WRITES.with(|failed| failed.borrow_mut().remove(&written));
+            } else {
+                web_sys::console::log_1(&format!("IndexedDB write of {} failed", written).into());
+                FAILED_WRITES.with(|failed| failed.borrow_mut().insert(written));
+            }
+        });
+        tx.set_oncomplete(Some(finished.unchecked_ref()));
+        tx.set_onabort(Some(finished.unchecked_ref()));
+        Ok(())
+    }
+}
+
+impl Store for IndexedDbStore {
+    fn name(&self) -> &'static str {
+        "IndexedDB"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.cache.get(key).cloned())
+    }
+
+    fn put(&mut self, key: &str, value: &str) -> Result<()> {
+        self.write(key, Some(value))?;
+        self.cache.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &str) -> Result<()> {
+        self.write(key, None)?;
+        self.cache.remove(key);
+        Ok(())
+    }
+
+    fn keys(&self) -> Result<Vec<String>> {
+        let mut keys: Vec<_> = self.cache.keys().cloned().collect();
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+/// Resolve once `request` succeeds, with its result
+async fn request_result(request: &IdbRequest) -> Result<JsValue, JsValue> {
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        let done = request.clone();
+        let onsuccess = wasm_bindgen::closure::Closure::once_into_js(move |_: web_sys::Event| {
+            let _ = resolve.call1(&JsValue::NULL, &done.result().unwrap_or(JsValue::UNDEFINED));
+        });
+        let onerror = wasm_bindgen::closure::Closure::once_into_js(move |_: web_sys::Event| {
+            let _ = reject.call1(&JsValue::NULL, &JsValue::from_str("IndexedDB request failed"));
+        });
+        request.set_onsuccess(Some(onsuccess.unchecked_ref()));
+        request.set_onerror(Some(onerror.unchecked_ref()));
+    });
+    JsFuture::from(promise).await
+}
+
+/// Prefer IndexedDB, carrying over anything still in LocalStorage the
+/// first time; fall back to LocalStorage where IndexedDB can't open
+pub async fn open_store() -> Box<dyn Store> {
+    match IndexedDbStore::open().await {
+        Ok(mut store) => {
+            if store.cache.is_empty() {
+                let legacy = LocalStorageStore;
+                let faceguard_keys = legacy
+                    .keys()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|k| k.starts_with("faceguard_"));
+                // Failed copies show up in Settings; LocalStorage keeps the
+                // originals either way
+                for key in faceguard_keys {
+                    let copied = legacy
+                        .get(&key)
+                        .and_then(|value| value.map_or(Ok(()), |value| store.put(&key, &value)));
+                    if let Err(e) = copied {
+                        web_sys::console::log_1(&format!("Copying {} to IndexedDB failed: {:#}", key, e).into());
+                        FAILED_WRITES.with(|failed| failed.borrow_mut().insert(key));
+                    }
+                }
+            }
+            Box::new(store)
+        }
+        Err(e) => {
+            web_sys::console::log_1(&format!("Using LocalStorage: {:#}", e).into());
+            Box::new(LocalStorageStore)
+        }
+    }
+}